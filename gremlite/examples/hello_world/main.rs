@@ -53,9 +53,9 @@ fn main() -> Result<()> {
 
     txn.commit()?;
 
-    let (vs, es) = graph.write_traversal(|g, mut txn| {
-        let vs = g.v(()).to_list(&mut txn)?;
-        let es = g.e(()).to_list(&mut txn)?;
+    let (vs, es) = graph.write_traversal(|g, txn| {
+        let vs = g.v(()).to_list(txn)?;
+        let es = g.e(()).to_list(txn)?;
         Ok((vs, es))
     })?;
     for v in vs {
@@ -13,6 +13,9 @@ pub enum Error {
     #[error("not found {0:?}")]
     NotFound(Id),
 
+    #[error("already exists {0:?}")]
+    AlreadyExists(Id),
+
     #[error("value not found")]
     ValueNotFound,
 
@@ -22,12 +25,36 @@ pub enum Error {
     #[error("invalid edge")]
     EdgeInvalid,
 
+    #[error("vertex {0:?} still has incident edges")]
+    IncidentEdges(Id),
+
+    #[error("corrupt record {1:?} in {0}")]
+    Corrupt(&'static str, Option<Id>),
+
+    #[error("{0} has {1} records but its index has {2}")]
+    CountMismatch(&'static str, usize, usize),
+
+    #[error("graph has schema version {0}, newer than the {1} this version can open")]
+    Schema(u32, u32),
+
+    #[error("no side effect {0:?}")]
+    SideEffect(String),
+
+    #[error("math error: {0}")]
+    Math(String),
+
     #[error("empty traversal")]
     EmptyTraversal,
 
+    #[error("traversal writes to the graph but was given a read transaction")]
+    WriteInReadTxn,
+
     #[error("error with serialization {0}")]
     Postcard(#[from] postcard::Error),
 
+    #[error("error with key encoding {0}")]
+    Key(String),
+
     #[error("ulid decode error {0}")]
     Ulid(DecodeError),
 
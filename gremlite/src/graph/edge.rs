@@ -5,11 +5,29 @@ use super::{
     parameter::{FromPValue, PValue, ToPValue},
     Id, Writable,
 };
-use crate::error::{Error, Result};
+use crate::{
+    error::{Error, Result},
+    heed::codec::{from_key, to_key},
+};
 use heed::{BytesDecode, BytesEncode};
 use std::{borrow::Cow, clone::Clone, collections::HashMap, convert::TryInto};
 
-#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Direction {
+    Out,
+    In,
+    Both,
+}
+
+/// The orders the hexstore keeps every edge in. `T` and `F` are the ids of the vertices the
+/// edge goes to and from, and `E` is the edge's own id.
+///
+/// Every key also holds the edge's label, just before `E` in the vertex-first orders and
+/// just after it in the edge-first ones. So once the ids ahead of it are known, a label
+/// narrows a prefix scan further, as in edges with some label from a vertex (`FET`) or
+/// between two vertices (`FTE`).
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
 pub enum HexOrder {
     TFE,
     FTE,
@@ -19,7 +37,6 @@ pub enum HexOrder {
     FET,
 }
 
-#[allow(dead_code)]
 pub static ORDERS: [HexOrder; 6] = [
     HexOrder::TFE,
     HexOrder::FTE,
@@ -29,18 +46,60 @@ pub static ORDERS: [HexOrder; 6] = [
     HexOrder::FET,
 ];
 
+/// The fields of a key in each `HexOrder`, with the label `L`. Its variants are in the same
+/// order as `HexOrder`'s, so a key starts with the encoding of its order followed by its
+/// fields.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Serialize, Deserialize)]
+enum HexKey<L> {
+    TFE(Id, Id, L, Id),
+    FTE(Id, Id, L, Id),
+    ETF(Id, L, Id, Id),
+    EFT(Id, L, Id, Id),
+    TEF(Id, L, Id, Id),
+    FET(Id, L, Id, Id),
+}
+
 impl HexOrder {
-    #[allow(dead_code)]
-    pub(crate) fn to_db(&self, id: Id, to: Id, from: Id) -> Result<Vec<u8>> {
-        let order = match self {
-            Self::TFE => (Self::TFE, to, from, id),
-            Self::FTE => (Self::FTE, from, to, id),
-            Self::ETF => (Self::ETF, id, to, from),
-            Self::EFT => (Self::EFT, id, from, to),
-            Self::TEF => (Self::TEF, to, id, from),
-            Self::FET => (Self::FET, from, id, to),
+    pub(crate) fn to_db<L: Writable>(self, id: Id, to: Id, from: Id, label: L) -> Result<Vec<u8>> {
+        let key = match self {
+            Self::TFE => HexKey::TFE(to, from, label, id),
+            Self::FTE => HexKey::FTE(from, to, label, id),
+            Self::ETF => HexKey::ETF(id, label, to, from),
+            Self::EFT => HexKey::EFT(id, label, from, to),
+            Self::TEF => HexKey::TEF(to, label, id, from),
+            Self::FET => HexKey::FET(from, label, id, to),
         };
-        Ok(to_stdvec(&order)?)
+        to_key(&key)
+    }
+
+    /// Encodes the leading `ids` of a key in this order, then the `label` if it's given,
+    /// for use as a prefix scan. The label only follows all of the ids ahead of it.
+    pub(crate) fn prefix_to_db<L: Writable>(
+        self,
+        label: Option<&L>,
+        ids: &[Id],
+    ) -> Result<Vec<u8>> {
+        let mut prefix = to_key(&self)?;
+        for id in ids {
+            prefix.extend(to_key(id)?);
+        }
+        if let Some(label) = label {
+            prefix.extend(to_key(label)?);
+        }
+        Ok(prefix)
+    }
+
+    /// Decodes a key written by `to_db`, returning `(id, to, from)`.
+    pub(crate) fn from_db<L: Writable>(key: &[u8]) -> Result<(Id, Id, Id)> {
+        Ok(match from_key(key)? {
+            HexKey::<L>::TFE(to, from, _, id)
+            | HexKey::FTE(from, to, _, id)
+            | HexKey::ETF(id, _, to, from)
+            | HexKey::EFT(id, _, from, to)
+            | HexKey::TEF(to, _, id, from)
+            | HexKey::FET(from, _, id, to) => (id, to, from),
+        })
     }
 }
 
@@ -84,6 +143,33 @@ where
     pub fn get_label(&self) -> E {
         self.label.clone()
     }
+
+    pub const fn get_id(&self) -> Option<Id> {
+        self.id
+    }
+
+    /// The vertex this edge points to, its in-vertex.
+    pub const fn to(&self) -> Id {
+        self.to
+    }
+
+    /// The vertex this edge comes from, its out-vertex.
+    pub const fn from(&self) -> Id {
+        self.from
+    }
+
+    pub fn get_param(&self, key: &P) -> Option<&PValue<V, E, P>> {
+        self.parameters.get(key)
+    }
+
+    pub const fn params(&self) -> &HashMap<P, PValue<V, E, P>> {
+        &self.parameters
+    }
+
+    pub fn set_param(mut self, p: P, val: PValue<V, E, P>) -> Self {
+        self.parameters.insert(p, val);
+        self
+    }
 }
 
 impl<V, E, P> FromPValue<V, E, P> for Edge<V, E, P>
@@ -120,10 +206,7 @@ where
     type EItem = Self;
 
     fn bytes_encode(item: &'a Self::EItem) -> Option<std::borrow::Cow<'a, [u8]>> {
-        match to_stdvec(item).ok() {
-            Some(vec) => Some(Cow::Owned(vec)),
-            None => None,
-        }
+        to_stdvec(item).ok().map(Cow::Owned)
     }
 }
 
@@ -166,6 +249,12 @@ mod tests {
         let e = Edge::<String, String, String>::new(&v1, &v2, "e".into())?;
 
         assert_eq!(e.get_label(), "e");
+        assert_eq!(e.get_id(), None);
+        assert_eq!(e.to(), Id::nil(Type::Vertex));
+        assert_eq!(e.from(), Id::max(Type::Vertex));
+        let e = e.set_param("weight".into(), PValue::Double(0.5));
+        assert_eq!(e.get_param(&"weight".into()), Some(&PValue::Double(0.5)));
+        assert_eq!(e.params().len(), 1);
 
         let pvalue = e.to_pvalue();
         let decoded = Edge::from_pvalue(pvalue)?;
@@ -194,10 +283,12 @@ mod tests {
             Id::nil(Type::Edge),
             Id::nil(Type::Vertex),
             Id::max(Type::Vertex),
+            "e".to_string(),
         )?;
-        let expected = to_stdvec(&(
+        let expected = to_key(&(
             HexOrder::EFT,
             Id::nil(Type::Edge),
+            "e".to_string(),
             Id::max(Type::Vertex),
             Id::nil(Type::Vertex),
         ))?;
@@ -206,4 +297,40 @@ mod tests {
 
         Ok(())
     }
+
+    #[rstest]
+    fn test_hex_order_round_trip() -> Result<()> {
+        let (id, to, from) = (
+            Id::nil(Type::Edge),
+            Id::nil(Type::Vertex),
+            Id::max(Type::Vertex),
+        );
+        let label = "e".to_string();
+        for order in ORDERS.iter() {
+            let key = order.to_db(id, to, from, label.clone())?;
+            assert_eq!(HexOrder::from_db::<String>(&key)?, (id, to, from));
+
+            let prefix = order.prefix_to_db::<String>(None, &[])?;
+            assert!(key.starts_with(&prefix));
+        }
+
+        let key = HexOrder::FTE.to_db(id, to, from, label.clone())?;
+        let prefix = |label: Option<&str>, ids: &[Id]| {
+            HexOrder::FTE.prefix_to_db(label.map(String::from).as_ref(), ids)
+        };
+        assert!(key.starts_with(&prefix(None, &[from])?));
+        assert!(key.starts_with(&prefix(None, &[from, to])?));
+        assert!(key.starts_with(&prefix(Some("e"), &[from, to])?));
+        assert!(!key.starts_with(&prefix(Some("f"), &[from, to])?));
+        assert!(!key.starts_with(&prefix(None, &[to])?));
+
+        let key = HexOrder::FET.to_db(id, to, from, label)?;
+        let prefix =
+            |label: &str, ids: &[Id]| HexOrder::FET.prefix_to_db(Some(&label.to_string()), ids);
+        assert!(key.starts_with(&prefix("e", &[from])?));
+        assert!(!key.starts_with(&prefix("", &[from])?));
+        assert!(!key.starts_with(&prefix("e", &[to])?));
+
+        Ok(())
+    }
 }
@@ -1,13 +1,15 @@
 pub(crate) mod edge;
 pub(crate) mod parameter;
+pub(crate) mod path;
 pub(crate) mod vertex;
 
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use ulid::{Generator, Ulid};
 
 pub use self::{
-    edge::Edge,
+    edge::{Direction, Edge},
     parameter::{FromPValue, PValue, ToPValue},
+    path::Path,
     vertex::Vertex,
 };
 use crate::error::{Error, Result};
@@ -50,11 +52,15 @@ impl Id {
     }
 
     pub const fn max(t: Type) -> Self {
-        Self(t, Ulid(u128::max_value()))
+        Self(t, Ulid(u128::MAX))
+    }
+
+    pub const fn get_type(&self) -> Type {
+        self.0
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Ids(pub(crate) Vec<Id>);
 
 impl From<()> for Ids {
@@ -75,6 +81,27 @@ impl<T: Into<Id>> From<Vec<T>> for Ids {
     }
 }
 
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Labels<L>(pub(crate) Vec<L>);
+
+impl<L> From<()> for Labels<L> {
+    fn from(_: ()) -> Self {
+        Self(vec![])
+    }
+}
+
+impl<L> From<Vec<L>> for Labels<L> {
+    fn from(l: Vec<L>) -> Self {
+        Self(l)
+    }
+}
+
+impl<L, const N: usize> From<[L; N]> for Labels<L> {
+    fn from(l: [L; N]) -> Self {
+        Self(l.into())
+    }
+}
+
 impl<V, E, P> TryFrom<&Vertex<V, E, P>> for Id
 where
     V: Writable,
@@ -111,7 +138,7 @@ impl<'a> BytesEncode<'a> for Id {
     fn bytes_encode(item: &'a Self::EItem) -> Option<Cow<'a, [u8]>> {
         let ulid = item.1;
         let ulid_bytes = ulid.0.to_be_bytes();
-        let vector = to_stdvec(&(item.0, ulid_bytes)).unwrap();
+        let vector = to_stdvec(&(item.0, ulid_bytes)).ok()?;
         Some(Cow::Owned(vector))
     }
 }
@@ -123,12 +150,9 @@ impl<'a> BytesDecode<'a> for Id {
         if bytes.len() != 17 {
             None
         } else {
-            let t: Type = from_bytes(&bytes[0..1]).unwrap();
-            let bytes: &[u8] = &bytes[1..17];
-            Some(Self(
-                t,
-                Ulid(u128::from_be_bytes(bytes.try_into().unwrap())),
-            ))
+            let t: Type = from_bytes(&bytes[0..1]).ok()?;
+            let ulid = bytes[1..17].try_into().ok()?;
+            Some(Self(t, Ulid(u128::from_be_bytes(ulid))))
         }
     }
 }
@@ -182,4 +206,13 @@ mod tests {
         assert!(decoded.is_some());
         assert_eq!(decoded.unwrap(), nil);
     }
+
+    #[test]
+    fn test_bad_type_decode() {
+        let mut encoded = Id::bytes_encode(&Id::nil(Type::Vertex))
+            .unwrap()
+            .into_owned();
+        encoded[0] = 0xFF;
+        assert_eq!(Id::bytes_decode(&encoded), None);
+    }
 }
@@ -1,20 +1,27 @@
-use super::{Edge, Id, Type, Vertex, Writable};
-use crate::error::Result;
+use super::{Edge, Id, Path, Type, Vertex, Writable};
+use crate::{error::Result, heed::codec::to_key};
 
 use chrono::{offset::Utc, serde::ts_nanoseconds, DateTime};
 use heed::{BytesDecode, BytesEncode};
 use postcard::{from_bytes, to_stdvec};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use std::{borrow::Cow, collections::HashMap};
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    ops::Bound,
+};
 use ulid::Ulid;
 
-#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub enum PValue<V = String, E = String, P = String>
 where
     V: Writable,
     E: Writable,
     P: Writable + Eq,
 {
+    #[default]
     None,
     #[serde(bound(deserialize = "V: DeserializeOwned, E: DeserializeOwned, P: DeserializeOwned"))]
     Vertex(Vertex<V, E, P>),
@@ -36,7 +43,6 @@ where
     Bool(bool),
 
     // From gremlin_client that I'm not ready to do yet
-    // Path
     // Metrics
     // TraversalMetrics
     // TraversalExplanation
@@ -51,22 +57,338 @@ where
     // Pop
     // Cardinality
     #[serde(bound(deserialize = "V: DeserializeOwned, E: DeserializeOwned, P: DeserializeOwned"))]
-    List(Vec<PValue<V, E, P>>),
+    List(Vec<Self>),
     // TODO: Is there a better type?
     #[serde(bound(deserialize = "V: DeserializeOwned, E: DeserializeOwned, P: DeserializeOwned"))]
-    Set(Vec<PValue<V, E, P>>),
+    Set(Vec<Self>),
     #[serde(bound(deserialize = "V: DeserializeOwned, E: DeserializeOwned, P: DeserializeOwned"))]
-    Map(HashMap<P, PValue<V, E, P>>),
+    Map(HashMap<P, Self>),
+    #[serde(bound(deserialize = "V: DeserializeOwned"))]
+    VertexLabel(V),
+    #[serde(bound(deserialize = "E: DeserializeOwned"))]
+    EdgeLabel(E),
+    /// A map keyed by any values, as its entries in insertion order. Equality and hashing
+    /// ignore that order, as they do for `Map`.
+    #[serde(bound(deserialize = "V: DeserializeOwned, E: DeserializeOwned, P: DeserializeOwned"))]
+    Entries(Vec<(Self, Self)>),
+    /// A parameter key, as in the entries of an `elementMap()`.
+    #[serde(bound(deserialize = "P: DeserializeOwned"))]
+    Key(P),
+    #[serde(bound(deserialize = "V: DeserializeOwned, E: DeserializeOwned, P: DeserializeOwned"))]
+    Path(Path<V, E, P>),
+    /// A single parameter of an element, as `properties()` reads them off.
+    #[serde(bound(deserialize = "V: DeserializeOwned, E: DeserializeOwned, P: DeserializeOwned"))]
+    Property(P, Box<Self>),
+}
+
+impl<V, E, P> PValue<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    /// Compares two values the way gremlin predicates do, so numbers compare by value
+    /// across their widths. Returns `None` when the values aren't comparable.
+    pub fn compare(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Self::None, Self::None) => Some(Ordering::Equal),
+            (Self::Vertex(a), Self::Vertex(b)) => a.id.partial_cmp(&b.id),
+            (Self::Edge(a), Self::Edge(b)) => a.id.partial_cmp(&b.id),
+            (Self::Id(a), Self::Id(b)) => a.partial_cmp(b),
+            (Self::Ulid(a), Self::Ulid(b)) => a.partial_cmp(b),
+            (Self::Type(a), Self::Type(b)) => a.partial_cmp(b),
+            (Self::Date(a), Self::Date(b)) => a.partial_cmp(b),
+            (Self::Token(a), Self::Token(b)) | (Self::String(a), Self::String(b)) => {
+                a.partial_cmp(b)
+            }
+            (Self::Bool(a), Self::Bool(b)) => a.partial_cmp(b),
+            (Self::VertexLabel(a), Self::VertexLabel(b)) => {
+                to_key(a).ok()?.partial_cmp(&to_key(b).ok()?)
+            }
+            (Self::EdgeLabel(a), Self::EdgeLabel(b)) => {
+                to_key(a).ok()?.partial_cmp(&to_key(b).ok()?)
+            }
+            (Self::Key(a), Self::Key(b)) => to_key(a).ok()?.partial_cmp(&to_key(b).ok()?),
+            (Self::List(a), Self::List(b)) | (Self::Set(a), Self::Set(b)) => {
+                for (a, b) in a.iter().zip(b.iter()) {
+                    match a.compare(b)? {
+                        Ordering::Equal => continue,
+                        ordering => return Some(ordering),
+                    }
+                }
+                a.len().partial_cmp(&b.len())
+            }
+            _ => match (self.as_i128(), other.as_i128()) {
+                (Some(a), Some(b)) => a.partial_cmp(&b),
+                _ => {
+                    let (a, a_rest) = self.as_number()?;
+                    let (b, b_rest) = other.as_number()?;
+                    Some(a.partial_cmp(&b)?.then(a_rest.cmp(&b_rest)))
+                }
+            },
+        }
+    }
+
+    /// A total order over all values, for sorting.
+    ///
+    /// Values that can go in the value index come first, in the order the index keeps
+    /// them: numbers by value across their widths, then dates, tokens, strings and bools.
+    /// After those come NaN, `None`, ids, ulids, types, vertices and edges (by id), lists
+    /// and sets (element by element), maps (only by size), labels (by their key encoding),
+    /// entries (entry by entry), keys (by their key encoding), paths (object by object) and
+    /// properties (by key, then by value).
+    pub fn total_cmp(&self, other: &Self) -> Ordering {
+        let rank = self.rank().cmp(&other.rank());
+        if rank != Ordering::Equal {
+            return rank;
+        }
+        match (self, other) {
+            (Self::List(a), Self::List(b)) | (Self::Set(a), Self::Set(b)) => {
+                Self::total_cmp_all(a, b)
+            }
+            (Self::Map(a), Self::Map(b)) => a.len().cmp(&b.len()),
+            (Self::Entries(a), Self::Entries(b)) => a
+                .iter()
+                .zip(b.iter())
+                .map(|((ak, av), (bk, bv))| ak.total_cmp(bk).then_with(|| av.total_cmp(bv)))
+                .find(|o| *o != Ordering::Equal)
+                .unwrap_or_else(|| a.len().cmp(&b.len())),
+            (Self::Path(a), Self::Path(b)) => Self::total_cmp_all(a.objects(), b.objects()),
+            (Self::Property(ak, av), Self::Property(bk, bv)) => (to_key(ak).ok())
+                .cmp(&to_key(bk).ok())
+                .then_with(|| av.total_cmp(bv)),
+            _ => self.compare(other).unwrap_or(Ordering::Equal),
+        }
+    }
+
+    fn total_cmp_all(a: &[Self], b: &[Self]) -> Ordering {
+        a.iter()
+            .zip(b.iter())
+            .map(|(a, b)| a.total_cmp(b))
+            .find(|o| *o != Ordering::Equal)
+            .unwrap_or_else(|| a.len().cmp(&b.len()))
+    }
+
+    const fn rank(&self) -> u8 {
+        match self {
+            Self::Float(f) if f.is_nan() => 5,
+            Self::Double(d) if d.is_nan() => 5,
+            Self::I32(_) | Self::I64(_) | Self::I128(_) | Self::Float(_) | Self::Double(_) => 0,
+            Self::Date(_) => 1,
+            Self::Token(_) => 2,
+            Self::String(_) => 3,
+            Self::Bool(_) => 4,
+            Self::None => 6,
+            Self::Id(_) => 7,
+            Self::Ulid(_) => 8,
+            Self::Type(_) => 9,
+            Self::Vertex(_) => 10,
+            Self::Edge(_) => 11,
+            Self::List(_) => 12,
+            Self::Set(_) => 13,
+            Self::Map(_) => 14,
+            Self::VertexLabel(_) => 15,
+            Self::EdgeLabel(_) => 16,
+            Self::Entries(_) => 17,
+            Self::Key(_) => 18,
+            Self::Path(_) => 19,
+            Self::Property(_, _) => 20,
+        }
+    }
+
+    /// Whether this value lies between `start` and `end`, compared with `compare`.
+    pub(crate) fn in_bounds(&self, start: Bound<&Self>, end: Bound<&Self>) -> bool {
+        let lower = match start {
+            Bound::Included(start) => matches!(
+                self.compare(start),
+                Some(Ordering::Greater) | Some(Ordering::Equal)
+            ),
+            Bound::Excluded(start) => self.compare(start) == Some(Ordering::Greater),
+            Bound::Unbounded => true,
+        };
+        let upper = match end {
+            Bound::Included(end) => matches!(
+                self.compare(end),
+                Some(Ordering::Less) | Some(Ordering::Equal)
+            ),
+            Bound::Excluded(end) => self.compare(end) == Some(Ordering::Less),
+            Bound::Unbounded => true,
+        };
+        lower && upper
+    }
+
+    pub(crate) fn as_i128(&self) -> Option<i128> {
+        match self {
+            Self::I32(i) => Some(i128::from(*i)),
+            Self::I64(i) => Some(i128::from(*i)),
+            Self::I128(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    /// A number as the `f64` it rounds to, and for integers, how far they are from it.
+    /// Rounding never reorders numbers, so comparing these pairs compares numbers of any
+    /// width exactly, even integers too large for an `f64`.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn as_number(&self) -> Option<(f64, i128)> {
+        let rounded = self.as_f64()?;
+        // The cast saturates, which only shifts every integer rounding to 2^127 by the same
+        // amount
+        let rest = self.as_i128().map_or(0, |i| i - rounded as i128);
+        Some((rounded, rest))
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::I32(i) => Some(f64::from(*i)),
+            Self::I64(i) => Some(*i as f64),
+            Self::I128(i) => Some(*i as f64),
+            Self::Float(f) => Some(f64::from(*f)),
+            Self::Double(d) => Some(*d),
+            _ => None,
+        }
+    }
 }
 
-impl<V, E, P> Default for PValue<V, E, P>
+impl<V, E, P> PartialEq for PValue<V, E, P>
 where
     V: Writable,
     E: Writable,
     P: Writable + Eq,
 {
-    fn default() -> Self {
-        Self::None
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::None, Self::None) => true,
+            (Self::Vertex(a), Self::Vertex(b)) => a == b,
+            (Self::Edge(a), Self::Edge(b)) => a == b,
+            (Self::Id(a), Self::Id(b)) => a == b,
+            (Self::Ulid(a), Self::Ulid(b)) => a == b,
+            (Self::Type(a), Self::Type(b)) => a == b,
+            (Self::I32(a), Self::I32(b)) => a == b,
+            (Self::I64(a), Self::I64(b)) => a == b,
+            (Self::I128(a), Self::I128(b)) => a == b,
+            (Self::Float(a), Self::Float(b)) => a == b,
+            (Self::Double(a), Self::Double(b)) => a == b,
+            (Self::Date(a), Self::Date(b)) => a == b,
+            (Self::Token(a), Self::Token(b)) | (Self::String(a), Self::String(b)) => a == b,
+            (Self::Bool(a), Self::Bool(b)) => a == b,
+            (Self::List(a), Self::List(b)) | (Self::Set(a), Self::Set(b)) => a == b,
+            (Self::Map(a), Self::Map(b)) => a == b,
+            (Self::VertexLabel(a), Self::VertexLabel(b)) => a == b,
+            (Self::EdgeLabel(a), Self::EdgeLabel(b)) => a == b,
+            (Self::Entries(a), Self::Entries(b)) => {
+                let mut unmatched = b.iter().collect::<Vec<_>>();
+                a.len() == b.len()
+                    && a.iter().all(|entry| {
+                        let found = unmatched.iter().position(|other| *other == entry);
+                        found.map(|i| unmatched.swap_remove(i)).is_some()
+                    })
+            }
+            (Self::Key(a), Self::Key(b)) => a == b,
+            (Self::Path(a), Self::Path(b)) => a == b,
+            (Self::Property(ak, av), Self::Property(bk, bv)) => ak == bk && av == bv,
+            _ => false,
+        }
+    }
+}
+
+/// Hashes consistently with `PartialEq`: elements hash by id and label, floats hash `-0.0`
+/// as `0.0`, and map entries are combined independently of their iteration order.
+impl<V, E, P> Hash for PValue<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Self::None => {}
+            Self::Vertex(v) => (v.id, &v.label).hash(state),
+            Self::Edge(e) => (e.id, &e.label).hash(state),
+            Self::Id(id) => id.hash(state),
+            Self::Ulid(u) => u.hash(state),
+            Self::Type(t) => t.hash(state),
+            Self::I32(i) => i.hash(state),
+            Self::I64(i) => i.hash(state),
+            Self::I128(i) => i.hash(state),
+            Self::Float(f) => (if *f == 0.0 { 0.0 } else { *f }).to_bits().hash(state),
+            Self::Double(d) => (if *d == 0.0 { 0.0 } else { *d }).to_bits().hash(state),
+            Self::Date(d) => d.hash(state),
+            Self::Token(s) | Self::String(s) => s.hash(state),
+            Self::Bool(b) => b.hash(state),
+            Self::List(l) | Self::Set(l) => l.hash(state),
+            Self::Map(m) => (m.len(), hash_unordered(m.iter())).hash(state),
+            Self::VertexLabel(l) => l.hash(state),
+            Self::EdgeLabel(l) => l.hash(state),
+            Self::Entries(e) => (e.len(), hash_unordered(e.iter())).hash(state),
+            Self::Key(k) => k.hash(state),
+            Self::Path(p) => p.hash(state),
+            Self::Property(k, v) => (k, v).hash(state),
+        }
+    }
+}
+
+/// Combines the hashes of `entries` so that their order doesn't matter.
+fn hash_unordered<T: Hash>(entries: impl Iterator<Item = T>) -> u64 {
+    entries.fold(0_u64, |acc, entry| {
+        let mut hasher = DefaultHasher::new();
+        entry.hash(&mut hasher);
+        acc.wrapping_add(hasher.finish())
+    })
+}
+
+/// Values seen so far, for deduplicating a stream or grouping by value. `PValue` can't be
+/// `Eq` because of its floats, so this buckets by hash and compares with `PartialEq`
+/// within a bucket.
+#[derive(Debug)]
+pub struct PValueSet<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    buckets: HashMap<u64, Vec<usize>>,
+    values: Vec<PValue<V, E, P>>,
+}
+
+impl<V, E, P> PValueSet<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    pub(crate) fn new() -> Self {
+        Self {
+            buckets: HashMap::new(),
+            values: vec![],
+        }
+    }
+
+    /// Adds `value`, returning whether it wasn't already present.
+    pub(crate) fn insert(&mut self, value: &PValue<V, E, P>) -> bool {
+        self.insert_full(value).1
+    }
+
+    /// Adds `value` if it isn't already present, returning its position in insertion order
+    /// and whether it was added.
+    pub(crate) fn insert_full(&mut self, value: &PValue<V, E, P>) -> (usize, bool) {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let values = &mut self.values;
+        let bucket = self.buckets.entry(hasher.finish()).or_default();
+        if let Some(&idx) = bucket.iter().find(|&&idx| values[idx] == *value) {
+            return (idx, false);
+        }
+        bucket.push(values.len());
+        values.push(value.clone());
+        (values.len() - 1, true)
+    }
+
+    /// The values in the order they were first added.
+    pub(crate) fn into_values(self) -> Vec<PValue<V, E, P>> {
+        self.values
     }
 }
 
@@ -139,6 +461,7 @@ mod tests {
     use rstest::rstest;
 
     use super::*;
+    use chrono::TimeZone;
 
     #[rstest]
     fn test_none_id() -> Result<()> {
@@ -149,4 +472,108 @@ mod tests {
 
         Ok(())
     }
+
+    #[rstest]
+    fn test_compare() {
+        type PV = PValue<String, String, String>;
+        assert_eq!(PV::I32(1).compare(&PV::I64(1)), Some(Ordering::Equal));
+        assert_eq!(
+            PV::I128(2).compare(&PV::Double(1.5)),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(PV::Float(0.5).compare(&PV::I32(1)), Some(Ordering::Less));
+        assert_eq!(
+            PV::String("a".into()).compare(&PV::String("b".into())),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            PV::List(vec![PV::I32(1), PV::I32(2)]).compare(&PV::List(vec![PV::I32(1)])),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(PV::String("1".into()).compare(&PV::I32(1)), None);
+        assert_eq!(PV::Double(f64::NAN).compare(&PV::Double(1.0)), None);
+    }
+
+    #[rstest]
+    fn test_total_cmp() {
+        type PV = PValue<String, String, String>;
+        let mut values = vec![
+            PV::List(vec![PV::I32(1)]),
+            PV::None,
+            PV::String("a".into()),
+            PV::Bool(false),
+            PV::Double(2.5),
+            PV::Token("b".into()),
+            PV::I64(-3),
+            PV::Date(Utc.timestamp_opt(0, 0).unwrap()),
+            PV::List(vec![]),
+            PV::I32(2),
+        ];
+        values.sort_by(PV::total_cmp);
+        assert_eq!(
+            values,
+            vec![
+                PV::I64(-3),
+                PV::I32(2),
+                PV::Double(2.5),
+                PV::Date(Utc.timestamp_opt(0, 0).unwrap()),
+                PV::Token("b".into()),
+                PV::String("a".into()),
+                PV::Bool(false),
+                PV::None,
+                PV::List(vec![]),
+                PV::List(vec![PV::I32(1)]),
+            ]
+        );
+        assert_eq!(PV::I32(1).total_cmp(&PV::Double(1.0)), Ordering::Equal);
+        let nan = PV::Double(f64::NAN);
+        assert_eq!(nan.total_cmp(&PV::Bool(true)), Ordering::Greater);
+        assert_eq!(nan.total_cmp(&PV::None), Ordering::Less);
+        assert_eq!(nan.total_cmp(&PV::Float(f32::NAN)), Ordering::Equal);
+    }
+
+    #[rstest]
+    fn test_set() {
+        type PV = PValue<String, String, String>;
+        let mut set = PValueSet::new();
+        assert!(set.insert(&PV::I32(1)));
+        assert!(!set.insert(&PV::I32(1)));
+        assert!(set.insert(&PV::I64(1)));
+        assert!(set.insert(&PV::Double(0.0)));
+        assert!(!set.insert(&PV::Double(-0.0)));
+        assert!(set.insert(&PV::Double(f64::NAN)));
+        assert!(set.insert(&PV::Double(f64::NAN)));
+
+        let map = |entries: &[(&str, i32)]| {
+            PV::Map(
+                entries
+                    .iter()
+                    .map(|(k, v)| ((*k).to_string(), PV::I32(*v)))
+                    .collect(),
+            )
+        };
+        assert!(set.insert(&map(&[("a", 1), ("b", 2)])));
+        assert!(!set.insert(&map(&[("b", 2), ("a", 1)])));
+        assert!(set.insert(&map(&[("a", 2), ("b", 1)])));
+
+        let entries = |entries: &[(i32, &str)]| {
+            PV::Entries(
+                entries
+                    .iter()
+                    .map(|(k, v)| (PV::I32(*k), PV::String((*v).to_string())))
+                    .collect(),
+            )
+        };
+        assert_eq!(
+            entries(&[(1, "a"), (2, "b")]),
+            entries(&[(2, "b"), (1, "a")])
+        );
+        assert_ne!(
+            entries(&[(1, "a"), (1, "a")]),
+            entries(&[(1, "a"), (2, "b")])
+        );
+        assert!(set.insert(&entries(&[(1, "a"), (2, "b")])));
+        assert!(!set.insert(&entries(&[(2, "b"), (1, "a")])));
+        assert!(set.insert(&entries(&[(1, "b"), (2, "a")])));
+    }
 }
@@ -0,0 +1,157 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::{
+    parameter::{FromPValue, PValue, ToPValue},
+    Writable,
+};
+use crate::error::{Error, Result};
+
+/// The values a traverser passed through, oldest first, each with the step labels given
+/// to it by `as_`.
+#[derive(Debug, PartialEq, Clone, Hash, Serialize, Deserialize)]
+pub struct Path<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    #[serde(bound(deserialize = "V: DeserializeOwned, E: DeserializeOwned, P: DeserializeOwned"))]
+    objects: Vec<PValue<V, E, P>>,
+    labels: Vec<Vec<String>>,
+}
+
+impl<V, E, P> Path<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    pub const fn new() -> Self {
+        Self {
+            objects: vec![],
+            labels: vec![],
+        }
+    }
+
+    pub fn objects(&self) -> &[PValue<V, E, P>] {
+        &self.objects
+    }
+
+    /// The labels of each object, in step with `objects`.
+    pub fn labels(&self) -> &[Vec<String>] {
+        &self.labels
+    }
+
+    pub const fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// The last object labelled `label`.
+    pub fn get(&self, label: &str) -> Option<&PValue<V, E, P>> {
+        self.labels
+            .iter()
+            .rposition(|labels| labels.iter().any(|l| l == label))
+            .map(|idx| &self.objects[idx])
+    }
+
+    pub(crate) fn push(&mut self, object: PValue<V, E, P>) {
+        self.objects.push(object);
+        self.labels.push(vec![]);
+    }
+
+    /// Labels the last object, if there is one.
+    pub(crate) fn label(&mut self, label: String) {
+        if let Some(labels) = self.labels.last_mut() {
+            if !labels.contains(&label) {
+                labels.push(label);
+            }
+        }
+    }
+}
+
+impl<V, E, P> Default for Path<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V, E, P> FromPValue<V, E, P> for Path<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    fn from_pvalue(v: PValue<V, E, P>) -> Result<Self> {
+        match v {
+            PValue::Path(p) => Ok(p),
+            _ => Err(Error::InvalidPValue(format!("{:#?}", v))),
+        }
+    }
+}
+
+impl<V, E, P> ToPValue<V, E, P> for Path<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    fn to_pvalue(&self) -> PValue<V, E, P> {
+        PValue::Path(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use heed::{BytesDecode, BytesEncode};
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    fn test_labels() {
+        let mut path = Path::<String, String, String>::new();
+        path.label("ignored".into());
+        assert!(path.is_empty());
+
+        path.push(PValue::I32(1));
+        path.label("a".into());
+        path.label("a".into());
+        path.push(PValue::I32(2));
+        path.push(PValue::I32(3));
+        path.label("a".into());
+        path.label("b".into());
+
+        assert_eq!(path.len(), 3);
+        assert_eq!(path.get("a"), Some(&PValue::I32(3)));
+        assert_eq!(path.get("b"), Some(&PValue::I32(3)));
+        assert_eq!(path.get("c"), None);
+        assert_eq!(
+            path.labels(),
+            &[vec!["a".to_string()], vec![], vec!["a".into(), "b".into()]]
+        );
+    }
+
+    #[rstest]
+    fn test_pvalue_round_trip() -> Result<()> {
+        let mut path = Path::<String, String, String>::new();
+        path.push(PValue::String("a".into()));
+        path.label("a".into());
+
+        let pvalue = path.to_pvalue();
+        let bytes = PValue::bytes_encode(&pvalue).unwrap();
+        let decoded = PValue::bytes_decode(&bytes).unwrap();
+        assert_eq!(Path::from_pvalue(decoded)?, path);
+        assert!(Path::<String, String, String>::from_pvalue(PValue::None).is_err());
+
+        Ok(())
+    }
+}
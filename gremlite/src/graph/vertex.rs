@@ -42,10 +42,18 @@ where
         self.label.clone()
     }
 
-    pub fn get_id(&self) -> Option<Id> {
+    pub const fn get_id(&self) -> Option<Id> {
         self.id
     }
 
+    pub fn get_param(&self, key: &P) -> Option<&PValue<V, E, P>> {
+        self.parameters.get(key)
+    }
+
+    pub const fn params(&self) -> &HashMap<P, PValue<V, E, P>> {
+        &self.parameters
+    }
+
     pub fn set_param(mut self, p: P, val: PValue<V, E, P>) -> Self
 where {
         self.parameters.insert(p, val);
@@ -62,10 +70,7 @@ where
     type EItem = Self;
 
     fn bytes_encode(item: &'a Self::EItem) -> Option<std::borrow::Cow<'a, [u8]>> {
-        match to_stdvec(item).ok() {
-            Some(vec) => Some(Cow::Owned(vec)),
-            None => None,
-        }
+        to_stdvec(item).ok().map(Cow::Owned)
     }
 }
 
@@ -120,6 +125,16 @@ mod tests {
         let vertex = Vertex::<_, (), ()>::new(label.clone());
         assert_eq!(vertex.get_label(), label);
         assert_eq!(vertex.id, None);
+        assert!(vertex.params().is_empty());
+    }
+
+    #[rstest]
+    fn test_params() {
+        let v = Vertex::<String, String, String>::new("v".into())
+            .set_param("age".into(), PValue::I32(30));
+        assert_eq!(v.get_param(&"age".into()), Some(&PValue::I32(30)));
+        assert_eq!(v.get_param(&"name".into()), None);
+        assert_eq!(v.params().len(), 1);
     }
 
     #[rstest]
@@ -0,0 +1,299 @@
+//! Anonymous traversals, the child traversals that steps like `repeat` take. `start` begins
+//! an empty one, and the rest begin one with a single step, as `__.out()` does in Gremlin.
+
+use super::{AnonymousTraversal, GraphTraversal, Predicate, TraversalBuilder, TraversalTerminator};
+use crate::graph::{Ids, Labels, PValue, Writable};
+
+pub fn start<V, E, P>() -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    GraphTraversal::new(
+        TraversalBuilder::default(),
+        TraversalTerminator::anonymous(),
+    )
+}
+
+pub fn v<V, E, P, T: Into<Ids>>(ids: T) -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().v(ids)
+}
+
+pub fn add_v<V, E, P>(label: V) -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().add_v(label)
+}
+
+pub fn out<V, E, P, L: Into<Labels<E>>>(labels: L) -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().out(labels)
+}
+
+pub fn in_<V, E, P, L: Into<Labels<E>>>(labels: L) -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().in_(labels)
+}
+
+pub fn both<V, E, P, L: Into<Labels<E>>>(labels: L) -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().both(labels)
+}
+
+pub fn out_e<V, E, P, L: Into<Labels<E>>>(labels: L) -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().out_e(labels)
+}
+
+pub fn in_e<V, E, P, L: Into<Labels<E>>>(labels: L) -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().in_e(labels)
+}
+
+pub fn both_e<V, E, P, L: Into<Labels<E>>>(labels: L) -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().both_e(labels)
+}
+
+pub fn out_v<V, E, P>() -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().out_v()
+}
+
+pub fn in_v<V, E, P>() -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().in_v()
+}
+
+pub fn other_v<V, E, P>() -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().other_v()
+}
+
+pub fn has<V, E, P, T: Into<Predicate<V, E, P>>>(
+    key: P,
+    predicate: T,
+) -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().has(key, predicate)
+}
+
+pub fn has_label<V, E, P, L: Into<Labels<V>>>(labels: L) -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().has_label(labels)
+}
+
+pub fn has_edge_label<V, E, P, L: Into<Labels<E>>>(labels: L) -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().has_edge_label(labels)
+}
+
+pub fn has_id<V, E, P, T: Into<Ids>>(ids: T) -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().has_id(ids)
+}
+
+pub fn has_not<V, E, P>(key: P) -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().has_not(key)
+}
+
+pub fn has_key<V, E, P, L: Into<Labels<P>>>(keys: L) -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().has_key(keys)
+}
+
+pub fn is<V, E, P, T: Into<Predicate<V, E, P>>>(predicate: T) -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().is(predicate)
+}
+
+pub fn loops<V, E, P>() -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().loops()
+}
+
+pub fn values<V, E, P, T: Into<Labels<P>>>(keys: T) -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().values(keys)
+}
+
+pub fn id<V, E, P>() -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().id()
+}
+
+pub fn label<V, E, P>() -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().label()
+}
+
+pub fn count<V, E, P>() -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().count()
+}
+
+pub fn fold<V, E, P>() -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().fold()
+}
+
+pub fn unfold<V, E, P>() -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().unfold()
+}
+
+pub fn as_<V, E, P, L: Into<String>>(label: L) -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().as_(label)
+}
+
+pub fn path<V, E, P>() -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().path()
+}
+
+pub fn store<V, E, P, K: Into<String>>(key: K) -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().store(key)
+}
+
+pub fn aggregate<V, E, P, K: Into<String>>(key: K) -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().aggregate(key)
+}
+
+pub fn constant<V, E, P>(value: PValue<V, E, P>) -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().constant(value)
+}
+
+pub fn math<V, E, P, S: Into<String>>(expression: S) -> AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    start().math(expression)
+}
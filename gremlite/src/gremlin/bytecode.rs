@@ -1,5 +1,12 @@
-use crate::graph::{Id, Ids, PValue, Writable};
-use std::collections::VecDeque;
+use super::predicate::Predicate;
+use crate::{
+    error::{Error, Result},
+    graph::{Id, Ids, Labels, PValue, Writable},
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::TryInto,
+};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Bytecode<V, E, P>
@@ -19,10 +26,7 @@ where
     P: Writable + Eq,
 {
     fn default() -> Self {
-        Self {
-            sources: VecDeque::new(),
-            steps: VecDeque::new(),
-        }
+        Self::new()
     }
 }
 
@@ -32,26 +36,224 @@ where
     E: Writable,
     P: Writable + Eq,
 {
-    // TODO: Uncomment this when it is necessary again
-    // pub fn add_source(&mut self, i: Instruction<V, E, P>) {
-    //     self.sources.push(i);
-    // }
+    pub const fn new() -> Self {
+        Self {
+            sources: VecDeque::new(),
+            steps: VecDeque::new(),
+        }
+    }
+
+    pub fn add_source(&mut self, i: Instruction<V, E, P>) {
+        self.sources.push_back(i);
+    }
+
+    pub const fn sources(&self) -> &VecDeque<Instruction<V, E, P>> {
+        &self.sources
+    }
 
     pub fn add_step(&mut self, i: Instruction<V, E, P>) {
         self.steps.push_back(i);
     }
 
-    pub fn steps(&self) -> &VecDeque<Instruction<V, E, P>> {
+    pub const fn steps(&self) -> &VecDeque<Instruction<V, E, P>> {
         &self.steps
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Vert(pub(crate) Ids);
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Edge(pub(crate) Ids);
 
+/// What a `by` modulator reads off each traverser.
+#[derive(Debug, PartialEq, Clone)]
+pub enum By<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    Value,
+    Key(P),
+    Label,
+    Id,
+    /// The first result of a child traversal.
+    Traversal(Bytecode<V, E, P>),
+}
+
+impl<V, E, P> From<P> for By<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    fn from(key: P) -> Self {
+        Self::Key(key)
+    }
+}
+
+/// How a `by` modulator sorts. Shuffled keys don't compare, so they only break ties at
+/// random.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Order {
+    Asc,
+    Desc,
+    Shuffle,
+}
+
+/// Where `from` or `to` finds the vertex at one end of the edges `add_e` makes.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Endpoint<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    Id(Id),
+    /// The vertex labelled with this by `as_`.
+    Label(String),
+    /// The first result of a child traversal, run from the traverser adding the edge.
+    Traversal(Bytecode<V, E, P>),
+}
+
+/// What `merge_v` looks for: vertices with the id, label and parameter values given, any
+/// of which can be left out. If none match, a vertex is made with all of them, so it needs
+/// a label then.
+#[derive(Debug, PartialEq, Clone)]
+pub struct MergeV<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    pub(crate) id: Option<Id>,
+    pub(crate) label: Option<V>,
+    pub(crate) params: HashMap<P, PValue<V, E, P>>,
+}
+
+impl<V, E, P> MergeV<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    pub fn new() -> Self {
+        Self {
+            id: None,
+            label: None,
+            params: HashMap::new(),
+        }
+    }
+
+    pub fn id<A>(mut self, id: A) -> Result<Self>
+    where
+        A: TryInto<Id, Error = Error>,
+    {
+        self.id = Some(id.try_into()?);
+        Ok(self)
+    }
+
+    pub fn label(mut self, label: V) -> Self {
+        self.label = Some(label);
+        self
+    }
+
+    pub fn property(mut self, key: P, value: PValue<V, E, P>) -> Self {
+        self.params.insert(key, value);
+        self
+    }
+}
+
+impl<V, E, P> Default for MergeV<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Like `MergeV`, for `merge_e`, which can also match on the vertices an edge goes from
+/// and to. A new edge needs a label and both vertices.
+#[derive(Debug, PartialEq, Clone)]
+pub struct MergeE<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    pub(crate) id: Option<Id>,
+    pub(crate) label: Option<E>,
+    pub(crate) from: Option<Id>,
+    pub(crate) to: Option<Id>,
+    pub(crate) params: HashMap<P, PValue<V, E, P>>,
+}
+
+impl<V, E, P> MergeE<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    pub fn new() -> Self {
+        Self {
+            id: None,
+            label: None,
+            from: None,
+            to: None,
+            params: HashMap::new(),
+        }
+    }
+
+    pub fn id<A>(mut self, id: A) -> Result<Self>
+    where
+        A: TryInto<Id, Error = Error>,
+    {
+        self.id = Some(id.try_into()?);
+        Ok(self)
+    }
+
+    pub fn label(mut self, label: E) -> Self {
+        self.label = Some(label);
+        self
+    }
+
+    pub fn from<A>(mut self, id: A) -> Result<Self>
+    where
+        A: TryInto<Id, Error = Error>,
+    {
+        self.from = Some(id.try_into()?);
+        Ok(self)
+    }
+
+    pub fn to<A>(mut self, id: A) -> Result<Self>
+    where
+        A: TryInto<Id, Error = Error>,
+    {
+        self.to = Some(id.try_into()?);
+        Ok(self)
+    }
+
+    pub fn property(mut self, key: P, value: PValue<V, E, P>) -> Self {
+        self.params.insert(key, value);
+        self
+    }
+}
+
+impl<V, E, P> Default for MergeE<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Instruction<V, E, P>
 where
@@ -64,6 +266,179 @@ where
     AddV(V),
     AddE(E),
     Property(P, PValue<V, E, P>),
-    From(Id),
-    To(Id),
+    From(Endpoint<V, E, P>),
+    To(Endpoint<V, E, P>),
+    Out(Labels<E>),
+    In(Labels<E>),
+    Both(Labels<E>),
+    OutE(Labels<E>),
+    InE(Labels<E>),
+    BothE(Labels<E>),
+    OutV,
+    InV,
+    OtherV,
+    Has(P, Predicate<V, E, P>),
+    HasLabel(Labels<V>),
+    HasEdgeLabel(Labels<E>),
+    HasId(Ids),
+    HasNot(P),
+    HasKey(Labels<P>),
+    Count,
+    Sum,
+    Min,
+    Max,
+    Mean,
+    Fold,
+    Unfold,
+    Limit(usize),
+    Range(usize, usize),
+    Skip(usize),
+    Tail(usize),
+    After(Id),
+    Order,
+    By(By<V, E, P>, Order),
+    Group,
+    GroupCount,
+    Dedup,
+    Values(Labels<P>),
+    Properties(Labels<P>),
+    ValueMap(bool),
+    ElementMap,
+    Id,
+    Label,
+    As(String),
+    Select(Vec<String>),
+    Path,
+    SimplePath,
+    CyclicPath,
+    Is(Predicate<V, E, P>),
+    Repeat(Bytecode<V, E, P>),
+    Until(Bytecode<V, E, P>),
+    Times(usize),
+    Emit(Bytecode<V, E, P>),
+    Loops,
+    Union(Vec<Bytecode<V, E, P>>),
+    Coalesce(Vec<Bytecode<V, E, P>>),
+    Optional(Bytecode<V, E, P>),
+    Choose(Bytecode<V, E, P>, Bytecode<V, E, P>, Bytecode<V, E, P>),
+    Where(Bytecode<V, E, P>),
+    Not(Bytecode<V, E, P>),
+    And(Vec<Bytecode<V, E, P>>),
+    Or(Vec<Bytecode<V, E, P>>),
+    WithSideEffect(String, PValue<V, E, P>),
+    Store(String),
+    Aggregate(String),
+    SideEffect(Bytecode<V, E, P>),
+    Cap(Vec<String>),
+    Project(Vec<P>),
+    Constant(PValue<V, E, P>),
+    Inject(Vec<PValue<V, E, P>>),
+    Math(String),
+    Drop,
+    MergeV(MergeV<V, E, P>),
+    MergeE(MergeE<V, E, P>),
+    OnCreate(HashMap<P, PValue<V, E, P>>),
+    OnMatch(HashMap<P, PValue<V, E, P>>),
+}
+
+impl<V, E, P> Instruction<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    /// Steps that write to the graph, or run child traversals that do, and so can't run
+    /// lazily against a shared transaction.
+    pub(crate) fn is_mutating(&self) -> bool {
+        matches!(
+            self,
+            Self::AddV(_)
+                | Self::AddE(_)
+                | Self::Property(_, _)
+                | Self::Drop
+                | Self::MergeV(_)
+                | Self::MergeE(_)
+        ) || self
+            .children()
+            .iter()
+            .any(|child| child.steps().iter().any(Self::is_mutating))
+    }
+
+    /// Steps that read the paths traversers have taken, or run child traversals that do.
+    /// `repeat` watches them for cycles.
+    pub(crate) fn uses_path(&self) -> bool {
+        matches!(
+            self,
+            Self::As(_)
+                | Self::Select(_)
+                | Self::Path
+                | Self::SimplePath
+                | Self::CyclicPath
+                | Self::Math(_)
+                | Self::Repeat(_)
+                | Self::From(Endpoint::Label(_))
+                | Self::To(Endpoint::Label(_))
+        ) || self
+            .children()
+            .iter()
+            .any(|child| child.steps().iter().any(Self::uses_path))
+    }
+
+    /// Steps that only configure the step before them.
+    pub(crate) const fn is_modulator(&self) -> bool {
+        matches!(
+            self,
+            Self::From(_)
+                | Self::To(_)
+                | Self::By(_, _)
+                | Self::Until(_)
+                | Self::Times(_)
+                | Self::Emit(_)
+                | Self::OnCreate(_)
+                | Self::OnMatch(_)
+        )
+    }
+
+    /// Whether this step takes `modulator` after it.
+    pub(crate) const fn takes(&self, modulator: &Self) -> bool {
+        match modulator {
+            Self::By(_, _) => matches!(
+                self,
+                Self::Order
+                    | Self::Group
+                    | Self::GroupCount
+                    | Self::Dedup
+                    | Self::Project(_)
+                    | Self::Math(_)
+            ),
+            Self::From(_) | Self::To(_) => matches!(self, Self::AddE(_)),
+            Self::Until(_) | Self::Times(_) | Self::Emit(_) => matches!(self, Self::Repeat(_)),
+            Self::OnCreate(_) | Self::OnMatch(_) => {
+                matches!(self, Self::MergeV(_) | Self::MergeE(_))
+            }
+            _ => false,
+        }
+    }
+
+    /// The child traversals this step runs.
+    pub(crate) fn children(&self) -> Vec<&Bytecode<V, E, P>> {
+        match self {
+            Self::Repeat(child)
+            | Self::Until(child)
+            | Self::Emit(child)
+            | Self::Optional(child)
+            | Self::Where(child)
+            | Self::Not(child)
+            | Self::SideEffect(child)
+            | Self::By(By::Traversal(child), _)
+            | Self::From(Endpoint::Traversal(child))
+            | Self::To(Endpoint::Traversal(child)) => vec![child],
+            Self::Union(children)
+            | Self::Coalesce(children)
+            | Self::And(children)
+            | Self::Or(children) => children.iter().collect(),
+            Self::Choose(test, then, otherwise) => vec![test, then, otherwise],
+            _ => vec![],
+        }
+    }
 }
@@ -1,18 +1,111 @@
 use crate::{
     error::{Error, Result},
     graph::{
-        parameter::{FromPValue, ToPValue},
-        Edge, Id, PValue, Vertex, Writable,
+        parameter::{FromPValue, PValueSet},
+        Direction, Edge, Id, Labels, PValue, Path, Type, Vertex, Writable,
     },
     gremlin::Bytecode,
-    heed::Graph,
+    heed::{
+        codec::{encode_value, to_key},
+        Graph,
+    },
+};
+
+use super::{
+    bytecode::{self, By, Endpoint, Instruction, MergeE, MergeV, Order},
+    math::Expression,
+    predicate::Predicate,
+    traverser::{Traverser, Traversers},
+};
+use heed::{RoTxn, RwTxn};
+use itertools::Either;
+use rand::{seq::SliceRandom, thread_rng};
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    collections::{HashMap, VecDeque},
+    convert::TryFrom,
+    iter::{from_fn, once, once_with, Once},
+    marker::PhantomData,
+    ops::Bound,
+    rc::Rc,
 };
 
-use super::bytecode::{self, Instruction};
-use heed::RwTxn;
-use std::{collections::VecDeque, marker::PhantomData};
+/// A single instruction along with the modulators (`from`, `to`, ...) that follow it.
+#[derive(Debug, Clone)]
+pub struct Step<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    pub(crate) instruction: Instruction<V, E, P>,
+    /// The `until` and `emit` given before a `repeat`, which it checks before going round.
+    pub(crate) leading: Vec<Instruction<V, E, P>>,
+    pub(crate) modulators: Vec<Instruction<V, E, P>>,
+}
+
+impl<V, E, P> Step<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    pub(crate) fn compile(bytecode: &Bytecode<V, E, P>) -> Result<Vec<Self>> {
+        let mut steps: Vec<Self> = vec![];
+        let mut leading = vec![];
+        for instruction in bytecode.steps().iter().cloned() {
+            if !instruction.is_modulator() {
+                if !leading.is_empty() && !matches!(instruction, Instruction::Repeat(_)) {
+                    return Err(Error::BadRequest("until and emit only go before repeat"));
+                }
+                steps.push(Self {
+                    instruction,
+                    leading: std::mem::take(&mut leading),
+                    modulators: vec![],
+                });
+            } else if let Some(step) = steps
+                .last_mut()
+                .filter(|step| leading.is_empty() && step.instruction.takes(&instruction))
+            {
+                if matches!(instruction, Instruction::By(..)) && instruction.is_mutating() {
+                    return Err(Error::BadRequest("by traversals can't write to the graph"));
+                }
+                step.modulators.push(instruction);
+            } else if matches!(instruction, Instruction::Until(_) | Instruction::Emit(_)) {
+                leading.push(instruction);
+            } else if steps.is_empty() {
+                return Err(Error::BadRequest("modulator without a step"));
+            } else {
+                return Err(Error::BadRequest("modulator the step before doesn't take"));
+            }
+        }
+        if !leading.is_empty() {
+            return Err(Error::BadRequest("until and emit only go before repeat"));
+        }
+        Ok(steps)
+    }
+
+    /// Whether the step or any of its modulators reads the paths of traversers.
+    pub(crate) fn uses_path(&self) -> bool {
+        self.modulators()
+            .chain(once(&self.instruction))
+            .any(Instruction::uses_path)
+    }
+
+    /// Whether the step or any of its modulators writes to the graph.
+    pub(crate) fn is_mutating(&self) -> bool {
+        self.modulators()
+            .chain(once(&self.instruction))
+            .any(Instruction::is_mutating)
+    }
+
+    fn modulators(&self) -> impl Iterator<Item = &Instruction<V, E, P>> {
+        self.leading.iter().chain(&self.modulators)
+    }
+}
 
-pub struct WriteExecutor<'graph, End, V, E, P>
+pub struct Executor<'graph, End, V, E, P>
 where
     V: 'static + Writable,
     E: 'static + Writable,
@@ -20,10 +113,13 @@ where
     End: FromPValue<V, E, P>,
 {
     graph: &'graph Graph<V, E, P>,
+    side_effects: SideEffects<V, E, P>,
+    /// Whether traversers keep their paths, which is only worth it when a step reads them.
+    track_paths: bool,
     _marker: PhantomData<(End,)>,
 }
 
-impl<'graph, End, V, E, P> WriteExecutor<'graph, End, V, E, P>
+impl<'graph, End, V, E, P> Executor<'graph, End, V, E, P>
 where
     V: 'static + Writable,
     E: 'static + Writable,
@@ -31,95 +127,2062 @@ where
     End: FromPValue<V, E, P>,
 {
     pub(crate) fn new(graph: &'graph Graph<V, E, P>) -> Self {
+        Self::with_side_effects(graph, SideEffects::new(), false)
+    }
+
+    const fn with_side_effects(
+        graph: &'graph Graph<V, E, P>,
+        side_effects: SideEffects<V, E, P>,
+        track_paths: bool,
+    ) -> Self {
         Self {
             graph,
+            side_effects,
+            track_paths,
             _marker: PhantomData,
         }
     }
 
+    /// Runs `bytecode` as a pipeline of steps, each turning a stream of traversers into
+    /// another.
+    ///
+    /// Everything up to the last mutating step runs eagerly, since writes need the
+    /// transaction exclusively. The steps after that are chained lazily, so results are
+    /// only pulled from the database as the returned iterator is consumed.
     pub(crate) fn execute<'txn>(
         &mut self,
         txn: &'txn mut RwTxn,
         bytecode: &Bytecode<V, E, P>,
-    ) -> Result<Box<dyn 'txn + Iterator<Item = PValue<V, E, P>>>>
+    ) -> Result<Box<dyn 'txn + Iterator<Item = Result<PValue<V, E, P>>>>>
     where
         'graph: 'txn,
     {
-        let mut steps = bytecode.steps().clone();
+        let steps = Step::compile(bytecode)?;
         if steps.is_empty() {
             return Ok(Box::new(vec![].into_iter()));
         }
-        let head = steps.pop_front().unwrap();
-        let iter: Box<dyn Iterator<Item = PValue<V, E, P>> + 'txn> = match head {
-            Instruction::Vert(bytecode::Vert(ids)) => {
-                if ids.0.is_empty() {
-                    Box::new(self.graph.vertices(txn).unwrap())
-                } else {
-                    Box::new(self.graph.get_vertices_by_ids(txn, ids.0)?)
-                }
+        self.side_effects.reset(bytecode.sources())?;
+        self.track_paths = steps.iter().any(Step::uses_path);
+        let barrier = steps
+            .iter()
+            .rposition(Step::is_mutating)
+            .map_or(0, |idx| idx + 1);
+        let traversers = self.run_eager(txn, self.starts(&steps), &steps[..barrier])?;
+
+        let txn: &'txn RoTxn = txn;
+        let stream = Box::new(traversers.into_iter().map(Ok));
+        let stream = self.pipeline(txn, stream, steps[barrier..].iter())?;
+        Ok(Box::new(stream.map(|t| t.map(Traverser::into_value))))
+    }
+
+    /// Runs `bytecode` lazily against a read transaction, failing with `Error::WriteInReadTxn`
+    /// before anything runs if it has a mutating step.
+    pub(crate) fn execute_read<'txn>(
+        &mut self,
+        txn: &'txn RoTxn,
+        bytecode: &Bytecode<V, E, P>,
+    ) -> Result<Box<dyn 'txn + Iterator<Item = Result<PValue<V, E, P>>>>>
+    where
+        'graph: 'txn,
+    {
+        let steps = Step::compile(bytecode)?;
+        if steps.iter().any(Step::is_mutating) {
+            return Err(Error::WriteInReadTxn);
+        }
+        if steps.is_empty() {
+            return Ok(Box::new(vec![].into_iter()));
+        }
+        self.side_effects.reset(bytecode.sources())?;
+        self.track_paths = steps.iter().any(Step::uses_path);
+        let stream = Box::new(self.starts(&steps).into_iter().map(Ok));
+        let stream = self.pipeline(txn, stream, steps.iter())?;
+        Ok(Box::new(stream.map(|t| t.map(Traverser::into_value))))
+    }
+
+    /// Runs the child traversals of read steps, with this executor's side effects.
+    fn children<'txn>(&self, txn: &'txn RoTxn) -> ReadChildren<'txn, V, E, P>
+    where
+        'graph: 'txn,
+    {
+        ReadChildren {
+            graph: self.graph,
+            txn,
+            side_effects: self.side_effects.clone(),
+            track_paths: self.track_paths,
+        }
+    }
+
+    /// The traversers a pipeline starts from: the placeholder that start steps like `V()` run
+    /// from, or nothing if the first step is an `inject` that brings its own.
+    fn starts(&self, steps: &[Step<V, E, P>]) -> Vec<Traverser<V, E, P>> {
+        match steps.first() {
+            Some(Step {
+                instruction: Instruction::Inject(_),
+                ..
+            }) => vec![],
+            _ => vec![Traverser::start(self.track_paths)],
+        }
+    }
+
+    /// Runs `steps` over `traversers` to completion, writing as it goes. The reads between
+    /// mutating steps go through `pipeline`.
+    fn run_eager(
+        &self,
+        txn: &mut RwTxn,
+        mut traversers: Vec<Traverser<V, E, P>>,
+        steps: &[Step<V, E, P>],
+    ) -> Result<Vec<Traverser<V, E, P>>> {
+        let mut pending: Vec<&Step<V, E, P>> = vec![];
+        for step in steps {
+            if step.is_mutating() {
+                let stream = Box::new(traversers.into_iter().map(Ok));
+                let read = self.pipeline(txn, stream, std::mem::take(&mut pending))?;
+                traversers = read.collect::<Result<_>>()?;
+                traversers = self.mutate(txn, traversers, step)?;
+            } else {
+                pending.push(step);
             }
-            Instruction::Edge(bytecode::Edge(ids)) => {
-                if ids.0.is_empty() {
-                    Box::new(self.graph.edges(txn).unwrap())
+        }
+        let stream = Box::new(traversers.into_iter().map(Ok));
+        self.pipeline(txn, stream, pending)?.collect()
+    }
+
+    fn pipeline<'txn, 's, I>(
+        &self,
+        txn: &'txn RoTxn,
+        mut stream: Traversers<'txn, V, E, P>,
+        steps: I,
+    ) -> Result<Traversers<'txn, V, E, P>>
+    where
+        'graph: 'txn,
+        I: IntoIterator<Item = &'s Step<V, E, P>>,
+        V: 's,
+        E: 's,
+        P: 's,
+    {
+        let mut steps = steps.into_iter().peekable();
+        while let Some(step) = steps.next() {
+            let next = steps.peek().copied();
+            stream = match (&step.instruction, next.map(|next| &next.instruction)) {
+                (Instruction::Vert(bytecode::Vert(ids)), Some(Instruction::Count))
+                    if ids.0.is_empty() =>
+                {
+                    steps.next();
+                    self.count_all(txn, stream, Graph::vertex_count)
+                }
+                (Instruction::Edge(bytecode::Edge(ids)), Some(Instruction::Count))
+                    if ids.0.is_empty() =>
+                {
+                    steps.next();
+                    self.count_all(txn, stream, Graph::edge_count)
+                }
+                (Instruction::Vert(bytecode::Vert(ids)), Some(Instruction::After(id)))
+                    if ids.0.is_empty() =>
+                {
+                    let id = *id;
+                    steps.next();
+                    let graph: &'txn Graph<V, E, P> = self.graph;
+                    flat_map(stream, move |t| {
+                        let vertices = graph.vertices_after(txn, &id)?;
+                        Ok(vertices.map(move |v| Ok(t.split(v?))))
+                    })
+                }
+                (Instruction::Edge(bytecode::Edge(ids)), Some(Instruction::After(id)))
+                    if ids.0.is_empty() =>
+                {
+                    let id = *id;
+                    steps.next();
+                    let graph: &'txn Graph<V, E, P> = self.graph;
+                    flat_map(stream, move |t| {
+                        let edges = graph.edges_after(txn, &id)?;
+                        Ok(edges.map(move |e| Ok(t.split(e?))))
+                    })
+                }
+                (Instruction::Vert(bytecode::Vert(ids)), Some(_)) if ids.0.is_empty() => {
+                    match next.and_then(VertexIndex::for_step) {
+                        Some(index) => {
+                            if index.replaces_step() {
+                                steps.next();
+                            }
+                            self.scan(txn, stream, index)
+                        }
+                        None => self.step(txn, stream, step)?,
+                    }
+                }
+                _ => self.step(txn, stream, step)?,
+            };
+        }
+        Ok(stream)
+    }
+
+    /// Stands in for a bare `V().count()` or `E().count()`, taking the count from the
+    /// database instead of reading every element.
+    fn count_all<'txn>(
+        &self,
+        txn: &'txn RoTxn,
+        stream: Traversers<'txn, V, E, P>,
+        total: fn(&Graph<V, E, P>, &RoTxn) -> Result<usize>,
+    ) -> Traversers<'txn, V, E, P>
+    where
+        'graph: 'txn,
+    {
+        let graph: &'txn Graph<V, E, P> = self.graph;
+        barrier(stream, self.track_paths, move |stream| {
+            let starts = count(stream)?;
+            Ok(once(count_value(starts * total(graph, txn)?)))
+        })
+    }
+
+    /// Stands in for a bare `V()`, reading only the vertices an index says can pass the
+    /// filter after it, which still runs afterwards, or reading them in the order the
+    /// `order()` after it asks for.
+    fn scan<'txn>(
+        &self,
+        txn: &'txn RoTxn,
+        stream: Traversers<'txn, V, E, P>,
+        index: VertexIndex<V, E, P>,
+    ) -> Traversers<'txn, V, E, P>
+    where
+        'graph: 'txn,
+    {
+        let graph: &'txn Graph<V, E, P> = self.graph;
+        flat_map(stream, move |t| {
+            let vertices: Box<dyn Iterator<Item = Result<Vertex<V, E, P>>>> = match &index {
+                VertexIndex::Label(labels) => {
+                    let ranges = labels
+                        .iter()
+                        .map(|label| graph.get_vertices_by_label(txn, label))
+                        .collect::<Result<Vec<_>>>()?;
+                    Box::new(ranges.into_iter().flatten())
+                }
+                VertexIndex::Param(key) => Box::new(graph.get_vertices_by_param(txn, key)?),
+                VertexIndex::Range(key, start, end) => Box::new(
+                    graph.get_vertices_by_value_range(txn, key, (start.clone(), end.clone()))?,
+                ),
+                VertexIndex::Values(key, values) => {
+                    let ranges = values
+                        .iter()
+                        .map(|value| graph.get_vertices_by_value(txn, key, value))
+                        .collect::<Result<Vec<_>>>()?;
+                    Box::new(ranges.into_iter().flatten())
+                }
+                VertexIndex::Ordered(key, descending) => {
+                    Box::new(graph.get_vertices_ordered_by(txn, key, *descending)?)
+                }
+            };
+            Ok(vertices.map(move |v| Ok(t.split(PValue::Vertex(v?)))))
+        })
+    }
+
+    fn step<'txn>(
+        &self,
+        txn: &'txn RoTxn,
+        stream: Traversers<'txn, V, E, P>,
+        step: &Step<V, E, P>,
+    ) -> Result<Traversers<'txn, V, E, P>>
+    where
+        'graph: 'txn,
+    {
+        let graph: &'txn Graph<V, E, P> = self.graph;
+        Ok(match step.instruction.clone() {
+            Instruction::Vert(bytecode::Vert(ids)) => flat_map(stream, move |t| {
+                let vertices: Box<dyn Iterator<Item = Result<PValue<V, E, P>>>> =
+                    if ids.0.is_empty() {
+                        Box::new(graph.vertices(txn)?)
+                    } else {
+                        Box::new(graph.get_vertices_by_ids(txn, ids.0.clone())?)
+                    };
+                Ok(vertices.map(move |v| Ok(t.split(v?))))
+            }),
+            Instruction::Edge(bytecode::Edge(ids)) => flat_map(stream, move |t| {
+                let edges: Box<dyn Iterator<Item = Result<PValue<V, E, P>>>> = if ids.0.is_empty() {
+                    Box::new(graph.edges(txn)?)
                 } else {
-                    Box::new(self.graph.get_edges_by_ids(txn, ids.0)?)
+                    Box::new(graph.get_edges_by_ids(txn, ids.0.clone())?)
+                };
+                Ok(edges.map(move |e| Ok(t.split(e?))))
+            }),
+            Instruction::Out(labels) => adjacent(graph, txn, stream, Direction::Out, labels, false),
+            Instruction::In(labels) => adjacent(graph, txn, stream, Direction::In, labels, false),
+            Instruction::Both(labels) => {
+                adjacent(graph, txn, stream, Direction::Both, labels, false)
+            }
+            Instruction::OutE(labels) => adjacent(graph, txn, stream, Direction::Out, labels, true),
+            Instruction::InE(labels) => adjacent(graph, txn, stream, Direction::In, labels, true),
+            Instruction::BothE(labels) => {
+                adjacent(graph, txn, stream, Direction::Both, labels, true)
+            }
+            Instruction::OutV => edge_vertex(graph, txn, stream, |e, _| Ok(e.from)),
+            Instruction::InV => edge_vertex(graph, txn, stream, |e, _| Ok(e.to)),
+            Instruction::OtherV => edge_vertex(graph, txn, stream, |e, origin| match origin {
+                Some(origin) if origin == e.from => Ok(e.to),
+                Some(origin) if origin == e.to => Ok(e.from),
+                _ => Err(Error::BadRequest("otherV needs to come from a vertex")),
+            }),
+            Instruction::Has(key, predicate) => filter(stream, move |t| {
+                Ok(t.params()?.get(&key).is_some_and(|v| predicate.test(v)))
+            }),
+            Instruction::HasLabel(labels) => filter(stream, move |t| {
+                Ok(matches!(&t.value, PValue::Vertex(v) if labels.0.contains(&v.label)))
+            }),
+            Instruction::HasEdgeLabel(labels) => filter(stream, move |t| {
+                Ok(matches!(&t.value, PValue::Edge(e) if labels.0.contains(&e.label)))
+            }),
+            Instruction::HasId(ids) => filter(stream, move |t| {
+                Ok(t.id().is_some_and(|id| ids.0.contains(&id)))
+            }),
+            Instruction::HasNot(key) => {
+                filter(stream, move |t| Ok(!t.params()?.contains_key(&key)))
+            }
+            Instruction::HasKey(keys) => filter(stream, move |t| {
+                let params = t.params()?;
+                Ok(keys.0.iter().any(|key| params.contains_key(key)))
+            }),
+            Instruction::Count => barrier(stream, self.track_paths, |stream| {
+                Ok(once(count_value(count(stream)?)))
+            }),
+            Instruction::Sum => barrier(stream, self.track_paths, |stream| sum(&values(stream)?)),
+            Instruction::Min => barrier(stream, self.track_paths, |stream| {
+                extreme(values(stream)?, Ordering::Less)
+            }),
+            Instruction::Max => barrier(stream, self.track_paths, |stream| {
+                extreme(values(stream)?, Ordering::Greater)
+            }),
+            Instruction::Mean => barrier(stream, self.track_paths, |stream| {
+                let values = values(stream)?;
+                Ok(sum(&values)?.map(|sum| {
+                    #[allow(clippy::cast_precision_loss)]
+                    let len = values.len() as f64;
+                    PValue::Double(sum.as_f64().unwrap_or(f64::NAN) / len)
+                }))
+            }),
+            Instruction::Fold => barrier(stream, self.track_paths, |stream| {
+                Ok(once(PValue::List(values(stream)?)))
+            }),
+            Instruction::Unfold => flat_map(stream, |t| {
+                let values = match &t.value {
+                    PValue::List(values) | PValue::Set(values) => values.clone(),
+                    PValue::Map(map) => map
+                        .iter()
+                        .map(|(k, v)| PValue::Map(once((k.clone(), v.clone())).collect()))
+                        .collect(),
+                    PValue::Entries(entries) => entries
+                        .iter()
+                        .map(|entry| PValue::Entries(vec![entry.clone()]))
+                        .collect(),
+                    other => vec![other.clone()],
+                };
+                Ok(values.into_iter().map(move |value| Ok(t.split(value))))
+            }),
+            Instruction::Limit(n) => slice(stream, 0, Some(n)),
+            Instruction::Range(lo, hi) => slice(stream, lo, Some(hi)),
+            Instruction::Skip(n) => slice(stream, n, None),
+            Instruction::Tail(n) => {
+                let last = once_with(move || {
+                    let mut last = VecDeque::with_capacity(n);
+                    for t in stream {
+                        let t = t?;
+                        if last.len() == n {
+                            last.pop_front();
+                        }
+                        if n > 0 {
+                            last.push_back(t);
+                        }
+                    }
+                    Ok(last)
+                });
+                Box::new(last.flat_map(|last| flatten(last.map(|last| last.into_iter().map(Ok)))))
+            }
+            Instruction::After(id) => filter(stream, move |t| Ok(t.id().is_some_and(|t| t > id))),
+            Instruction::Order => order(self.children(txn), stream, bys(&step.modulators)?),
+            Instruction::Group => {
+                let mut bys = bys(&step.modulators)?.into_iter().map(|(by, _)| by);
+                let key = bys.next().unwrap_or(ByKey::Value);
+                let value = bys.next().unwrap_or(ByKey::Value);
+                let children = self.children(txn);
+                barrier(stream, self.track_paths, move |stream| {
+                    let groups = group_by(&children, stream, &key, |group: &mut Vec<_>, t| {
+                        group.extend(by_key(&children, &t, &value)?);
+                        Ok(())
+                    })?;
+                    let groups = groups.into_iter().map(|(k, v)| (k, PValue::List(v)));
+                    Ok(once(PValue::Entries(groups.collect())))
+                })
+            }
+            Instruction::GroupCount => {
+                let key = bys(&step.modulators)?
+                    .pop()
+                    .map_or(ByKey::Value, |(by, _)| by);
+                let children = self.children(txn);
+                barrier(stream, self.track_paths, move |stream| {
+                    let counts = group_by(&children, stream, &key, |count: &mut usize, _| {
+                        *count += 1;
+                        Ok(())
+                    })?;
+                    let counts = counts.into_iter().map(|(k, n)| (k, count_value(n)));
+                    Ok(once(PValue::Entries(counts.collect())))
+                })
+            }
+            Instruction::Dedup => {
+                let key = bys(&step.modulators)?
+                    .pop()
+                    .map_or(ByKey::Value, |(by, _)| by);
+                let mut seen = PValueSet::new();
+                let children = self.children(txn);
+                filter(stream, move |t| {
+                    Ok(by_key(&children, t, &key)?.is_some_and(|key| seen.insert(&key)))
+                })
+            }
+            Instruction::Values(keys) => flat_map(stream, move |t| {
+                let values = properties(&t, &keys)?.into_iter().map(|(_, v)| v);
+                Ok(values.map(move |value| Ok(t.split(value))))
+            }),
+            Instruction::Properties(keys) => flat_map(stream, move |t| {
+                let properties = properties(&t, &keys)?.into_iter();
+                let properties = properties.map(|(k, v)| PValue::Property(k, Box::new(v)));
+                Ok(properties.map(move |property| Ok(t.split(property))))
+            }),
+            Instruction::ValueMap(false) => map(stream, |t| Ok(PValue::Map(t.params()?.clone()))),
+            Instruction::ValueMap(true) => map(stream, |t| element_map(t, false)),
+            Instruction::ElementMap => map(stream, |t| element_map(t, true)),
+            Instruction::Id => map(stream, |t| {
+                t.id().map(PValue::Id).ok_or_else(|| not_element(t))
+            }),
+            Instruction::Label => map(stream, |t| match &t.value {
+                PValue::Vertex(v) => Ok(PValue::VertexLabel(v.label.clone())),
+                PValue::Edge(e) => Ok(PValue::EdgeLabel(e.label.clone())),
+                _ => Err(not_element(t)),
+            }),
+            Instruction::As(label) => Box::new(stream.map(move |t| {
+                t.map(|mut t| {
+                    t.path.label(label.clone());
+                    t
+                })
+            })),
+            Instruction::Select(labels) => Box::new(stream.filter_map(move |t| {
+                t.map(|t| select(&t.path, &labels).map(|value| t.split(value)))
+                    .transpose()
+            })),
+            Instruction::Path => map(stream, |t| Ok(PValue::Path(t.path.clone()))),
+            Instruction::SimplePath => filter(stream, |t| Ok(is_simple(&t.path))),
+            Instruction::CyclicPath => filter(stream, |t| Ok(!is_simple(&t.path))),
+            Instruction::Is(predicate) => filter(stream, move |t| Ok(predicate.test(&t.value))),
+            Instruction::Repeat(_)
+            | Instruction::Union(_)
+            | Instruction::Coalesce(_)
+            | Instruction::Optional(_)
+            | Instruction::Choose(_, _, _)
+            | Instruction::Where(_)
+            | Instruction::Not(_)
+            | Instruction::And(_)
+            | Instruction::Or(_)
+            | Instruction::SideEffect(_) => {
+                let branch = Branch::compile(step)?;
+                let mut children = self.children(txn);
+                flat_map(stream, move |t| {
+                    Ok(branch.run(&mut children, t)?.into_iter().map(Ok))
+                })
+            }
+            Instruction::Store(key) => {
+                let side_effects = self.side_effects.clone();
+                Box::new(stream.map(move |t| {
+                    let t = t?;
+                    side_effects.store(&key, t.value.clone())?;
+                    Ok(t)
+                }))
+            }
+            Instruction::Aggregate(key) => {
+                let side_effects = self.side_effects.clone();
+                let all = once_with(move || {
+                    let all = stream.collect::<Result<Vec<_>>>()?;
+                    for t in &all {
+                        side_effects.store(&key, t.value.clone())?;
+                    }
+                    Ok(all)
+                });
+                Box::new(all.flat_map(|all| flatten(all.map(|all| all.into_iter().map(Ok)))))
+            }
+            Instruction::Cap(keys) => {
+                let side_effects = self.side_effects.clone();
+                barrier(stream, self.track_paths, move |stream| {
+                    count(stream)?;
+                    let values = keys
+                        .iter()
+                        .map(|key| side_effects.get(key))
+                        .collect::<Result<Vec<_>>>()?;
+                    Ok(once(match values.as_slice() {
+                        [value] => value.clone(),
+                        _ => PValue::Entries(
+                            keys.into_iter().map(PValue::String).zip(values).collect(),
+                        ),
+                    }))
+                })
+            }
+            Instruction::Project(keys) => {
+                let mut bys: Vec<_> = bys(&step.modulators)?
+                    .into_iter()
+                    .map(|(by, _)| by)
+                    .collect();
+                if bys.is_empty() {
+                    bys.push(ByKey::Value);
                 }
+                let children = self.children(txn);
+                map(stream, move |t| {
+                    let mut row = HashMap::with_capacity(keys.len());
+                    for (key, by) in keys.iter().zip(bys.iter().cycle()) {
+                        if let Some(value) = by_key(&children, t, by)? {
+                            row.insert(key.clone(), value);
+                        }
+                    }
+                    Ok(PValue::Map(row))
+                })
+            }
+            Instruction::Constant(value) => map(stream, move |_| Ok(value.clone())),
+            Instruction::Inject(values) => {
+                let track_paths = self.track_paths;
+                Box::new(
+                    values
+                        .into_iter()
+                        .map(move |value| Ok(Traverser::new(value, track_paths)))
+                        .chain(stream),
+                )
             }
+            Instruction::Math(expression) => {
+                let expression = Expression::parse(&expression)?;
+                let bys: Vec<_> = bys(&step.modulators)?
+                    .into_iter()
+                    .map(|(by, _)| by)
+                    .collect();
+                let children = self.children(txn);
+                map(stream, move |t| {
+                    let mut values = Vec::with_capacity(expression.variables().len());
+                    for (idx, name) in expression.variables().iter().enumerate() {
+                        let labelled;
+                        let t = if name == "_" {
+                            t
+                        } else {
+                            let value = t.path.get(name).ok_or_else(|| {
+                                Error::Math(format!("nothing is labelled {:?}", name))
+                            })?;
+                            labelled = t.split(value.clone());
+                            &labelled
+                        };
+                        let by = bys.get(idx % bys.len().max(1)).unwrap_or(&ByKey::Value);
+                        let value = by_key(&children, t, by)?
+                            .ok_or_else(|| Error::Math(format!("no value for {}", name)))?;
+                        values.push(value.as_f64().ok_or_else(|| {
+                            Error::Math(format!("{} is {:?}, not a number", name, value))
+                        })?);
+                    }
+                    Ok(PValue::Double(expression.evaluate(&values)))
+                })
+            }
+            Instruction::Loops => map(stream, |t| {
+                Ok(count_value(t.loops.last().copied().unwrap_or(0)))
+            }),
+            Instruction::AddV(_)
+            | Instruction::AddE(_)
+            | Instruction::Property(_, _)
+            | Instruction::Drop
+            | Instruction::MergeV(_)
+            | Instruction::MergeE(_)
+            | Instruction::OnCreate(_)
+            | Instruction::OnMatch(_)
+            | Instruction::By(_, _)
+            | Instruction::From(_)
+            | Instruction::To(_)
+            | Instruction::Until(_)
+            | Instruction::Times(_)
+            | Instruction::Emit(_) => unreachable!("{:?} is not a read step", step.instruction),
+            Instruction::WithSideEffect(_, _) => {
+                return Err(Error::BadRequest("withSideEffect is a source, not a step"))
+            }
+        })
+    }
+
+    fn mutate(
+        &self,
+        txn: &mut RwTxn,
+        traversers: Vec<Traverser<V, E, P>>,
+        step: &Step<V, E, P>,
+    ) -> Result<Vec<Traverser<V, E, P>>> {
+        let mut out = Vec::with_capacity(traversers.len());
+        match &step.instruction {
             Instruction::AddV(label) => {
-                let v = self.graph.put_vertex(txn, &Vertex::new(label))?.to_pvalue();
-                Box::new(vec![v].into_iter())
+                for t in traversers {
+                    let vertex = self.graph.put_vertex(txn, &Vertex::new(label.clone()))?;
+                    out.push(t.split(PValue::Vertex(vertex)));
+                }
             }
             Instruction::AddE(label) => {
-                let (to, from) = Self::pop_to_from(&mut steps)?;
-                let e = self
-                    .graph
-                    .put_edge(txn, &Edge::<V, E, P>::new(to, from, label)?)?
-                    .to_pvalue();
-                Box::new(vec![e].into_iter())
-            }
-            _ => todo!(),
-        };
-        Ok(iter)
+                let (from, to) = Self::endpoints(&step.modulators)?;
+                for t in traversers {
+                    let from = self.endpoint(txn, from.as_ref(), &t, "Missing from")?;
+                    let to = self.endpoint(txn, to.as_ref(), &t, "Missing to")?;
+                    let edge = Edge::<V, E, P>::new(to, from, label.clone())?;
+                    let edge = self.graph.put_edge(txn, &edge)?;
+                    out.push(t.split(PValue::Edge(edge)));
+                }
+            }
+            Instruction::Property(key, value) => {
+                for mut t in traversers {
+                    // Re-read the element, an earlier traverser may have written to it
+                    t.value = match &t.value {
+                        PValue::Vertex(v) => {
+                            let id = v.id.ok_or(Error::VertexInvalid)?;
+                            let vertex = self
+                                .graph
+                                .get_vertex_by_id(txn, &id)?
+                                .ok_or(Error::NotFound(id))?
+                                .set_param(key.clone(), value.clone());
+                            PValue::Vertex(self.graph.put_vertex(txn, &vertex)?)
+                        }
+                        PValue::Edge(e) => {
+                            let id = e.id.ok_or(Error::EdgeInvalid)?;
+                            let edge = self
+                                .graph
+                                .get_edge_by_id(txn, &id)?
+                                .ok_or(Error::NotFound(id))?
+                                .set_param(key.clone(), value.clone());
+                            PValue::Edge(self.graph.put_edge(txn, &edge)?)
+                        }
+                        other => {
+                            return Err(Error::InvalidPValue(format!(
+                                "can't set a property on {:?}",
+                                other
+                            )))
+                        }
+                    };
+                    out.push(t);
+                }
+            }
+            Instruction::Drop => {
+                for t in traversers {
+                    match &t.value {
+                        PValue::Vertex(v) => {
+                            self.graph.remove_vertex(
+                                txn,
+                                &v.id.ok_or(Error::VertexInvalid)?,
+                                true,
+                            )?;
+                        }
+                        PValue::Edge(e) => {
+                            self.graph
+                                .remove_edge(txn, &e.id.ok_or(Error::EdgeInvalid)?)?;
+                        }
+                        // A property, from the element it was read off
+                        PValue::Property(key, _) => {
+                            let id = t.origin.ok_or_else(|| not_element(&t))?;
+                            self.graph.remove_property(txn, &id, key)?;
+                        }
+                        _ => return Err(not_element(&t)),
+                    }
+                }
+            }
+            Instruction::MergeV(merge) => {
+                let (on_create, on_match) = Self::on_create_match(&step.modulators)?;
+                for t in traversers {
+                    for vertex in self.merge_vertex(txn, merge, &on_create, &on_match)? {
+                        out.push(t.split(PValue::Vertex(vertex)));
+                    }
+                }
+            }
+            Instruction::MergeE(merge) => {
+                let (on_create, on_match) = Self::on_create_match(&step.modulators)?;
+                for t in traversers {
+                    for edge in self.merge_edge(txn, merge, &on_create, &on_match)? {
+                        out.push(t.split(PValue::Edge(edge)));
+                    }
+                }
+            }
+            _ => {
+                let branch = Branch::compile(step)?;
+                let mut children = WriteChildren {
+                    executor: self,
+                    txn,
+                };
+                for t in traversers {
+                    out.extend(branch.run(&mut children, t)?);
+                }
+            }
+        }
+        Ok(out)
     }
 
-    fn pop_to_from(steps: &mut VecDeque<Instruction<V, E, P>>) -> Result<(Id, Id)> {
-        let (mut to, mut from) = (None, None);
-        let mut dels = vec![];
-        for (idx, step) in steps.iter().enumerate() {
-            match step {
-                Instruction::From(id) => {
-                    from = Some(*id);
-                    dels.push(idx);
+    /// The vertices matching `merge`, with `on_match` set on them, or if there are none a
+    /// new vertex made from `merge` and `on_create`. A vertex that has the id but doesn't
+    /// match is never written over.
+    fn merge_vertex(
+        &self,
+        txn: &mut RwTxn,
+        merge: &MergeV<V, E, P>,
+        on_create: &HashMap<P, PValue<V, E, P>>,
+        on_match: &HashMap<P, PValue<V, E, P>>,
+    ) -> Result<Vec<Vertex<V, E, P>>> {
+        if merge.id.is_some_and(|id| id.get_type() != Type::Vertex) {
+            return Err(Error::BadRequest("mergeV needs a vertex id"));
+        }
+        let matches = self.matching_vertices(txn, merge)?;
+        if matches.is_empty() {
+            if let Some(id) = merge.id {
+                if self.graph.get_vertex_by_id(txn, &id)?.is_some() {
+                    return Err(Error::AlreadyExists(id));
                 }
-                Instruction::To(id) => {
-                    to = Some(*id);
-                    dels.push(idx);
+            }
+            let vertex = Vertex {
+                id: merge.id,
+                label: merge
+                    .label
+                    .clone()
+                    .ok_or(Error::BadRequest("mergeV needs a label to create a vertex"))?,
+                parameters: created_params(&merge.params, on_create)?,
+            };
+            return Ok(vec![self.graph.put_vertex(txn, &vertex)?]);
+        }
+        matches
+            .into_iter()
+            .map(|mut vertex| {
+                if on_match.is_empty() {
+                    return Ok(vertex);
                 }
-                _ => {
-                    // Don't care
+                vertex.parameters.extend(on_match.clone());
+                self.graph.put_vertex(txn, &vertex)
+            })
+            .collect()
+    }
+
+    /// The vertices matching `merge`, read off whichever index narrows them down most.
+    fn matching_vertices(
+        &self,
+        txn: &RoTxn,
+        merge: &MergeV<V, E, P>,
+    ) -> Result<Vec<Vertex<V, E, P>>> {
+        let graph = self.graph;
+        let indexed = merge
+            .params
+            .iter()
+            .find(|(_, value)| encode_value(value).is_some());
+        let candidates: Box<dyn Iterator<Item = Result<Vertex<V, E, P>>>> =
+            if let Some(id) = &merge.id {
+                Box::new(graph.get_vertex_by_id(txn, id)?.map(Ok).into_iter())
+            } else if let Some((key, value)) = indexed {
+                Box::new(graph.get_vertices_by_value(txn, key, value)?)
+            } else if let Some(label) = &merge.label {
+                Box::new(graph.get_vertices_by_label(txn, label)?)
+            } else if let Some(key) = merge.params.keys().next() {
+                Box::new(graph.get_vertices_by_param(txn, key)?)
+            } else {
+                Box::new(
+                    graph
+                        .vertices(txn)?
+                        .map(|v| v.and_then(Vertex::from_pvalue)),
+                )
+            };
+        let mut matches = vec![];
+        for vertex in candidates {
+            let vertex = vertex?;
+            if merge
+                .label
+                .as_ref()
+                .is_none_or(|label| *label == vertex.label)
+                && has_params(&vertex.parameters, &merge.params)
+            {
+                matches.push(vertex);
+            }
+        }
+        Ok(matches)
+    }
+
+    /// Like `merge_vertex`, for edges. New edges need both of their vertices to exist.
+    fn merge_edge(
+        &self,
+        txn: &mut RwTxn,
+        merge: &MergeE<V, E, P>,
+        on_create: &HashMap<P, PValue<V, E, P>>,
+        on_match: &HashMap<P, PValue<V, E, P>>,
+    ) -> Result<Vec<Edge<V, E, P>>> {
+        if merge.id.is_some_and(|id| id.get_type() != Type::Edge) {
+            return Err(Error::BadRequest("mergeE needs an edge id"));
+        }
+        let matches = self.matching_edges(txn, merge)?;
+        if matches.is_empty() {
+            if let Some(id) = merge.id {
+                if self.graph.get_edge_by_id(txn, &id)?.is_some() {
+                    return Err(Error::AlreadyExists(id));
+                }
+            }
+            for id in merge.from.iter().chain(&merge.to) {
+                if id.get_type() != Type::Vertex {
+                    return Err(Error::BadRequest("mergeE from and to need vertex ids"));
+                }
+                self.graph
+                    .get_vertex_by_id(txn, id)?
+                    .ok_or(Error::NotFound(*id))?;
+            }
+            let missing = Error::BadRequest("mergeE needs a label, from and to to create an edge");
+            let edge = match (&merge.label, merge.from, merge.to) {
+                (Some(label), Some(from), Some(to)) => Edge {
+                    id: merge.id,
+                    to,
+                    from,
+                    label: label.clone(),
+                    parameters: created_params(&merge.params, on_create)?,
+                },
+                _ => return Err(missing),
+            };
+            return Ok(vec![self.graph.put_edge(txn, &edge)?]);
+        }
+        matches
+            .into_iter()
+            .map(|mut edge| {
+                if on_match.is_empty() {
+                    return Ok(edge);
+                }
+                edge.parameters.extend(on_match.clone());
+                self.graph.put_edge(txn, &edge)
+            })
+            .collect()
+    }
+
+    /// The edges matching `merge`, read off the adjacency or label index if it says which
+    /// vertices or label to look for.
+    fn matching_edges(&self, txn: &RoTxn, merge: &MergeE<V, E, P>) -> Result<Vec<Edge<V, E, P>>> {
+        let graph = self.graph;
+        let label = merge.label.as_ref();
+        let candidates: Box<dyn Iterator<Item = Result<Edge<V, E, P>>>> =
+            match (&merge.id, &merge.from, &merge.to) {
+                (Some(id), _, _) => Box::new(graph.get_edge_by_id(txn, id)?.map(Ok).into_iter()),
+                (None, Some(from), Some(to)) => {
+                    Box::new(graph.get_edges_between(txn, from, to, label)?)
                 }
+                (None, Some(from), None) => Box::new(graph.get_edges_from(txn, from, label)?),
+                (None, None, Some(to)) => Box::new(graph.get_edges_to(txn, to, label)?),
+                (None, None, None) => match label {
+                    Some(label) => Box::new(graph.get_edges_by_label(txn, label)?),
+                    None => Box::new(graph.edges(txn)?.map(|e| e.and_then(Edge::from_pvalue))),
+                },
             };
+        let mut matches = vec![];
+        for edge in candidates {
+            let edge = edge?;
+            if label.is_none_or(|label| *label == edge.label)
+                && merge.from.is_none_or(|from| from == edge.from)
+                && merge.to.is_none_or(|to| to == edge.to)
+                && has_params(&edge.parameters, &merge.params)
+            {
+                matches.push(edge);
+            }
+        }
+        Ok(matches)
+    }
+
+    /// The `on_create` and `on_match` parameters of a merge step.
+    fn on_create_match(
+        modulators: &[Instruction<V, E, P>],
+    ) -> Result<(HashMap<P, PValue<V, E, P>>, HashMap<P, PValue<V, E, P>>)> {
+        let (mut on_create, mut on_match) = (HashMap::new(), HashMap::new());
+        for modulator in modulators {
+            match modulator {
+                Instruction::OnCreate(params) => on_create.extend(params.clone()),
+                Instruction::OnMatch(params) => on_match.extend(params.clone()),
+                _ => {
+                    return Err(Error::BadRequest(
+                        "merge steps only take onCreate and onMatch",
+                    ))
+                }
+            }
+        }
+        Ok((on_create, on_match))
+    }
+
+    /// The `from` and `to` modulators of an `add_e`, if it has them.
+    fn endpoints(
+        modulators: &[Instruction<V, E, P>],
+    ) -> Result<(Option<Target<V, E, P>>, Option<Target<V, E, P>>)> {
+        let (mut from, mut to) = (None, None);
+        for modulator in modulators {
+            match modulator {
+                Instruction::From(endpoint) => from = Some(Target::compile(endpoint)?),
+                Instruction::To(endpoint) => to = Some(Target::compile(endpoint)?),
+                _ => {}
+            }
         }
-        for idx in dels {
-            steps.remove(idx);
+        Ok((from, to))
+    }
+
+    /// The vertex at one end of an edge `t` is adding: the one `endpoint` points to, or
+    /// without one, `t`'s own vertex, failing with `missing` if `t` isn't at a vertex.
+    fn endpoint(
+        &self,
+        txn: &mut RwTxn,
+        endpoint: Option<&Target<V, E, P>>,
+        t: &Traverser<V, E, P>,
+        missing: &'static str,
+    ) -> Result<Id> {
+        let value = match endpoint {
+            Some(Target::Id(id)) => {
+                let vertex = self.graph.get_vertex_by_id(txn, id)?;
+                PValue::Vertex(vertex.ok_or(Error::NotFound(*id))?)
+            }
+            Some(Target::Label(label)) => t
+                .path
+                .get(label)
+                .cloned()
+                .ok_or(Error::BadRequest("addE step label isn't on the path"))?,
+            Some(Target::Traversal(child)) => self
+                .run_eager(txn, vec![t.clone()], child)?
+                .into_iter()
+                .next()
+                .ok_or(Error::BadRequest("addE traversal found no vertex"))?
+                .into_value(),
+            None if matches!(t.value, PValue::Vertex(_)) => t.value.clone(),
+            None => return Err(Error::BadRequest(missing)),
+        };
+        match value {
+            PValue::Vertex(v) => v.id.ok_or(Error::VertexInvalid),
+            other => Err(Error::InvalidPValue(format!("{:?} is not a vertex", other))),
+        }
+    }
+}
+
+/// An `addE` endpoint, with its child traversal compiled once for the whole step.
+enum Target<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    Id(Id),
+    Label(String),
+    Traversal(Vec<Step<V, E, P>>),
+}
+
+impl<V, E, P> Target<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    fn compile(endpoint: &Endpoint<V, E, P>) -> Result<Self> {
+        Ok(match endpoint {
+            Endpoint::Id(id) => Self::Id(*id),
+            Endpoint::Label(label) => Self::Label(label.clone()),
+            Endpoint::Traversal(child) => Self::Traversal(Step::compile(child)?),
+        })
+    }
+}
+
+/// Index lookups that can replace a full scan of the vertices.
+#[allow(clippy::large_enum_variant)]
+enum VertexIndex<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    Label(Vec<V>),
+    Param(P),
+    Range(P, Bound<PValue<V, E, P>>, Bound<PValue<V, E, P>>),
+    Values(P, Vec<PValue<V, E, P>>),
+    /// Vertices with the key, in order of its value, descending if set.
+    Ordered(P, bool),
+}
+
+impl<V, E, P> VertexIndex<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    fn for_step(step: &Step<V, E, P>) -> Option<Self> {
+        match &step.instruction {
+            Instruction::HasLabel(labels) => {
+                let mut unique: Vec<V> = vec![];
+                for label in &labels.0 {
+                    if !unique.contains(label) {
+                        unique.push(label.clone());
+                    }
+                }
+                Some(Self::Label(unique))
+            }
+            Instruction::Has(key, predicate) => Some(Self::for_predicate(key, predicate)),
+            Instruction::Order => match step.modulators.as_slice() {
+                [Instruction::By(By::Key(key), order @ (Order::Asc | Order::Desc))] => {
+                    Some(Self::Ordered(key.clone(), *order == Order::Desc))
+                }
+                _ => None,
+            },
+            _ => None,
         }
+    }
+
+    /// Whether this does the whole job of the step it was made for.
+    const fn replaces_step(&self) -> bool {
+        matches!(self, Self::Ordered(..))
+    }
 
-        Ok((
-            to.ok_or(Error::BadRequest("Missing to"))?,
-            from.ok_or(Error::BadRequest("Missing from"))?,
-        ))
+    fn for_predicate(key: &P, predicate: &Predicate<V, E, P>) -> Self {
+        let indexed = |value: &PValue<V, E, P>| encode_value(value).is_some();
+        if let Predicate::Within(values) = predicate {
+            if values.iter().all(indexed) {
+                let mut unique: Vec<PValue<V, E, P>> = vec![];
+                for value in values {
+                    if !unique
+                        .iter()
+                        .any(|u| u.compare(value) == Some(Ordering::Equal))
+                    {
+                        unique.push(value.clone());
+                    }
+                }
+                return Self::Values(key.clone(), unique);
+            }
+        }
+        match predicate.bounds() {
+            Some((start, end))
+                if [start, end].iter().all(|bound| match bound {
+                    Bound::Included(value) | Bound::Excluded(value) => indexed(value),
+                    Bound::Unbounded => true,
+                }) =>
+            {
+                Self::Range(key.clone(), start.cloned(), end.cloned())
+            }
+            _ => Self::Param(key.clone()),
+        }
     }
 }
 
-#[allow(dead_code)]
-struct VertexPValueIter<Start, End, I, V, E, P>
+/// Runs the child traversals of a step, in whichever transaction the step runs in.
+trait Children<V, E, P>
 where
-    I: Iterator<Item = Start>,
     V: Writable,
     E: Writable,
     P: Writable + Eq,
+{
+    fn run(
+        &mut self,
+        steps: &[Step<V, E, P>],
+        traversers: Vec<Traverser<V, E, P>>,
+    ) -> Result<Vec<Traverser<V, E, P>>>;
+
+    /// Whether the child traversal has any results for `t`.
+    fn passes(&mut self, steps: &[Step<V, E, P>], t: &Traverser<V, E, P>) -> Result<bool> {
+        Ok(!self.run(steps, vec![t.clone()])?.is_empty())
+    }
+}
+
+/// Children of a step in the lazy part of the pipeline, which only read.
+struct ReadChildren<'txn, V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    graph: &'txn Graph<V, E, P>,
+    txn: &'txn RoTxn,
+    side_effects: SideEffects<V, E, P>,
+    track_paths: bool,
+}
+
+impl<'txn, V, E, P> ReadChildren<'txn, V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    fn stream(
+        &self,
+        steps: &[Step<V, E, P>],
+        traversers: Vec<Traverser<V, E, P>>,
+    ) -> Result<Traversers<'txn, V, E, P>> {
+        let stream = Box::new(traversers.into_iter().map(Ok));
+        Executor::<PValue<V, E, P>, V, E, P>::with_side_effects(
+            self.graph,
+            self.side_effects.clone(),
+            self.track_paths,
+        )
+        .pipeline(self.txn, stream, steps)
+    }
+
+    /// The first result of the child traversal for `t`, pulling no more than that.
+    fn first(
+        &self,
+        steps: &[Step<V, E, P>],
+        t: &Traverser<V, E, P>,
+    ) -> Result<Option<PValue<V, E, P>>> {
+        let mut results = self.stream(steps, vec![t.clone()])?;
+        Ok(results.next().transpose()?.map(Traverser::into_value))
+    }
+}
+
+impl<'txn, V, E, P> Children<V, E, P> for ReadChildren<'txn, V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    fn run(
+        &mut self,
+        steps: &[Step<V, E, P>],
+        traversers: Vec<Traverser<V, E, P>>,
+    ) -> Result<Vec<Traverser<V, E, P>>> {
+        self.stream(steps, traversers)?.collect()
+    }
+
+    /// Pulls no more than the first result.
+    fn passes(&mut self, steps: &[Step<V, E, P>], t: &Traverser<V, E, P>) -> Result<bool> {
+        let mut results = self.stream(steps, vec![t.clone()])?;
+        Ok(results.next().transpose()?.is_some())
+    }
+}
+
+/// Children of a step that writes, or has a child that does, which run eagerly.
+struct WriteChildren<'a, 'graph, 'p, End, V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+    End: FromPValue<V, E, P>,
+{
+    executor: &'a Executor<'graph, End, V, E, P>,
+    txn: &'a mut RwTxn<'p>,
+}
+
+impl<'a, 'graph, 'p, End, V, E, P> Children<V, E, P> for WriteChildren<'a, 'graph, 'p, End, V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
     End: FromPValue<V, E, P>,
 {
-    iter: Option<I>,
-    _marker: PhantomData<(Start, End, V, E, P)>,
+    fn run(
+        &mut self,
+        steps: &[Step<V, E, P>],
+        traversers: Vec<Traverser<V, E, P>>,
+    ) -> Result<Vec<Traverser<V, E, P>>> {
+        self.executor.run_eager(self.txn, traversers, steps)
+    }
+}
+
+/// A step that runs child traversals, compiled once for all of its traversers.
+enum Branch<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    Repeat(Repeat<V, E, P>),
+    /// The results of every child in turn.
+    Union(Vec<Vec<Step<V, E, P>>>),
+    /// The results of the first child that has any.
+    Coalesce(Vec<Vec<Step<V, E, P>>>),
+    /// The results of the child, or the traverser itself if there are none.
+    Optional(Vec<Step<V, E, P>>),
+    /// The results of the second child if the first passes, otherwise of the third.
+    Choose(Vec<Step<V, E, P>>, Vec<Step<V, E, P>>, Vec<Step<V, E, P>>),
+    /// Keeps the traverser if every child passes.
+    All(Vec<Vec<Step<V, E, P>>>),
+    /// Keeps the traverser if any child passes.
+    Any(Vec<Vec<Step<V, E, P>>>),
+    /// Keeps the traverser if the child doesn't pass.
+    Not(Vec<Step<V, E, P>>),
+    /// Runs the child and keeps the traverser, whatever the child's results.
+    SideEffect(Vec<Step<V, E, P>>),
+}
+
+impl<V, E, P> Branch<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    fn compile(step: &Step<V, E, P>) -> Result<Self> {
+        let all = |children: &[Bytecode<V, E, P>]| -> Result<Vec<_>> {
+            children.iter().map(Step::compile).collect()
+        };
+        Ok(match &step.instruction {
+            Instruction::Repeat(body) => Self::Repeat(Repeat::new(body, step)?),
+            Instruction::Union(children) => Self::Union(all(children)?),
+            Instruction::Coalesce(children) => Self::Coalesce(all(children)?),
+            Instruction::Optional(child) => Self::Optional(Step::compile(child)?),
+            Instruction::Choose(test, then, otherwise) => Self::Choose(
+                Step::compile(test)?,
+                Step::compile(then)?,
+                Step::compile(otherwise)?,
+            ),
+            Instruction::Where(child) => Self::All(vec![Step::compile(child)?]),
+            Instruction::And(children) => Self::All(all(children)?),
+            Instruction::Or(children) => Self::Any(all(children)?),
+            Instruction::Not(child) => Self::Not(Step::compile(child)?),
+            Instruction::SideEffect(child) => Self::SideEffect(Step::compile(child)?),
+            _ => return Err(Error::BadRequest("unsupported step")),
+        })
+    }
+
+    fn run<C>(&self, children: &mut C, t: Traverser<V, E, P>) -> Result<Vec<Traverser<V, E, P>>>
+    where
+        C: Children<V, E, P>,
+    {
+        Ok(match self {
+            Self::Repeat(repeat) => repeat.run(children, t)?,
+            Self::Union(branches) => {
+                let mut out = vec![];
+                for branch in branches {
+                    out.extend(children.run(branch, vec![t.clone()])?);
+                }
+                out
+            }
+            Self::Coalesce(branches) => {
+                for branch in branches {
+                    let out = children.run(branch, vec![t.clone()])?;
+                    if !out.is_empty() {
+                        return Ok(out);
+                    }
+                }
+                vec![]
+            }
+            Self::Optional(branch) => {
+                let out = children.run(branch, vec![t.clone()])?;
+                if out.is_empty() {
+                    vec![t]
+                } else {
+                    out
+                }
+            }
+            Self::Choose(test, then, otherwise) => {
+                let branch = if children.passes(test, &t)? {
+                    then
+                } else {
+                    otherwise
+                };
+                children.run(branch, vec![t])?
+            }
+            Self::All(tests) => {
+                for test in tests {
+                    if !children.passes(test, &t)? {
+                        return Ok(vec![]);
+                    }
+                }
+                vec![t]
+            }
+            Self::Any(tests) => {
+                for test in tests {
+                    if children.passes(test, &t)? {
+                        return Ok(vec![t]);
+                    }
+                }
+                vec![]
+            }
+            Self::Not(test) => {
+                if children.passes(test, &t)? {
+                    vec![]
+                } else {
+                    vec![t]
+                }
+            }
+            Self::SideEffect(child) => {
+                children.run(child, vec![t.clone()])?;
+                vec![t]
+            }
+        })
+    }
+}
+
+/// A `repeat` step along with its `until`, `times` and `emit` modulators. An `until` or
+/// `emit` given before the `repeat` is also checked before going round the first time.
+struct Repeat<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    body: Vec<Step<V, E, P>>,
+    until: Option<Vec<Step<V, E, P>>>,
+    times: Option<usize>,
+    emit: Option<Vec<Step<V, E, P>>>,
+    until_first: bool,
+    emit_first: bool,
+}
+
+impl<V, E, P> Repeat<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    fn new(body: &Bytecode<V, E, P>, step: &Step<V, E, P>) -> Result<Self> {
+        let mut repeat = Self {
+            body: Step::compile(body)?,
+            until: None,
+            times: None,
+            emit: None,
+            until_first: false,
+            emit_first: false,
+        };
+        for modulator in &step.leading {
+            match modulator {
+                Instruction::Until(until) => {
+                    repeat.until = Some(Step::compile(until)?);
+                    repeat.until_first = true;
+                }
+                Instruction::Emit(emit) => {
+                    repeat.emit = Some(Step::compile(emit)?);
+                    repeat.emit_first = true;
+                }
+                _ => return Err(Error::BadRequest("only until and emit go before repeat")),
+            }
+        }
+        for modulator in &step.modulators {
+            match modulator {
+                Instruction::Until(until) => repeat.until = Some(Step::compile(until)?),
+                Instruction::Times(n) => repeat.times = Some(*n),
+                Instruction::Emit(emit) => repeat.emit = Some(Step::compile(emit)?),
+                _ => return Err(Error::BadRequest("repeat only takes until, times and emit")),
+            }
+        }
+        Ok(repeat)
+    }
+
+    /// Takes `t` round the loop breadth first, so every traverser in the frontier has been
+    /// round the same number of times, and returns the ones that come out.
+    ///
+    /// Without `times`, a traverser that comes back to where it's already been round the
+    /// loop is still checked against `until` and `emit`, but doesn't go round again.
+    fn run<C>(&self, children: &mut C, mut t: Traverser<V, E, P>) -> Result<Vec<Traverser<V, E, P>>>
+    where
+        C: Children<V, E, P>,
+    {
+        let outer = t.loops.clone();
+        let start = t.path.len().saturating_sub(1);
+        t.loops.push(0);
+        let mut out = vec![];
+        if self.until_first && self.done(children, &t, 0)? {
+            t.loops.pop();
+            return Ok(vec![t]);
+        }
+        if self.emit_first {
+            self.emit(children, &t, &mut out)?;
+        }
+        let mut frontier = vec![t];
+        let mut loops = 0;
+        while !frontier.is_empty() {
+            loops += 1;
+            for mut t in children.run(&self.body, std::mem::take(&mut frontier))? {
+                t.loops = outer.clone();
+                t.loops.push(loops);
+                if self.done(children, &t, loops)? {
+                    t.loops.pop();
+                    out.push(t);
+                    continue;
+                }
+                self.emit(children, &t, &mut out)?;
+                if self.times.is_some() || !revisits(&t.path, start, loops) {
+                    frontier.push(t);
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Whether `t` has been round `loops` times, or passes `until`.
+    fn done<C>(&self, children: &mut C, t: &Traverser<V, E, P>, loops: usize) -> Result<bool>
+    where
+        C: Children<V, E, P>,
+    {
+        match (&self.times, &self.until) {
+            (Some(times), _) if loops >= *times => Ok(true),
+            (_, Some(until)) => children.passes(until, t),
+            _ => Ok(false),
+        }
+    }
+
+    /// Adds a copy of `t` to `out` if it passes `emit`.
+    fn emit<C>(
+        &self,
+        children: &mut C,
+        t: &Traverser<V, E, P>,
+        out: &mut Vec<Traverser<V, E, P>>,
+    ) -> Result<()>
+    where
+        C: Children<V, E, P>,
+    {
+        if let Some(emit) = &self.emit {
+            if children.passes(emit, t)? {
+                let mut emitted = t.clone();
+                emitted.loops.pop();
+                out.push(emitted);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The side effects of a traversal, by key, shared with its child traversals.
+struct SideEffects<V, E, P>(Rc<RefCell<HashMap<String, PValue<V, E, P>>>>)
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq;
+
+impl<V, E, P> SideEffects<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    fn new() -> Self {
+        Self(Rc::new(RefCell::new(HashMap::new())))
+    }
+
+    /// Starts over with the side effects from the `withSideEffect` sources of a traversal.
+    fn reset<'a, I>(&self, sources: I) -> Result<()>
+    where
+        I: IntoIterator<Item = &'a Instruction<V, E, P>>,
+        V: 'a,
+        E: 'a,
+        P: 'a,
+    {
+        let mut side_effects = self.0.borrow_mut();
+        side_effects.clear();
+        for source in sources {
+            match source {
+                Instruction::WithSideEffect(key, initial) => {
+                    side_effects.insert(key.clone(), initial.clone());
+                }
+                _ => return Err(Error::BadRequest("unsupported source")),
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds `value` to the list or set under `key`, starting a list if there isn't one.
+    fn store(&self, key: &str, value: PValue<V, E, P>) -> Result<()> {
+        let mut side_effects = self.0.borrow_mut();
+        match side_effects.get_mut(key) {
+            Some(PValue::List(values)) => values.push(value),
+            Some(PValue::Set(values)) => {
+                if !values.contains(&value) {
+                    values.push(value);
+                }
+            }
+            Some(other) => {
+                return Err(Error::InvalidPValue(format!(
+                    "can't store into {:?}",
+                    other
+                )))
+            }
+            None => {
+                side_effects.insert(key.to_string(), PValue::List(vec![value]));
+            }
+        }
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<PValue<V, E, P>> {
+        self.0
+            .borrow()
+            .get(key)
+            .cloned()
+            .ok_or_else(|| Error::SideEffect(key.to_string()))
+    }
+}
+
+impl<V, E, P> Clone for SideEffects<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    fn clone(&self) -> Self {
+        Self(Rc::clone(&self.0))
+    }
+}
+
+/// Whether `params` has each of `wanted`, with a value that compares equal.
+fn has_params<V, E, P>(
+    params: &HashMap<P, PValue<V, E, P>>,
+    wanted: &HashMap<P, PValue<V, E, P>>,
+) -> bool
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    wanted
+        .iter()
+        .all(|(key, value)| params.get(key).is_some_and(|param| same(param, value)))
+}
+
+/// Whether `a` and `b` compare equal, as they would in an index lookup, or are equal
+/// values that don't compare, like lists.
+fn same<V, E, P>(a: &PValue<V, E, P>, b: &PValue<V, E, P>) -> bool
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    a == b || a.compare(b) == Some(Ordering::Equal)
+}
+
+/// The parameters of an element a merge step creates: the ones it matched on, along with
+/// its `on_create` ones as long as they don't disagree.
+fn created_params<V, E, P>(
+    matched: &HashMap<P, PValue<V, E, P>>,
+    on_create: &HashMap<P, PValue<V, E, P>>,
+) -> Result<HashMap<P, PValue<V, E, P>>>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    let mut params = matched.clone();
+    for (key, value) in on_create {
+        let param = params.entry(key.clone()).or_insert_with(|| value.clone());
+        if !same(param, value) {
+            return Err(Error::BadRequest(
+                "onCreate can't change what a merge step matches on",
+            ));
+        }
+    }
+    Ok(params)
+}
+
+/// Whether a traverser `loops` times round a loop it entered at `path[start]` has come back
+/// to a value it had in the loop, or hasn't moved on each time round.
+fn revisits<V, E, P>(path: &Path<V, E, P>, start: usize, loops: usize) -> bool
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    let objects = path.objects().get(start..).unwrap_or_default();
+    match objects.split_last() {
+        Some((last, earlier)) => earlier.len() < loops || earlier.contains(last),
+        None => true,
+    }
+}
+
+/// Hands the whole stream to `f` once the first result is pulled, for steps that have to
+/// see every traverser before they can produce anything.
+fn barrier<'txn, V, E, P, F, I>(
+    stream: Traversers<'txn, V, E, P>,
+    track_paths: bool,
+    f: F,
+) -> Traversers<'txn, V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+    F: 'txn + FnOnce(Traversers<'txn, V, E, P>) -> Result<I>,
+    I: 'txn + IntoIterator<Item = PValue<V, E, P>>,
+{
+    Box::new(
+        once_with(move || f(stream))
+            .flat_map(|values| flatten(values.map(|values| values.into_iter().map(Ok))))
+            .map(move |value| value.map(|value| Traverser::new(value, track_paths))),
+    )
+}
+
+/// Sorts the stream by the `by` modulators in turn, or by value if there are none.
+/// Traversers without a key to sort by are dropped.
+fn order<'txn, V, E, P>(
+    children: ReadChildren<'txn, V, E, P>,
+    stream: Traversers<'txn, V, E, P>,
+    mut bys: Vec<(ByKey<V, E, P>, Order)>,
+) -> Traversers<'txn, V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    if bys.is_empty() {
+        bys.push((ByKey::Value, Order::Asc));
+    }
+    Box::new(
+        once_with(move || {
+            let mut keyed = vec![];
+            for t in stream {
+                let t = t?;
+                let keys = bys
+                    .iter()
+                    .map(|(by, _)| by_key(&children, &t, by))
+                    .collect::<Result<Option<Vec<_>>>>()?;
+                if let Some(keys) = keys {
+                    keyed.push((keys, t));
+                }
+            }
+            if bys.iter().any(|(_, order)| *order == Order::Shuffle) {
+                keyed.shuffle(&mut thread_rng());
+            }
+            keyed.sort_by(|(a, _), (b, _)| {
+                bys.iter()
+                    .zip(a.iter().zip(b.iter()))
+                    .map(|((_, order), (a, b))| match order {
+                        Order::Asc => a.total_cmp(b),
+                        Order::Desc => b.total_cmp(a),
+                        Order::Shuffle => Ordering::Equal,
+                    })
+                    .find(|o| *o != Ordering::Equal)
+                    .unwrap_or(Ordering::Equal)
+            });
+            Ok(keyed.into_iter().map(|(_, t)| Ok(t)))
+        })
+        .flat_map(flatten),
+    )
+}
+
+/// The `by` modulators of a step, in order.
+fn bys<V, E, P>(modulators: &[Instruction<V, E, P>]) -> Result<Vec<(ByKey<V, E, P>, Order)>>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    modulators
+        .iter()
+        .filter_map(|m| match m {
+            Instruction::By(by, order) => Some(ByKey::compile(by).map(|by| (by, *order))),
+            _ => None,
+        })
+        .collect()
+}
+
+/// A `by` modulator, with its child traversal compiled once for the whole step.
+enum ByKey<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    Value,
+    Key(P),
+    Label,
+    Id,
+    Traversal(Vec<Step<V, E, P>>),
+}
+
+impl<V, E, P> ByKey<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    fn compile(by: &By<V, E, P>) -> Result<Self> {
+        Ok(match by {
+            By::Value => Self::Value,
+            By::Key(key) => Self::Key(key.clone()),
+            By::Label => Self::Label,
+            By::Id => Self::Id,
+            By::Traversal(child) => Self::Traversal(Step::compile(child)?),
+        })
+    }
+}
+
+/// What a single `by` reads off a traverser, or `None` if it doesn't have the key.
+fn by_key<V, E, P>(
+    children: &ReadChildren<V, E, P>,
+    t: &Traverser<V, E, P>,
+    by: &ByKey<V, E, P>,
+) -> Result<Option<PValue<V, E, P>>>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    Ok(Some(match (by, &t.value) {
+        (ByKey::Value, value) => value.clone(),
+        (ByKey::Key(key), _) => match t.params()?.get(key) {
+            Some(value) => value.clone(),
+            None => return Ok(None),
+        },
+        (ByKey::Label, PValue::Vertex(v)) => PValue::VertexLabel(v.label.clone()),
+        (ByKey::Label, PValue::Edge(e)) => PValue::EdgeLabel(e.label.clone()),
+        (ByKey::Label, _) => return Err(not_element(t)),
+        (ByKey::Id, _) => PValue::Id(t.id().ok_or_else(|| not_element(t))?),
+        (ByKey::Traversal(child), _) => return children.first(child, t),
+    }))
+}
+
+fn not_element<V, E, P>(t: &Traverser<V, E, P>) -> Error
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    Error::InvalidPValue(format!("{:?} is not an element", t.value))
+}
+
+/// The parameters `keys` that an element has, or all of its parameters sorted by their
+/// key encoding if `keys` is empty.
+fn properties<V, E, P>(
+    t: &Traverser<V, E, P>,
+    keys: &Labels<P>,
+) -> Result<Vec<(P, PValue<V, E, P>)>>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    let params = t.params()?;
+    if keys.0.is_empty() {
+        let mut all = params
+            .iter()
+            .map(|(k, v)| Ok((to_key(k)?, (k.clone(), v.clone()))))
+            .collect::<Result<Vec<_>>>()?;
+        all.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(all.into_iter().map(|(_, p)| p).collect())
+    } else {
+        Ok(keys
+            .0
+            .iter()
+            .filter_map(|k| Some((k.clone(), params.get(k)?.clone())))
+            .collect())
+    }
+}
+
+/// An element's id, label and parameters as `PValue::Entries`, along with the ids of its
+/// vertices if it's an edge and `with_vertices` is set.
+fn element_map<V, E, P>(t: &Traverser<V, E, P>, with_vertices: bool) -> Result<PValue<V, E, P>>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    let token = |token: &str| PValue::Token(token.to_string());
+    let mut entries = vec![(
+        token("id"),
+        PValue::Id(t.id().ok_or_else(|| not_element(t))?),
+    )];
+    match &t.value {
+        PValue::Vertex(v) => entries.push((token("label"), PValue::VertexLabel(v.label.clone()))),
+        PValue::Edge(e) => {
+            entries.push((token("label"), PValue::EdgeLabel(e.label.clone())));
+            if with_vertices {
+                entries.push((token("IN"), PValue::Id(e.to)));
+                entries.push((token("OUT"), PValue::Id(e.from)));
+            }
+        }
+        _ => return Err(not_element(t)),
+    }
+    let params = properties(t, &Labels(vec![]))?.into_iter();
+    entries.extend(params.map(|(k, v)| (PValue::Key(k), v)));
+    Ok(PValue::Entries(entries))
+}
+
+/// The object labelled with the only label in `labels`, or the entries for all of them, as
+/// long as the path has every label.
+fn select<V, E, P>(path: &Path<V, E, P>, labels: &[String]) -> Option<PValue<V, E, P>>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    match labels {
+        [label] => path.get(label).cloned(),
+        labels => labels
+            .iter()
+            .map(|label| Some((PValue::String(label.clone()), path.get(label)?.clone())))
+            .collect::<Option<_>>()
+            .map(PValue::Entries),
+    }
+}
+
+/// Whether no object appears twice in `path`.
+fn is_simple<V, E, P>(path: &Path<V, E, P>) -> bool
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    let mut seen = PValueSet::new();
+    path.objects().iter().all(|object| seen.insert(object))
+}
+
+/// Folds the stream into one `T` per distinct key, in the order the keys were first seen.
+/// Traversers without the key are dropped.
+fn group_by<'txn, V, E, P, T, F>(
+    children: &ReadChildren<'txn, V, E, P>,
+    stream: Traversers<'txn, V, E, P>,
+    by: &ByKey<V, E, P>,
+    mut fold: F,
+) -> Result<Vec<(PValue<V, E, P>, T)>>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+    T: Default,
+    F: FnMut(&mut T, Traverser<V, E, P>) -> Result<()>,
+{
+    let mut keys = PValueSet::new();
+    let mut groups = vec![];
+    for t in stream {
+        let t = t?;
+        if let Some(key) = by_key(children, &t, by)? {
+            let (idx, new) = keys.insert_full(&key);
+            if new {
+                groups.push(T::default());
+            }
+            fold(&mut groups[idx], t)?;
+        }
+    }
+    Ok(keys.into_values().into_iter().zip(groups).collect())
+}
+
+/// Flattens a batch that failed as a whole into a stream with the failure as its only item.
+fn flatten<T, I>(batch: Result<I>) -> Either<I::IntoIter, Once<Result<T>>>
+where
+    I: IntoIterator<Item = Result<T>>,
+{
+    match batch {
+        Ok(batch) => Either::Left(batch.into_iter()),
+        Err(e) => Either::Right(once(Err(e))),
+    }
+}
+
+/// Passes on the traversers from `lo` up to `hi`, and stops pulling from the stream once
+/// it reaches `hi`. Errors pass through without counting.
+fn slice<'txn, V, E, P>(
+    mut stream: Traversers<'txn, V, E, P>,
+    lo: usize,
+    hi: Option<usize>,
+) -> Traversers<'txn, V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    let mut seen = 0;
+    Box::new(from_fn(move || loop {
+        if hi.is_some_and(|hi| seen >= hi) {
+            return None;
+        }
+        let t = stream.next()?;
+        if t.is_ok() {
+            seen += 1;
+            if seen <= lo {
+                continue;
+            }
+        }
+        return Some(t);
+    }))
+}
+
+/// Counts the traversers, stopping at the first error.
+fn count<V, E, P>(mut stream: Traversers<V, E, P>) -> Result<usize>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    stream.try_fold(0, |n, t| t.map(|_| n + 1))
+}
+
+fn values<V, E, P>(stream: Traversers<V, E, P>) -> Result<Vec<PValue<V, E, P>>>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    stream.map(|t| t.map(Traverser::into_value)).collect()
+}
+
+fn count_value<V, E, P>(count: usize) -> PValue<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    PValue::I64(i64::try_from(count).unwrap_or(i64::MAX))
+}
+
+/// Adds up numbers, staying an integer of the widest type seen unless a float is involved
+/// or the total overflows. Nothing comes out of an empty stream.
+fn sum<V, E, P>(values: &[PValue<V, E, P>]) -> Result<Option<PValue<V, E, P>>>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    if values.is_empty() {
+        return Ok(None);
+    }
+    let (mut int, mut float, mut wide) = (Some(0_i128), 0.0, false);
+    for value in values {
+        let f = value
+            .as_f64()
+            .ok_or_else(|| Error::InvalidPValue(format!("can't sum {:?}", value)))?;
+        float += f;
+        int = int
+            .zip(value.as_i128())
+            .and_then(|(sum, i)| sum.checked_add(i));
+        wide |= matches!(value, PValue::I128(_));
+    }
+    Ok(Some(match int {
+        Some(int) if wide => PValue::I128(int),
+        Some(int) => i64::try_from(int).map_or(PValue::I128(int), PValue::I64),
+        None => PValue::Double(float),
+    }))
+}
+
+/// The first of the values that every other compares `ordering` to, for `min` and `max`.
+fn extreme<V, E, P>(
+    values: Vec<PValue<V, E, P>>,
+    ordering: Ordering,
+) -> Result<Option<PValue<V, E, P>>>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    let mut values = values.into_iter();
+    let mut best = match values.next() {
+        Some(first) => first,
+        None => return Ok(None),
+    };
+    for value in values {
+        match value.compare(&best) {
+            Some(o) if o == ordering => best = value,
+            Some(_) => {}
+            None => {
+                return Err(Error::InvalidPValue(format!(
+                    "can't compare {:?} with {:?}",
+                    value, best
+                )))
+            }
+        }
+    }
+    Ok(Some(best))
+}
+
+/// Keeps the traversers `f` accepts, passing errors on both sides through.
+fn filter<'txn, V, E, P, F>(
+    stream: Traversers<'txn, V, E, P>,
+    mut f: F,
+) -> Traversers<'txn, V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+    F: 'txn + FnMut(&Traverser<V, E, P>) -> Result<bool>,
+{
+    Box::new(stream.filter_map(move |t| t.and_then(|t| Ok(f(&t)?.then_some(t))).transpose()))
+}
+
+/// Moves every traverser on to the value `f` maps it to.
+fn map<'txn, V, E, P, F>(stream: Traversers<'txn, V, E, P>, mut f: F) -> Traversers<'txn, V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+    F: 'txn + FnMut(&Traverser<V, E, P>) -> Result<PValue<V, E, P>>,
+{
+    Box::new(stream.map(move |t| t.and_then(|t| Ok(t.split(f(&t)?)))))
+}
+
+/// Maps every traverser to a stream of traversers, passing errors on both sides through.
+fn flat_map<'txn, V, E, P, F, I>(
+    stream: Traversers<'txn, V, E, P>,
+    mut f: F,
+) -> Traversers<'txn, V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+    F: 'txn + FnMut(Traverser<V, E, P>) -> Result<I>,
+    I: 'txn + Iterator<Item = Result<Traverser<V, E, P>>>,
+{
+    Box::new(stream.flat_map(move |t| flatten(t.and_then(&mut f))))
+}
+
+fn adjacent<'txn, V, E, P>(
+    graph: &'txn Graph<V, E, P>,
+    txn: &'txn RoTxn,
+    stream: Traversers<'txn, V, E, P>,
+    direction: Direction,
+    labels: Labels<E>,
+    edges: bool,
+) -> Traversers<'txn, V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    flat_map(stream, move |t| {
+        let id = t.vertex()?.id.ok_or(Error::VertexInvalid)?;
+        let adjacent = graph.adjacent_edges(txn, id, direction, &labels.0)?;
+        Ok(adjacent.map(move |next| {
+            let (edge, other) = next?;
+            let value = if edges {
+                PValue::Edge(
+                    graph
+                        .get_edge_by_id(txn, &edge)?
+                        .ok_or(Error::NotFound(edge))?,
+                )
+            } else {
+                PValue::Vertex(
+                    graph
+                        .get_vertex_by_id(txn, &other)?
+                        .ok_or(Error::NotFound(other))?,
+                )
+            };
+            Ok(t.split(value))
+        }))
+    })
+}
+
+fn edge_vertex<'txn, V, E, P, F>(
+    graph: &'txn Graph<V, E, P>,
+    txn: &'txn RoTxn,
+    stream: Traversers<'txn, V, E, P>,
+    end: F,
+) -> Traversers<'txn, V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+    F: 'txn + Fn(&Edge<V, E, P>, Option<Id>) -> Result<Id>,
+{
+    Box::new(stream.map(move |t| {
+        let t = t?;
+        let id = end(t.edge()?, t.origin)?;
+        let vertex = graph
+            .get_vertex_by_id(txn, &id)?
+            .ok_or(Error::NotFound(id))?;
+        Ok(t.split(PValue::Vertex(vertex)))
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    type PV = PValue<String, String, ()>;
+
+    #[rstest]
+    fn test_sum() -> Result<()> {
+        assert_eq!(sum(&[PV::I32(1), PV::I64(2)])?, Some(PV::I64(3)));
+        assert_eq!(sum(&[PV::I32(1), PV::I128(2)])?, Some(PV::I128(3)));
+        assert_eq!(
+            sum(&[PV::I64(i64::MAX), PV::I32(1)])?,
+            Some(PV::I128(1 << 63))
+        );
+        assert_eq!(sum(&[PV::I32(1), PV::Double(0.5)])?, Some(PV::Double(1.5)));
+        assert_eq!(sum::<String, String, ()>(&[])?, None);
+        assert!(sum(&[PV::I32(1), PV::String("2".into())]).is_err());
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_extreme() -> Result<()> {
+        let values = vec![PV::I32(3), PV::Double(1.5), PV::I64(3), PV::I32(2)];
+        assert_eq!(
+            extreme(values.clone(), Ordering::Less)?,
+            Some(PV::Double(1.5))
+        );
+        assert_eq!(extreme(values, Ordering::Greater)?, Some(PV::I32(3)));
+        assert_eq!(extreme(vec![PV::None], Ordering::Less)?, Some(PV::None));
+        assert!(extreme(vec![PV::I32(1), PV::Bool(true)], Ordering::Less).is_err());
+        Ok(())
+    }
 }
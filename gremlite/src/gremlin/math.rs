@@ -0,0 +1,309 @@
+//! The expressions of the `math` step: numbers and variables combined with `+`, `-`, `*`,
+//! `/`, `%` and `^`, brackets, and functions of one argument.
+
+use crate::error::{Error, Result};
+
+/// A parsed `math` expression. Its variables are numbered in the order they first appear.
+#[derive(Debug, Clone)]
+pub struct Expression {
+    root: Node,
+    variables: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Number(f64),
+    Variable(usize),
+    Neg(Box<Self>),
+    Binary(Op, Box<Self>, Box<Self>),
+    Call(fn(f64) -> f64, Box<Self>),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+}
+
+impl Op {
+    fn apply(self, a: f64, b: f64) -> f64 {
+        match self {
+            Self::Add => a + b,
+            Self::Sub => a - b,
+            Self::Mul => a * b,
+            Self::Div => a / b,
+            Self::Rem => a % b,
+            Self::Pow => a.powf(b),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Symbol(char),
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut parser = Parser {
+            source,
+            tokens: tokenize(source)?,
+            pos: 0,
+            variables: vec![],
+        };
+        let root = parser.sum()?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(parser.error(&format!("unexpected {:?}", token)));
+        }
+        Ok(Self {
+            root,
+            variables: parser.variables,
+        })
+    }
+
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    /// Evaluates the expression with `values` for its variables, in step with `variables`.
+    pub fn evaluate(&self, values: &[f64]) -> f64 {
+        evaluate(&self.root, values)
+    }
+}
+
+fn evaluate(node: &Node, values: &[f64]) -> f64 {
+    match node {
+        Node::Number(n) => *n,
+        Node::Variable(idx) => values[*idx],
+        Node::Neg(node) => -evaluate(node, values),
+        Node::Binary(op, a, b) => op.apply(evaluate(a, values), evaluate(b, values)),
+        Node::Call(f, node) => f(evaluate(node, values)),
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = source.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut end = start;
+            while let Some(&(idx, c)) = chars.peek() {
+                if !(c.is_ascii_digit() || c == '.') {
+                    break;
+                }
+                end = idx + c.len_utf8();
+                chars.next();
+            }
+            let number = source[start..end].parse().map_err(|_| {
+                Error::Math(format!(
+                    "bad number {:?} in {:?}",
+                    &source[start..end],
+                    source
+                ))
+            })?;
+            tokens.push(Token::Number(number));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut end = start;
+            while let Some(&(idx, c)) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                end = idx + c.len_utf8();
+                chars.next();
+            }
+            tokens.push(Token::Name(source[start..end].to_string()));
+        } else if "+-*/%^()".contains(c) {
+            tokens.push(Token::Symbol(c));
+            chars.next();
+        } else {
+            return Err(Error::Math(format!("unexpected {:?} in {:?}", c, source)));
+        }
+    }
+    Ok(tokens)
+}
+
+fn function(name: &str) -> Option<fn(f64) -> f64> {
+    Some(match name {
+        "abs" => f64::abs,
+        "ceil" => f64::ceil,
+        "floor" => f64::floor,
+        "round" => f64::round,
+        "signum" => f64::signum,
+        "sqrt" => f64::sqrt,
+        "cbrt" => f64::cbrt,
+        "exp" => f64::exp,
+        "log" => f64::ln,
+        "log2" => f64::log2,
+        "log10" => f64::log10,
+        "sin" => f64::sin,
+        "cos" => f64::cos,
+        "tan" => f64::tan,
+        "asin" => f64::asin,
+        "acos" => f64::acos,
+        "atan" => f64::atan,
+        "sinh" => f64::sinh,
+        "cosh" => f64::cosh,
+        "tanh" => f64::tanh,
+        _ => return None,
+    })
+}
+
+/// A recursive descent parser, one method per level of precedence from lowest to highest.
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+    variables: Vec<String>,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> Error {
+        Error::Math(format!("{} in {:?}", message, self.source))
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// Takes the next token if it's one of `symbols`.
+    fn symbol(&mut self, symbols: &str) -> Option<char> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Symbol(c)) if symbols.contains(*c) => {
+                self.pos += 1;
+                Some(*c)
+            }
+            _ => None,
+        }
+    }
+
+    fn sum(&mut self) -> Result<Node> {
+        let mut node = self.product()?;
+        while let Some(c) = self.symbol("+-") {
+            let op = if c == '+' { Op::Add } else { Op::Sub };
+            node = Node::Binary(op, Box::new(node), Box::new(self.product()?));
+        }
+        Ok(node)
+    }
+
+    fn product(&mut self) -> Result<Node> {
+        let mut node = self.unary()?;
+        while let Some(c) = self.symbol("*/%") {
+            let op = match c {
+                '*' => Op::Mul,
+                '/' => Op::Div,
+                _ => Op::Rem,
+            };
+            node = Node::Binary(op, Box::new(node), Box::new(self.unary()?));
+        }
+        Ok(node)
+    }
+
+    fn unary(&mut self) -> Result<Node> {
+        match self.symbol("+-") {
+            Some('-') => Ok(Node::Neg(Box::new(self.unary()?))),
+            Some(_) => self.unary(),
+            None => self.power(),
+        }
+    }
+
+    /// `^` binds tighter than a sign before it and groups to the right, so `-2^2` is `-4`
+    /// and `2^3^2` is `2^9`.
+    fn power(&mut self) -> Result<Node> {
+        let base = self.atom()?;
+        if self.symbol("^").is_some() {
+            return Ok(Node::Binary(
+                Op::Pow,
+                Box::new(base),
+                Box::new(self.unary()?),
+            ));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Node> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(Node::Number(n)),
+            Some(Token::Symbol('(')) => self.bracketed(),
+            Some(Token::Name(name)) => {
+                if self.symbol("(").is_some() {
+                    let f = function(&name)
+                        .ok_or_else(|| self.error(&format!("unknown function {:?}", name)))?;
+                    return Ok(Node::Call(f, Box::new(self.bracketed()?)));
+                }
+                let idx = match self.variables.iter().position(|v| *v == name) {
+                    Some(idx) => idx,
+                    None => {
+                        self.variables.push(name);
+                        self.variables.len() - 1
+                    }
+                };
+                Ok(Node::Variable(idx))
+            }
+            Some(token) => Err(self.error(&format!("unexpected {:?}", token))),
+            None => Err(self.error("unexpected end")),
+        }
+    }
+
+    /// The rest of a bracketed expression, after its `(`.
+    fn bracketed(&mut self) -> Result<Node> {
+        let node = self.sum()?;
+        self.symbol(")").ok_or_else(|| self.error("missing )"))?;
+        Ok(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    fn test_evaluate() -> Result<()> {
+        for (source, expected) in &[
+            ("1 + 2 * 3", 7.0),
+            ("(1 + 2) * 3", 9.0),
+            ("10 - 4 - 3", 3.0),
+            ("2 ^ 3 ^ 2", 512.0),
+            ("-2 ^ 2", -4.0),
+            ("7 % 4 / 2", 1.5),
+            ("--1.5", 1.5),
+            ("sqrt(16) + abs(-2)", 6.0),
+            ("floor(2.7) * ceil(0.2)", 2.0),
+        ] {
+            let expression = Expression::parse(source)?;
+            assert!(expression.variables().is_empty());
+            assert_eq!(expression.evaluate(&[]), *expected, "{}", source);
+        }
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_variables() -> Result<()> {
+        let expression = Expression::parse("_ * b - a / _")?;
+        assert_eq!(expression.variables(), ["_", "b", "a"]);
+        assert_eq!(expression.evaluate(&[2.0, 3.0, 4.0]), 4.0);
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_invalid() {
+        for source in &["", "1 +", "(1 + 2", "1 2", "1..2", "1 $ 2", "nope(1)", ")"] {
+            assert!(
+                matches!(Expression::parse(source), Err(Error::Math(_))),
+                "{}",
+                source
+            );
+        }
+    }
+}
@@ -1,17 +1,26 @@
+pub mod anonymous;
 pub(crate) mod bytecode;
 pub(crate) mod executor;
+pub(crate) mod math;
+pub(crate) mod predicate;
 pub(crate) mod terminator;
+pub(crate) mod traverser;
 
 use crate::{
     error::{Error, Result},
-    graph::{Id, Ids, PValue, Writable},
-    gremlin::{bytecode::Bytecode, terminator::Terminator},
+    graph::{Id, Ids, Labels, PValue, Writable},
+    gremlin::bytecode::Bytecode,
     heed::Graph,
 };
+pub use anonymous as __;
 use bytecode::Instruction;
-use heed::RwTxn;
+pub use bytecode::{By, Endpoint, MergeE, MergeV, Order};
+use heed::{RoTxn, RwTxn};
+pub use predicate::Predicate;
 use std::{convert::TryInto, fmt::Debug};
-use terminator::TraversalTerminator;
+pub use terminator::{
+    Anonymous, Iter, ReadOnly, ReadWrite, Results, Terminator, TraversalTerminator, Values,
+};
 
 pub trait TraversalSource<'graph, V, E, P>
 where
@@ -36,6 +45,19 @@ where
     fn add_e<'a>(&'a self, label: E) -> GraphTraversal<'graph, V, E, P>
     where
         'graph: 'a;
+
+    fn inject<'a, I>(&'a self, values: I) -> GraphTraversal<'graph, V, E, P>
+    where
+        I: IntoIterator<Item = PValue<V, E, P>>,
+        'graph: 'a;
+
+    fn merge_v<'a>(&'a self, merge: MergeV<V, E, P>) -> GraphTraversal<'graph, V, E, P>
+    where
+        'graph: 'a;
+
+    fn merge_e<'a>(&'a self, merge: MergeE<V, E, P>) -> GraphTraversal<'graph, V, E, P>
+    where
+        'graph: 'a;
 }
 
 #[derive(Clone)]
@@ -46,6 +68,7 @@ where
     P: 'static + Writable + Eq,
 {
     graph: &'graph Graph<V, E, P>,
+    bytecode: Bytecode<V, E, P>,
 }
 
 impl<'graph, V, E, P> RWTraversalSource<'graph, V, E, P>
@@ -54,8 +77,21 @@ where
     E: 'static + Writable,
     P: 'static + Writable + Eq,
 {
-    pub fn new(graph: &'graph Graph<V, E, P>) -> Self {
-        Self { graph }
+    pub const fn new(graph: &'graph Graph<V, E, P>) -> Self {
+        Self {
+            graph,
+            bytecode: Bytecode::new(),
+        }
+    }
+
+    /// A source whose traversals start with the side effect `key` set to `initial`, for
+    /// steps like `store` to add to and `cap` to read.
+    pub fn with_side_effect<K: Into<String>>(&self, key: K, initial: PValue<V, E, P>) -> Self {
+        let mut source = self.clone();
+        source
+            .bytecode
+            .add_source(Instruction::WithSideEffect(key.into(), initial));
+        source
     }
 }
 
@@ -70,7 +106,7 @@ where
         T: Into<Ids>,
         'graph: 'a,
     {
-        let mut code = Bytecode::default();
+        let mut code = self.bytecode.clone();
         code.add_step(Instruction::Vert(bytecode::Vert(ids.into())));
         GraphTraversal::new(TraversalBuilder::new(code), self.graph.terminator())
     }
@@ -80,7 +116,7 @@ where
         T: Into<Ids>,
         'graph: 'a,
     {
-        let mut code = Bytecode::default();
+        let mut code = self.bytecode.clone();
         code.add_step(Instruction::Edge(bytecode::Edge(ids.into())));
         GraphTraversal::new(TraversalBuilder::new(code), self.graph.terminator())
     }
@@ -89,7 +125,7 @@ where
     where
         'graph: 'a,
     {
-        let mut code = Bytecode::default();
+        let mut code = self.bytecode.clone();
         code.add_step(Instruction::AddV(label));
         GraphTraversal::new(TraversalBuilder::new(code), self.graph.terminator())
     }
@@ -98,23 +134,180 @@ where
     where
         'graph: 'a,
     {
-        let mut code = Bytecode::default();
+        let mut code = self.bytecode.clone();
         code.add_step(Instruction::AddE(label));
         GraphTraversal::new(TraversalBuilder::new(code), self.graph.terminator())
     }
+
+    fn inject<'a, I>(&'a self, values: I) -> GraphTraversal<'graph, V, E, P>
+    where
+        I: IntoIterator<Item = PValue<V, E, P>>,
+        'graph: 'a,
+    {
+        let mut code = self.bytecode.clone();
+        code.add_step(Instruction::Inject(values.into_iter().collect()));
+        GraphTraversal::new(TraversalBuilder::new(code), self.graph.terminator())
+    }
+
+    fn merge_v<'a>(&'a self, merge: MergeV<V, E, P>) -> GraphTraversal<'graph, V, E, P>
+    where
+        'graph: 'a,
+    {
+        let mut code = self.bytecode.clone();
+        code.add_step(Instruction::MergeV(merge));
+        GraphTraversal::new(TraversalBuilder::new(code), self.graph.terminator())
+    }
+
+    fn merge_e<'a>(&'a self, merge: MergeE<V, E, P>) -> GraphTraversal<'graph, V, E, P>
+    where
+        'graph: 'a,
+    {
+        let mut code = self.bytecode.clone();
+        code.add_step(Instruction::MergeE(merge));
+        GraphTraversal::new(TraversalBuilder::new(code), self.graph.terminator())
+    }
+}
+
+/// A read-only traversal source, handed out by `Graph::read_traversal`.
+///
+/// Only the read starts are here, and traversals from it can only be run in a `&RoTxn`,
+/// which fails with `Error::WriteInReadTxn` if any step would write.
+#[derive(Clone)]
+pub struct ROTraversalSource<'graph, V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    graph: &'graph Graph<V, E, P>,
+    bytecode: Bytecode<V, E, P>,
+}
+
+impl<'graph, V, E, P> ROTraversalSource<'graph, V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    pub const fn new(graph: &'graph Graph<V, E, P>) -> Self {
+        Self {
+            graph,
+            bytecode: Bytecode::new(),
+        }
+    }
+
+    /// Like `RWTraversalSource::with_side_effect`.
+    pub fn with_side_effect<K: Into<String>>(&self, key: K, initial: PValue<V, E, P>) -> Self {
+        let mut source = self.clone();
+        source
+            .bytecode
+            .add_source(Instruction::WithSideEffect(key.into(), initial));
+        source
+    }
+
+    pub fn v<T: Into<Ids>>(&self, ids: T) -> GraphTraversal<'graph, V, E, P, ReadOnly> {
+        let mut code = self.bytecode.clone();
+        code.add_step(Instruction::Vert(bytecode::Vert(ids.into())));
+        GraphTraversal::new(TraversalBuilder::new(code), self.graph.terminator())
+    }
+
+    pub fn e<T: Into<Ids>>(&self, ids: T) -> GraphTraversal<'graph, V, E, P, ReadOnly> {
+        let mut code = self.bytecode.clone();
+        code.add_step(Instruction::Edge(bytecode::Edge(ids.into())));
+        GraphTraversal::new(TraversalBuilder::new(code), self.graph.terminator())
+    }
+
+    pub fn inject<I>(&self, values: I) -> GraphTraversal<'graph, V, E, P, ReadOnly>
+    where
+        I: IntoIterator<Item = PValue<V, E, P>>,
+    {
+        let mut code = self.bytecode.clone();
+        code.add_step(Instruction::Inject(values.into_iter().collect()));
+        GraphTraversal::new(TraversalBuilder::new(code), self.graph.terminator())
+    }
+}
+
+/// A child traversal, started from `__`.
+pub type AnonymousTraversal<V, E, P> = GraphTraversal<'static, V, E, P, Anonymous>;
+
+/// What `from` and `to` take: anything with a vertex id, a step label, or an anonymous
+/// traversal.
+pub trait IntoEndpoint<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    fn into_endpoint(self) -> Result<Endpoint<V, E, P>>;
+}
+
+impl<V, E, P, T> IntoEndpoint<V, E, P> for T
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+    T: TryInto<Id, Error = Error>,
+{
+    fn into_endpoint(self) -> Result<Endpoint<V, E, P>> {
+        Ok(Endpoint::Id(self.try_into()?))
+    }
+}
+
+impl<V, E, P> IntoEndpoint<V, E, P> for &str
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    fn into_endpoint(self) -> Result<Endpoint<V, E, P>> {
+        Ok(Endpoint::Label(self.to_string()))
+    }
+}
+
+impl<V, E, P> IntoEndpoint<V, E, P> for String
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    fn into_endpoint(self) -> Result<Endpoint<V, E, P>> {
+        Ok(Endpoint::Label(self))
+    }
+}
+
+impl<V, E, P> IntoEndpoint<V, E, P> for AnonymousTraversal<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    fn into_endpoint(self) -> Result<Endpoint<V, E, P>> {
+        Ok(Endpoint::Traversal(self.builder.bytecode))
+    }
 }
 
-pub struct GraphTraversal<'graph, V, E, P>
+impl<V, E, P> From<AnonymousTraversal<V, E, P>> for By<V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    fn from(traversal: AnonymousTraversal<V, E, P>) -> Self {
+        Self::Traversal(traversal.builder.bytecode)
+    }
+}
+
+pub struct GraphTraversal<'graph, V, E, P, M = ReadWrite>
 where
     V: 'static + Writable,
     E: 'static + Writable,
     P: 'static + Writable + Eq,
 {
     builder: TraversalBuilder<V, E, P>,
-    terminator: TraversalTerminator<'graph, V, E, P>,
+    terminator: TraversalTerminator<'graph, V, E, P, M>,
 }
 
-impl<'graph, V, E, P> Debug for GraphTraversal<'graph, V, E, P>
+impl<'graph, V, E, P, M> Debug for GraphTraversal<'graph, V, E, P, M>
 where
     V: 'static + Writable,
     E: 'static + Writable,
@@ -125,15 +318,15 @@ where
     }
 }
 
-impl<'term, V, E, P> GraphTraversal<'term, V, E, P>
+impl<'term, V, E, P, M> GraphTraversal<'term, V, E, P, M>
 where
     V: Writable,
     E: Writable,
     P: Writable + Eq,
 {
-    pub fn new(
+    pub const fn new(
         builder: TraversalBuilder<V, E, P>,
-        terminator: TraversalTerminator<'term, V, E, P>,
+        terminator: TraversalTerminator<'term, V, E, P, M>,
     ) -> Self {
         Self {
             builder,
@@ -141,43 +334,572 @@ where
         }
     }
 
-    pub fn bytecode(&self) -> &Bytecode<V, E, P> {
+    pub const fn bytecode(&self) -> &Bytecode<V, E, P> {
         self.builder.bytecode()
     }
 
-    pub fn from<A>(mut self, id: A) -> Result<Self>
+    /// The vertex the edges of the `add_e` before it go from: an element or id, a step
+    /// label, or an anonymous traversal. Without it, they go from the vertex adding them.
+    pub fn from<A>(mut self, from: A) -> Result<Self>
     where
-        A: TryInto<Id, Error = Error>,
+        A: IntoEndpoint<V, E, P>,
     {
-        self.builder = self.builder.from(id)?;
+        self.builder = self.builder.from(from)?;
         Ok(self)
     }
 
-    pub fn to<A>(mut self, id: A) -> Result<Self>
+    /// Like `from`, for the vertex the edges go to.
+    pub fn to<A>(mut self, to: A) -> Result<Self>
     where
-        A: TryInto<Id, Error = Error>,
+        A: IntoEndpoint<V, E, P>,
     {
-        self.builder = self.builder.to(id)?;
+        self.builder = self.builder.to(to)?;
         Ok(self)
     }
 
-    pub fn to_list<'a>(
-        &'a self,
-        txn: &mut RwTxn<'term>,
-    ) -> <TraversalTerminator<'term, V, E, P> as Terminator<'term, PValue<V, E, P>, V, E, P>>::List
+    pub fn v<T: Into<Ids>>(self, ids: T) -> Self {
+        self.add_step(Instruction::Vert(bytecode::Vert(ids.into())))
+    }
+
+    pub fn add_v(self, label: V) -> Self {
+        self.add_step(Instruction::AddV(label))
+    }
+
+    pub fn add_e(self, label: E) -> Self {
+        self.add_step(Instruction::AddE(label))
+    }
+
+    /// Sets `key` to `value` on every vertex or edge passing through.
+    pub fn property(self, key: P, value: PValue<V, E, P>) -> Self {
+        self.add_step(Instruction::Property(key, value))
+    }
+
+    /// The vertices matching `merge`, once for each traverser, or a new one made from it if
+    /// there are none. Every match and creation happens in the traversal's transaction.
+    pub fn merge_v(self, merge: MergeV<V, E, P>) -> Self {
+        self.add_step(Instruction::MergeV(merge))
+    }
+
+    /// Like `merge_v`, for edges.
+    pub fn merge_e(self, merge: MergeE<V, E, P>) -> Self {
+        self.add_step(Instruction::MergeE(merge))
+    }
+
+    /// Parameters for `merge_v` or `merge_e` to give what they create, on top of the ones
+    /// they matched on, which these can't change.
+    pub fn on_create<I: IntoIterator<Item = (P, PValue<V, E, P>)>>(self, params: I) -> Self {
+        self.add_step(Instruction::OnCreate(params.into_iter().collect()))
+    }
+
+    /// Parameters for `merge_v` or `merge_e` to set on what they match.
+    pub fn on_match<I: IntoIterator<Item = (P, PValue<V, E, P>)>>(self, params: I) -> Self {
+        self.add_step(Instruction::OnMatch(params.into_iter().collect()))
+    }
+
+    /// Removes every vertex, edge or property passing through, along with their index
+    /// rows, and passes nothing on. Vertices go with all of their edges, and properties are
+    /// the ones from `properties`. Anything else, maps included, is an error.
+    pub fn drop(self) -> Self {
+        self.add_step(Instruction::Drop)
+    }
+
+    pub fn out<L: Into<Labels<E>>>(self, labels: L) -> Self {
+        self.add_step(Instruction::Out(labels.into()))
+    }
+
+    pub fn in_<L: Into<Labels<E>>>(self, labels: L) -> Self {
+        self.add_step(Instruction::In(labels.into()))
+    }
+
+    pub fn both<L: Into<Labels<E>>>(self, labels: L) -> Self {
+        self.add_step(Instruction::Both(labels.into()))
+    }
+
+    pub fn out_e<L: Into<Labels<E>>>(self, labels: L) -> Self {
+        self.add_step(Instruction::OutE(labels.into()))
+    }
+
+    pub fn in_e<L: Into<Labels<E>>>(self, labels: L) -> Self {
+        self.add_step(Instruction::InE(labels.into()))
+    }
+
+    pub fn both_e<L: Into<Labels<E>>>(self, labels: L) -> Self {
+        self.add_step(Instruction::BothE(labels.into()))
+    }
+
+    pub fn out_v(self) -> Self {
+        self.add_step(Instruction::OutV)
+    }
+
+    pub fn in_v(self) -> Self {
+        self.add_step(Instruction::InV)
+    }
+
+    pub fn other_v(self) -> Self {
+        self.add_step(Instruction::OtherV)
+    }
+
+    pub fn has<T: Into<Predicate<V, E, P>>>(self, key: P, predicate: T) -> Self {
+        self.add_step(Instruction::Has(key, predicate.into()))
+    }
+
+    pub fn has_label<L: Into<Labels<V>>>(self, labels: L) -> Self {
+        self.add_step(Instruction::HasLabel(labels.into()))
+    }
+
+    pub fn has_edge_label<L: Into<Labels<E>>>(self, labels: L) -> Self {
+        self.add_step(Instruction::HasEdgeLabel(labels.into()))
+    }
+
+    pub fn has_id<T: Into<Ids>>(self, ids: T) -> Self {
+        self.add_step(Instruction::HasId(ids.into()))
+    }
+
+    pub fn has_not(self, key: P) -> Self {
+        self.add_step(Instruction::HasNot(key))
+    }
+
+    /// Keeps elements that have a value for any of `keys`.
+    pub fn has_key<L: Into<Labels<P>>>(self, keys: L) -> Self {
+        self.add_step(Instruction::HasKey(keys.into()))
+    }
+
+    pub fn count(self) -> Self {
+        self.add_step(Instruction::Count)
+    }
+
+    /// Adds up numbers, which stay integers unless a float is involved.
+    pub fn sum(self) -> Self {
+        self.add_step(Instruction::Sum)
+    }
+
+    pub fn min(self) -> Self {
+        self.add_step(Instruction::Min)
+    }
+
+    pub fn max(self) -> Self {
+        self.add_step(Instruction::Max)
+    }
+
+    pub fn mean(self) -> Self {
+        self.add_step(Instruction::Mean)
+    }
+
+    /// Gathers every value into a single `PValue::List`.
+    pub fn fold(self) -> Self {
+        self.add_step(Instruction::Fold)
+    }
+
+    /// Splits lists and sets into their elements, and maps and entries into single entries.
+    pub fn unfold(self) -> Self {
+        self.add_step(Instruction::Unfold)
+    }
+
+    /// Passes on at most the first `n` results.
+    pub fn limit(self, n: usize) -> Self {
+        self.add_step(Instruction::Limit(n))
+    }
+
+    /// Passes on the results from `lo` up to but not including `hi`.
+    pub fn range(self, lo: usize, hi: usize) -> Self {
+        self.add_step(Instruction::Range(lo, hi))
+    }
+
+    pub fn skip(self, n: usize) -> Self {
+        self.add_step(Instruction::Skip(n))
+    }
+
+    /// Passes on the last `n` results, which means reading all of them first.
+    pub fn tail(self, n: usize) -> Self {
+        self.add_step(Instruction::Tail(n))
+    }
+
+    /// Keeps the elements with ids after `id`, for resuming a traversal from the last
+    /// element a client saw.
+    ///
+    /// `V()` and `E()` produce elements in id order, and right after either of them this
+    /// seeks straight past `id` rather than reading what came before it.
+    pub fn after<A>(self, id: A) -> Result<Self>
+    where
+        A: TryInto<Id, Error = Error>,
+    {
+        Ok(self.add_step(Instruction::After(id.try_into()?)))
+    }
+
+    /// Sorts the results by the `by` modulators that follow, or by value if there are none.
+    pub fn order(self) -> Self {
+        self.add_step(Instruction::Order)
+    }
+
+    /// Modulates the step before it by `by`: a parameter key, `By::Label`, `By::Id`,
+    /// `By::Value` or the first result of an anonymous traversal. Elements without the key
+    /// are dropped.
+    pub fn by<B>(self, by: B) -> Self
+    where
+        B: Into<By<V, E, P>>,
+    {
+        self.by_order(by, Order::Asc)
+    }
+
+    /// Like `by`, for `order()`, sorting in `order`.
+    pub fn by_order<B>(self, by: B, order: Order) -> Self
+    where
+        B: Into<By<V, E, P>>,
+    {
+        self.add_step(Instruction::By(by.into(), order))
+    }
+
+    /// Gathers the results into a `PValue::Entries` keyed by the first `by`, holding lists
+    /// of the results or of the second `by` of them.
+    pub fn group(self) -> Self {
+        self.add_step(Instruction::Group)
+    }
+
+    /// Counts the results by the `by` that follows, or by value, into a `PValue::Entries`.
+    pub fn group_count(self) -> Self {
+        self.add_step(Instruction::GroupCount)
+    }
+
+    /// Drops results already seen, or whose `by` was already seen.
+    pub fn dedup(self) -> Self {
+        self.add_step(Instruction::Dedup)
+    }
+
+    /// The values of the parameters `keys`, or of every parameter if there are none, in
+    /// key order.
+    pub fn values<T: Into<Labels<P>>>(self, keys: T) -> Self {
+        self.add_step(Instruction::Values(keys.into()))
+    }
+
+    /// Like `values`, but each as a `PValue::Property` along with its key.
+    pub fn properties<T: Into<Labels<P>>>(self, keys: T) -> Self {
+        self.add_step(Instruction::Properties(keys.into()))
+    }
+
+    /// The parameters of each element as a `PValue::Map`. With tokens, it's instead a
+    /// `PValue::Entries` led by the `Token("id")` and `Token("label")` entries, with the
+    /// parameters keyed by `PValue::Key`.
+    pub fn value_map(self, with_tokens: bool) -> Self {
+        self.add_step(Instruction::ValueMap(with_tokens))
+    }
+
+    /// Like `value_map(true)`, with edges also getting `Token("IN")` and `Token("OUT")`
+    /// entries for the ids of their vertices.
+    pub fn element_map(self) -> Self {
+        self.add_step(Instruction::ElementMap)
+    }
+
+    pub fn id(self) -> Self {
+        self.add_step(Instruction::Id)
+    }
+
+    /// The label of each element, as a `PValue::VertexLabel` or `PValue::EdgeLabel`.
+    pub fn label(self) -> Self {
+        self.add_step(Instruction::Label)
+    }
+
+    /// Labels the current value of each traverser, for `select` and `path` to refer to.
+    pub fn as_<L: Into<String>>(self, label: L) -> Self {
+        self.add_step(Instruction::As(label.into()))
+    }
+
+    /// The value last labelled with the only label given, or with several, a
+    /// `PValue::Entries` of them keyed by `PValue::String`. Traversers missing any of the
+    /// labels are dropped.
+    pub fn select<I, L>(self, labels: I) -> Self
+    where
+        I: IntoIterator<Item = L>,
+        L: Into<String>,
+    {
+        self.add_step(Instruction::Select(
+            labels.into_iter().map(Into::into).collect(),
+        ))
+    }
+
+    /// Every value each traverser has been at, as a `PValue::Path`.
+    pub fn path(self) -> Self {
+        self.add_step(Instruction::Path)
+    }
+
+    /// Keeps the traversers that haven't been at any value twice.
+    pub fn simple_path(self) -> Self {
+        self.add_step(Instruction::SimplePath)
+    }
+
+    /// Keeps the traversers that have been at some value twice.
+    pub fn cyclic_path(self) -> Self {
+        self.add_step(Instruction::CyclicPath)
+    }
+
+    /// Keeps the values that pass `predicate`.
+    pub fn is<T: Into<Predicate<V, E, P>>>(self, predicate: T) -> Self {
+        self.add_step(Instruction::Is(predicate.into()))
+    }
+
+    /// Runs `traversal` over each traverser again and again, until the `until` or `times`
+    /// after it says to stop. The traversers come out of the loop then, and also on every
+    /// time round that they pass an `emit`.
+    ///
+    /// Without `times`, a traverser that comes back to a value it already had in the loop
+    /// doesn't go round again, so cycles in the graph can't loop forever. It still comes
+    /// out if it passes `until` or `emit`.
+    pub fn repeat(self, traversal: AnonymousTraversal<V, E, P>) -> Self {
+        self.add_step(Instruction::Repeat(traversal.builder.bytecode))
+    }
+
+    /// Ends a `repeat` for the traversers that `traversal` has results for. Before the
+    /// `repeat`, it's also checked before going round the first time.
+    pub fn until(self, traversal: AnonymousTraversal<V, E, P>) -> Self {
+        self.add_step(Instruction::Until(traversal.builder.bytecode))
+    }
+
+    /// Ends a `repeat` after `n` times round.
+    pub fn times(self, n: usize) -> Self {
+        self.add_step(Instruction::Times(n))
+    }
+
+    /// Passes on every traverser from each time round a `repeat`, as well as those that
+    /// come out at the end. Before the `repeat`, it passes on the traversers going in too.
+    pub fn emit(self) -> Self {
+        self.add_step(Instruction::Emit(Bytecode::default()))
+    }
+
+    /// Like `emit`, for the traversers that `traversal` has results for.
+    pub fn emit_if(self, traversal: AnonymousTraversal<V, E, P>) -> Self {
+        self.add_step(Instruction::Emit(traversal.builder.bytecode))
+    }
+
+    /// The results of each of `traversals` in turn.
+    pub fn union<I>(self, traversals: I) -> Self
+    where
+        I: IntoIterator<Item = AnonymousTraversal<V, E, P>>,
+    {
+        self.add_step(Instruction::Union(bytecodes(traversals)))
+    }
+
+    /// The results of the first of `traversals` that has any, as in the "get or create"
+    /// pattern `coalesce([__::v(()).has(..), __::add_v(..)])`.
+    pub fn coalesce<I>(self, traversals: I) -> Self
+    where
+        I: IntoIterator<Item = AnonymousTraversal<V, E, P>>,
+    {
+        self.add_step(Instruction::Coalesce(bytecodes(traversals)))
+    }
+
+    /// The results of `traversal`, or the traverser as it was if there are none.
+    pub fn optional(self, traversal: AnonymousTraversal<V, E, P>) -> Self {
+        self.add_step(Instruction::Optional(traversal.builder.bytecode))
+    }
+
+    /// The results of `then` for the traversers that `test` has results for, and of
+    /// `otherwise` for the rest.
+    pub fn choose(
+        self,
+        test: AnonymousTraversal<V, E, P>,
+        then: AnonymousTraversal<V, E, P>,
+        otherwise: AnonymousTraversal<V, E, P>,
+    ) -> Self {
+        self.add_step(Instruction::Choose(
+            test.builder.bytecode,
+            then.builder.bytecode,
+            otherwise.builder.bytecode,
+        ))
+    }
+
+    /// Keeps the traversers that `traversal` has results for.
+    pub fn where_(self, traversal: AnonymousTraversal<V, E, P>) -> Self {
+        self.add_step(Instruction::Where(traversal.builder.bytecode))
+    }
+
+    /// Keeps the traversers that `traversal` has no results for.
+    pub fn not(self, traversal: AnonymousTraversal<V, E, P>) -> Self {
+        self.add_step(Instruction::Not(traversal.builder.bytecode))
+    }
+
+    /// Keeps the traversers that every one of `traversals` has results for.
+    pub fn and<I>(self, traversals: I) -> Self
+    where
+        I: IntoIterator<Item = AnonymousTraversal<V, E, P>>,
+    {
+        self.add_step(Instruction::And(bytecodes(traversals)))
+    }
+
+    /// Keeps the traversers that any of `traversals` has results for.
+    pub fn or<I>(self, traversals: I) -> Self
+    where
+        I: IntoIterator<Item = AnonymousTraversal<V, E, P>>,
+    {
+        self.add_step(Instruction::Or(bytecodes(traversals)))
+    }
+
+    /// Adds each value to the side effect `key` as it passes.
+    pub fn store<K: Into<String>>(self, key: K) -> Self {
+        self.add_step(Instruction::Store(key.into()))
+    }
+
+    /// Like `store`, but adds every value before passing any of them on.
+    pub fn aggregate<K: Into<String>>(self, key: K) -> Self {
+        self.add_step(Instruction::Aggregate(key.into()))
+    }
+
+    /// Runs `traversal` for each traverser, for what it does rather than its results, and
+    /// passes the traverser on as it was.
+    pub fn side_effect(self, traversal: AnonymousTraversal<V, E, P>) -> Self {
+        self.add_step(Instruction::SideEffect(traversal.builder.bytecode))
+    }
+
+    /// Once every traverser has gone through, the side effect with the only key given, or
+    /// with several, a `PValue::Entries` of them keyed by `PValue::String`.
+    pub fn cap<I, K>(self, keys: I) -> Self
     where
-        'term: 'a,
+        I: IntoIterator<Item = K>,
+        K: Into<String>,
     {
+        self.add_step(Instruction::Cap(keys.into_iter().map(Into::into).collect()))
+    }
+
+    /// A `PValue::Map` from each of `keys` to the `by` modulators in turn, cycling through
+    /// them if there are fewer than keys, or to the value itself if there are none. Keys
+    /// whose `by` has nothing for a traverser are left out.
+    pub fn project<I: IntoIterator<Item = P>>(self, keys: I) -> Self {
+        self.add_step(Instruction::Project(keys.into_iter().collect()))
+    }
+
+    pub fn constant(self, value: PValue<V, E, P>) -> Self {
+        self.add_step(Instruction::Constant(value))
+    }
+
+    /// Passes `values` on ahead of the traversers from the step before. As the first step,
+    /// they are all that the traversal starts from.
+    pub fn inject<I>(self, values: I) -> Self
+    where
+        I: IntoIterator<Item = PValue<V, E, P>>,
+    {
+        self.add_step(Instruction::Inject(values.into_iter().collect()))
+    }
+
+    /// Evaluates `expression` as a `PValue::Double`. It can use `+`, `-`, `*`, `/`, `%`,
+    /// `^`, brackets and functions like `sqrt` and `abs`. `_` stands for the value, and other
+    /// names for the values labelled with them by `as_`, read through the `by` modulators
+    /// in the order the names first appear.
+    pub fn math<S: Into<String>>(self, expression: S) -> Self {
+        self.add_step(Instruction::Math(expression.into()))
+    }
+
+    /// How many times round the innermost `repeat` each traverser has been.
+    pub fn loops(self) -> Self {
+        self.add_step(Instruction::Loops)
+    }
+
+    fn add_step(mut self, i: Instruction<V, E, P>) -> Self {
+        self.builder.bytecode.add_step(i);
+        self
+    }
+}
+
+// Running a traversal runs its writes, so these take the traversal and it can only run once.
+impl<'term, V, E, P> GraphTraversal<'term, V, E, P, ReadWrite>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    pub fn to_list(self, txn: &mut RwTxn) -> Result<Vec<PValue<V, E, P>>> {
         self.terminator.to_list(txn, self.bytecode())
     }
 
-    pub fn next<'a>(
-        &'a self,
-        txn: &mut RwTxn<'term>,
-    ) -> <TraversalTerminator<'term, V, E, P> as Terminator<'term, PValue<V, E, P>, V, E, P>>::Next
+    pub fn to_set(self, txn: &mut RwTxn) -> Result<Vec<PValue<V, E, P>>> {
+        self.terminator.to_set(txn, self.bytecode())
+    }
+
+    pub fn next(self, txn: &mut RwTxn) -> Result<PValue<V, E, P>> {
+        self.terminator.next(txn, self.bytecode())
+    }
+
+    pub fn iterate(self, txn: &mut RwTxn) -> Result<()> {
+        Terminator::<PValue<V, E, P>, _, _, _, _>::iterate(&self.terminator, txn, self.bytecode())
+    }
+
+    /// Streams results lazily; nothing past the last mutating step runs until it's pulled.
+    pub fn iter<'txn>(self, txn: &'txn mut RwTxn) -> Result<Results<'txn, V, E, P>>
+    where
+        'term: 'txn,
     {
+        let values = Terminator::<PValue<V, E, P>, _, _, _, _>::values(
+            &self.terminator,
+            txn,
+            self.bytecode(),
+        )?;
+        Ok(Results::new(values))
+    }
+
+    /// Starts the traversal and hands back its results, so `Results::has_next` can check for
+    /// one without losing it or running the writes again.
+    pub fn has_next<'txn>(self, txn: &'txn mut RwTxn) -> Result<Results<'txn, V, E, P>>
+    where
+        'term: 'txn,
+    {
+        self.iter(txn)
+    }
+}
+
+impl<'term, V, E, P> GraphTraversal<'term, V, E, P, ReadOnly>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    pub fn to_list(&self, txn: &RoTxn) -> Result<Vec<PValue<V, E, P>>> {
+        self.terminator.to_list(txn, self.bytecode())
+    }
+
+    pub fn to_set(&self, txn: &RoTxn) -> Result<Vec<PValue<V, E, P>>> {
+        self.terminator.to_set(txn, self.bytecode())
+    }
+
+    pub fn next(&self, txn: &RoTxn) -> Result<PValue<V, E, P>> {
         self.terminator.next(txn, self.bytecode())
     }
+
+    /// Whether the traversal has any results, pulling at most the first one.
+    pub fn has_next(&self, txn: &RoTxn) -> Result<bool> {
+        let mut values = Terminator::<PValue<V, E, P>, _, _, _, _>::values(
+            &self.terminator,
+            txn,
+            self.bytecode(),
+        )?;
+        Ok(values.next().transpose()?.is_some())
+    }
+
+    pub fn iterate(&self, txn: &RoTxn) -> Result<()> {
+        Terminator::<PValue<V, E, P>, _, _, _, _>::iterate(&self.terminator, txn, self.bytecode())
+    }
+
+    /// Streams results lazily; nothing past the last mutating step runs until it's pulled.
+    pub fn iter<'txn>(&self, txn: &'txn RoTxn) -> Result<Results<'txn, V, E, P>>
+    where
+        'term: 'txn,
+    {
+        let values = Terminator::<PValue<V, E, P>, _, _, _, _>::values(
+            &self.terminator,
+            txn,
+            self.bytecode(),
+        )?;
+        Ok(Results::new(values))
+    }
+}
+
+fn bytecodes<V, E, P, I>(traversals: I) -> Vec<Bytecode<V, E, P>>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+    I: IntoIterator<Item = AnonymousTraversal<V, E, P>>,
+{
+    traversals
+        .into_iter()
+        .map(|traversal| traversal.builder.bytecode)
+        .collect()
 }
 
 #[derive(Debug)]
@@ -196,27 +918,28 @@ where
     E: Writable,
     P: Writable + Eq,
 {
-    pub fn new(bytecode: Bytecode<V, E, P>) -> Self {
+    pub const fn new(bytecode: Bytecode<V, E, P>) -> Self {
         Self { bytecode }
     }
 
-    pub fn bytecode(&self) -> &Bytecode<V, E, P> {
+    pub const fn bytecode(&self) -> &Bytecode<V, E, P> {
         &self.bytecode
     }
 
-    pub fn from<A>(mut self, id: A) -> Result<Self>
+    pub fn from<A>(mut self, from: A) -> Result<Self>
     where
-        A: TryInto<Id, Error = crate::error::Error>,
+        A: IntoEndpoint<V, E, P>,
     {
-        self.bytecode.add_step(Instruction::From(id.try_into()?));
+        self.bytecode
+            .add_step(Instruction::From(from.into_endpoint()?));
         Ok(self)
     }
 
-    pub fn to<A>(mut self, id: A) -> Result<Self>
+    pub fn to<A>(mut self, to: A) -> Result<Self>
     where
-        A: TryInto<Id, Error = crate::error::Error>,
+        A: IntoEndpoint<V, E, P>,
     {
-        self.bytecode.add_step(Instruction::To(id.try_into()?));
+        self.bytecode.add_step(Instruction::To(to.into_endpoint()?));
         Ok(self)
     }
 }
@@ -0,0 +1,165 @@
+use crate::graph::{PValue, Writable};
+use std::{cmp::Ordering, ops::Bound};
+
+/// Tests a single `PValue`, as used by `has(key, predicate)` and friends.
+///
+/// Values are compared with `PValue::compare`, so `I32(1)` equals `I64(1)`, and values
+/// that can't be compared never pass anything but `neq` and `without`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Predicate<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    Eq(PValue<V, E, P>),
+    Neq(PValue<V, E, P>),
+    Lt(PValue<V, E, P>),
+    Lte(PValue<V, E, P>),
+    Gt(PValue<V, E, P>),
+    Gte(PValue<V, E, P>),
+    /// `lo <= value < hi`
+    Between(PValue<V, E, P>, PValue<V, E, P>),
+    /// `lo < value < hi`
+    Inside(PValue<V, E, P>, PValue<V, E, P>),
+    /// `value < lo || value > hi`
+    Outside(PValue<V, E, P>, PValue<V, E, P>),
+    Within(Vec<PValue<V, E, P>>),
+    Without(Vec<PValue<V, E, P>>),
+}
+
+impl<V, E, P> Predicate<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    pub const fn eq(v: PValue<V, E, P>) -> Self {
+        Self::Eq(v)
+    }
+
+    pub const fn neq(v: PValue<V, E, P>) -> Self {
+        Self::Neq(v)
+    }
+
+    pub const fn lt(v: PValue<V, E, P>) -> Self {
+        Self::Lt(v)
+    }
+
+    pub const fn lte(v: PValue<V, E, P>) -> Self {
+        Self::Lte(v)
+    }
+
+    pub const fn gt(v: PValue<V, E, P>) -> Self {
+        Self::Gt(v)
+    }
+
+    pub const fn gte(v: PValue<V, E, P>) -> Self {
+        Self::Gte(v)
+    }
+
+    pub const fn between(lo: PValue<V, E, P>, hi: PValue<V, E, P>) -> Self {
+        Self::Between(lo, hi)
+    }
+
+    pub const fn inside(lo: PValue<V, E, P>, hi: PValue<V, E, P>) -> Self {
+        Self::Inside(lo, hi)
+    }
+
+    pub const fn outside(lo: PValue<V, E, P>, hi: PValue<V, E, P>) -> Self {
+        Self::Outside(lo, hi)
+    }
+
+    pub const fn within(values: Vec<PValue<V, E, P>>) -> Self {
+        Self::Within(values)
+    }
+
+    pub const fn without(values: Vec<PValue<V, E, P>>) -> Self {
+        Self::Without(values)
+    }
+
+    pub fn test(&self, value: &PValue<V, E, P>) -> bool {
+        let cmp = |other: &PValue<V, E, P>| value.compare(other);
+        match self {
+            Self::Neq(v) => cmp(v) != Some(Ordering::Equal),
+            Self::Outside(lo, hi) => {
+                cmp(lo) == Some(Ordering::Less) || cmp(hi) == Some(Ordering::Greater)
+            }
+            Self::Within(values) => values.iter().any(|v| cmp(v) == Some(Ordering::Equal)),
+            Self::Without(values) => values.iter().all(|v| cmp(v) != Some(Ordering::Equal)),
+            _ => self
+                .bounds()
+                .is_some_and(|(start, end)| value.in_bounds(start, end)),
+        }
+    }
+
+    /// The values this predicate accepts, when they form a single range.
+    pub(crate) const fn bounds(
+        &self,
+    ) -> Option<(Bound<&PValue<V, E, P>>, Bound<&PValue<V, E, P>>)> {
+        Some(match self {
+            Self::Eq(v) => (Bound::Included(v), Bound::Included(v)),
+            Self::Lt(v) => (Bound::Unbounded, Bound::Excluded(v)),
+            Self::Lte(v) => (Bound::Unbounded, Bound::Included(v)),
+            Self::Gt(v) => (Bound::Excluded(v), Bound::Unbounded),
+            Self::Gte(v) => (Bound::Included(v), Bound::Unbounded),
+            Self::Between(lo, hi) => (Bound::Included(lo), Bound::Excluded(hi)),
+            Self::Inside(lo, hi) => (Bound::Excluded(lo), Bound::Excluded(hi)),
+            _ => return None,
+        })
+    }
+}
+
+impl<V, E, P> From<PValue<V, E, P>> for Predicate<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    fn from(v: PValue<V, E, P>) -> Self {
+        Self::Eq(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    type PV = PValue<String, String, String>;
+    type Pred = Predicate<String, String, String>;
+
+    #[rstest]
+    fn test_comparisons() {
+        let three = PV::I32(3);
+        assert!(Pred::eq(PV::I64(3)).test(&three));
+        assert!(Pred::neq(PV::String("3".into())).test(&three));
+        assert!(Pred::lt(PV::Double(3.5)).test(&three));
+        assert!(Pred::lte(PV::I32(3)).test(&three));
+        assert!(!Pred::gt(PV::I32(3)).test(&three));
+        assert!(Pred::gte(PV::I128(3)).test(&three));
+        assert!(!Pred::lt(PV::String("4".into())).test(&three));
+    }
+
+    #[rstest]
+    fn test_ranges() {
+        let (lo, hi) = (PV::I32(1), PV::I32(3));
+        assert!(Pred::between(lo.clone(), hi.clone()).test(&PV::I32(1)));
+        assert!(!Pred::between(lo.clone(), hi.clone()).test(&PV::I32(3)));
+        assert!(!Pred::inside(lo.clone(), hi.clone()).test(&PV::I32(1)));
+        assert!(Pred::inside(lo.clone(), hi.clone()).test(&PV::I32(2)));
+        assert!(Pred::outside(lo.clone(), hi.clone()).test(&PV::I32(0)));
+        assert!(!Pred::outside(lo, hi).test(&PV::I32(3)));
+    }
+
+    #[rstest]
+    fn test_collections() {
+        let values = vec![PV::String("a".into()), PV::I32(1)];
+        assert!(Pred::within(values.clone()).test(&PV::I64(1)));
+        assert!(!Pred::within(values.clone()).test(&PV::String("b".into())));
+        assert!(Pred::without(values.clone()).test(&PV::String("b".into())));
+        assert!(!Pred::without(values).test(&PV::String("a".into())));
+        assert_eq!(Pred::from(PV::Bool(true)), Pred::eq(PV::Bool(true)));
+    }
+}
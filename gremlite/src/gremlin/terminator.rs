@@ -1,139 +1,208 @@
-use super::{bytecode::Bytecode, executor::WriteExecutor};
+use super::{bytecode::Bytecode, executor::Executor};
 use crate::{
     error::{Error, Result},
-    graph::{parameter::FromPValue, Writable},
+    graph::{
+        parameter::{FromPValue, PValueSet},
+        PValue, Writable,
+    },
     heed::Graph,
 };
-use heed::RwTxn;
+use heed::{RoTxn, RwTxn};
+use std::{
+    iter::{Map, Peekable},
+    marker::PhantomData,
+};
+
+/// Results of a traversal, pulled lazily from its transaction.
+pub type Values<'txn, V, E, P> = Box<dyn 'txn + Iterator<Item = Result<PValue<V, E, P>>>>;
+
+/// `Values` converted to the traversal's end type.
+pub type Iter<'txn, End, V, E, P> =
+    Map<Values<'txn, V, E, P>, fn(Result<PValue<V, E, P>>) -> Result<End>>;
+
+/// A traversal's results, pulled lazily from its transaction as it's iterated.
+pub struct Results<'txn, V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    values: Peekable<Values<'txn, V, E, P>>,
+}
 
-pub trait Terminator<'graph, End, V, E, P>
+impl<'txn, V, E, P> Results<'txn, V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    pub(crate) fn new(values: Values<'txn, V, E, P>) -> Self {
+        Self {
+            values: values.peekable(),
+        }
+    }
+
+    /// Whether there are more results. It pulls at most the next one, which `next` then
+    /// returns, so nothing runs twice.
+    pub fn has_next(&mut self) -> Result<bool> {
+        match self.values.peek() {
+            Some(Ok(_)) => Ok(true),
+            Some(Err(_)) => Err(self.values.next().and_then(Result::err).expect("peeked")),
+            None => Ok(false),
+        }
+    }
+}
+
+impl<'txn, V, E, P> Iterator for Results<'txn, V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    type Item = Result<PValue<V, E, P>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.values.next()
+    }
+}
+
+/// Runs traversals in a transaction of type `Txn`, which is either `&mut RwTxn` or `&RoTxn`.
+///
+/// Everything is built on `values`, which streams results as they're pulled, so only
+/// `to_list` and `to_set` hold the whole result set in memory.
+pub trait Terminator<'graph, 'txn, End, V, E, P, Txn>
 where
     End: FromPValue<V, E, P>,
     V: Writable,
     E: Writable,
     P: Writable + Eq,
 {
-    type List;
-    type Next;
-    type HasNext;
-    type Iter;
-
-    fn to_list<'a, 'txn>(
-        &'a self,
-        txn: &'txn mut RwTxn<'graph>,
-        traversal: &'txn Bytecode<V, E, P>,
-    ) -> Self::List
-    where
-        'txn: 'a;
-
-    fn next<'a, 'txn>(
-        &'a self,
-        txn: &'txn mut RwTxn<'graph>,
-        traversal: &'txn Bytecode<V, E, P>,
-    ) -> Self::Next
-    where
-        'txn: 'a;
-
-    fn has_next<'a, 'txn>(
-        &'a self,
-        txn: &'txn mut RwTxn<'graph>,
-        traversal: &'txn Bytecode<V, E, P>,
-    ) -> Self::HasNext
-    where
-        'txn: 'a;
-
-    fn iter<'a, 'txn>(
-        &'a self,
-        txn: &'txn mut RwTxn<'graph>,
-        traversal: &'txn Bytecode<V, E, P>,
-    ) -> Self::Iter
-    where
-        'txn: 'a;
+    fn values(&self, txn: Txn, traversal: &Bytecode<V, E, P>) -> Result<Values<'txn, V, E, P>>;
+
+    fn iter(&self, txn: Txn, traversal: &Bytecode<V, E, P>) -> Result<Iter<'txn, End, V, E, P>> {
+        let convert: fn(Result<PValue<V, E, P>>) -> Result<End> =
+            |value| value.and_then(End::from_pvalue);
+        Ok(self.values(txn, traversal)?.map(convert))
+    }
+
+    fn to_list(&self, txn: Txn, traversal: &Bytecode<V, E, P>) -> Result<Vec<End>> {
+        self.iter(txn, traversal)?.collect()
+    }
+
+    /// Like `to_list`, but with duplicate results dropped, keeping the first of each.
+    fn to_set(&self, txn: Txn, traversal: &Bytecode<V, E, P>) -> Result<Vec<End>> {
+        let mut seen = PValueSet::new();
+        let mut set = Vec::new();
+        for value in self.values(txn, traversal)? {
+            let value = value?;
+            if seen.insert(&value) {
+                set.push(End::from_pvalue(value)?);
+            }
+        }
+        Ok(set)
+    }
+
+    fn next(&self, txn: Txn, traversal: &Bytecode<V, E, P>) -> Result<End> {
+        self.iter(txn, traversal)?
+            .next()
+            .unwrap_or(Err(Error::EmptyTraversal))
+    }
+
+    /// Runs the traversal for its side effects, discarding the results.
+    fn iterate(&self, txn: Txn, traversal: &Bytecode<V, E, P>) -> Result<()> {
+        self.values(txn, traversal)?
+            .try_for_each(|value| value.map(drop))
+    }
 }
 
-pub struct TraversalTerminator<'graph, V, E, P>
+/// Marks traversals that may write, and so need a `&mut RwTxn` to run.
+#[derive(Debug, Clone, Copy)]
+pub struct ReadWrite;
+
+/// Marks traversals from an `ROTraversalSource`, which run in a `&RoTxn`.
+#[derive(Debug, Clone, Copy)]
+pub struct ReadOnly;
+
+/// Marks the anonymous traversals from `__`, which only run as part of another traversal.
+#[derive(Debug, Clone, Copy)]
+pub struct Anonymous;
+
+pub struct TraversalTerminator<'graph, V, E, P, M = ReadWrite>
 where
     V: 'static + Writable,
     E: 'static + Writable,
     P: 'static + Writable + Eq,
 {
-    graph: &'graph Graph<V, E, P>,
+    graph: Option<&'graph Graph<V, E, P>>,
+    _mode: PhantomData<M>,
 }
 
-impl<'graph, V, E, P> TraversalTerminator<'graph, V, E, P>
+impl<'graph, V, E, P, M> TraversalTerminator<'graph, V, E, P, M>
 where
     V: 'static + Writable,
     E: 'static + Writable,
     P: 'static + Writable + Eq,
 {
-    pub fn new(graph: &'graph Graph<V, E, P>) -> Self {
-        Self { graph }
+    pub const fn new(graph: &'graph Graph<V, E, P>) -> Self {
+        Self {
+            graph: Some(graph),
+            _mode: PhantomData,
+        }
+    }
+
+    fn graph(&self) -> Result<&'graph Graph<V, E, P>> {
+        self.graph.ok_or(Error::BadRequest(
+            "anonymous traversals only run as part of another traversal",
+        ))
     }
 }
 
-impl<'graph, End, V, E, P> Terminator<'graph, End, V, E, P> for TraversalTerminator<'graph, V, E, P>
+impl<V, E, P> TraversalTerminator<'static, V, E, P, Anonymous>
 where
-    End: FromPValue<V, E, P>,
     V: 'static + Writable,
     E: 'static + Writable,
     P: 'static + Writable + Eq,
 {
-    type List = Result<Vec<End>>;
-    type Next = Result<End>;
-    type HasNext = ();
-    type Iter = ();
-
-    fn to_list<'a, 'txn>(
-        &'a self,
-        txn: &'txn mut RwTxn<'graph>,
-        bytecode: &'txn Bytecode<V, E, P>,
-    ) -> Result<Vec<End>>
-    where
-        'txn: 'a,
-    {
-        let mut executor = WriteExecutor::<'graph, End, V, E, P>::new(self.graph);
-        Ok(executor
-            .execute(txn, bytecode)?
-            .map(End::from_pvalue)
-            .map(Result::unwrap)
-            .collect())
-    }
-
-    fn next<'a, 'txn>(
-        &'a self,
-        txn: &'txn mut RwTxn<'graph>,
-        traversal: &'txn Bytecode<V, E, P>,
-    ) -> Result<End>
-    where
-        'txn: 'a,
-    {
-        let mut executor: WriteExecutor<End, V, E, P> = WriteExecutor::new(self.graph);
-        let iter = executor.execute(txn, traversal)?.next();
-        iter.map(End::from_pvalue)
-            .unwrap_or_else(|| Err(Error::EmptyTraversal))
+    pub const fn anonymous() -> Self {
+        Self {
+            graph: None,
+            _mode: PhantomData,
+        }
     }
+}
 
-    fn has_next<'a, 'txn>(
-        &'a self,
-        _txn: &'txn mut RwTxn<'graph>,
-        _traversal: &'txn Bytecode<V, E, P>,
-    ) -> Self::HasNext
-    where
-        'txn: 'a,
-    {
-        todo!()
-        // let mut executor: WriteExecutor<'graph, End, V, E, P> = WriteExecutor::new(self.graph);
-        // let iter = executor.execute(txn, traversal)?.next();
+impl<'graph, 'txn, 'p, End, V, E, P> Terminator<'graph, 'txn, End, V, E, P, &'txn mut RwTxn<'p>>
+    for TraversalTerminator<'graph, V, E, P, ReadWrite>
+where
+    End: FromPValue<V, E, P>,
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+    'graph: 'txn,
+{
+    fn values(
+        &self,
+        txn: &'txn mut RwTxn<'p>,
+        traversal: &Bytecode<V, E, P>,
+    ) -> Result<Values<'txn, V, E, P>> {
+        Executor::<End, V, E, P>::new(self.graph()?).execute(txn, traversal)
     }
+}
 
-    fn iter<'a, 'txn>(
-        &'a self,
-        _txn: &'txn mut RwTxn<'graph>,
-        _traversal: &'txn Bytecode<V, E, P>,
-    ) -> Self::Iter
-    where
-        'txn: 'a,
-    {
-        todo!()
+impl<'graph, 'txn, End, V, E, P, M> Terminator<'graph, 'txn, End, V, E, P, &'txn RoTxn>
+    for TraversalTerminator<'graph, V, E, P, M>
+where
+    End: FromPValue<V, E, P>,
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+    'graph: 'txn,
+{
+    fn values(
+        &self,
+        txn: &'txn RoTxn,
+        traversal: &Bytecode<V, E, P>,
+    ) -> Result<Values<'txn, V, E, P>> {
+        Executor::<End, V, E, P>::new(self.graph()?).execute_read(txn, traversal)
     }
 }
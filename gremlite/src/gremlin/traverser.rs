@@ -0,0 +1,103 @@
+use crate::{
+    error::{Error, Result},
+    graph::{Edge, Id, PValue, Path, Vertex, Writable},
+};
+use std::collections::HashMap;
+
+pub type Traversers<'txn, V, E, P> = Box<dyn 'txn + Iterator<Item = Result<Traverser<V, E, P>>>>;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Traverser<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    pub(crate) value: PValue<V, E, P>,
+    /// Id of the element this traverser stepped off of, if any.
+    pub(crate) origin: Option<Id>,
+    /// Every value this traverser has been at, ending with `value`, if `tracked`.
+    pub(crate) path: Path<V, E, P>,
+    /// Whether `path` is kept. It's left empty when no step reads it, so that moving on
+    /// doesn't mean copying it.
+    pub(crate) tracked: bool,
+    /// How many times this traverser has been round each `repeat` it's in, innermost last.
+    pub(crate) loops: Vec<usize>,
+}
+
+impl<V, E, P> Traverser<V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    pub(crate) fn new(value: PValue<V, E, P>, tracked: bool) -> Self {
+        Self::start(tracked).split(value)
+    }
+
+    /// The traverser every pipeline starts from, before any start step has run. Its
+    /// placeholder value isn't part of its path.
+    pub(crate) const fn start(tracked: bool) -> Self {
+        Self {
+            value: PValue::None,
+            origin: None,
+            path: Path::new(),
+            tracked,
+            loops: vec![],
+        }
+    }
+
+    /// Moves this traverser on to `value`, remembering where it came from.
+    pub(crate) fn split(&self, value: PValue<V, E, P>) -> Self {
+        let mut path = Path::new();
+        if self.tracked {
+            path = self.path.clone();
+            path.push(value.clone());
+        }
+        Self {
+            value,
+            origin: self.id(),
+            path,
+            tracked: self.tracked,
+            loops: self.loops.clone(),
+        }
+    }
+
+    pub fn into_value(self) -> PValue<V, E, P> {
+        self.value
+    }
+
+    pub(crate) const fn id(&self) -> Option<Id> {
+        match &self.value {
+            PValue::Vertex(v) => v.id,
+            PValue::Edge(e) => e.id,
+            _ => None,
+        }
+    }
+
+    pub(crate) fn vertex(&self) -> Result<&Vertex<V, E, P>> {
+        match &self.value {
+            PValue::Vertex(v) => Ok(v),
+            other => Err(Error::InvalidPValue(format!("{:?} is not a vertex", other))),
+        }
+    }
+
+    pub(crate) fn edge(&self) -> Result<&Edge<V, E, P>> {
+        match &self.value {
+            PValue::Edge(e) => Ok(e),
+            other => Err(Error::InvalidPValue(format!("{:?} is not an edge", other))),
+        }
+    }
+
+    /// Parameters of the vertex or edge this traverser is on.
+    pub(crate) fn params(&self) -> Result<&HashMap<P, PValue<V, E, P>>> {
+        match &self.value {
+            PValue::Vertex(v) => Ok(&v.parameters),
+            PValue::Edge(e) => Ok(&e.parameters),
+            other => Err(Error::InvalidPValue(format!(
+                "{:?} is not an element",
+                other
+            ))),
+        }
+    }
+}
@@ -0,0 +1,834 @@
+//! Sortable encodings for index keys.
+//!
+//! Keys written with `to_key` compare byte-wise in the same order as the values they
+//! encode: integers are sign-flipped big-endian, floats use their IEEE total order,
+//! strings are escaped and terminated, and sequences mark every element, so that LMDB
+//! range and prefix scans follow the values. The format isn't self-describing, so `from_key`
+//! needs the type that wrote the key.
+
+use crate::{
+    error::{Error, Result},
+    graph::{PValue, Writable},
+};
+use serde::{
+    de::{self, DeserializeSeed, IntoDeserializer, Visitor},
+    ser::{self, Serialize},
+    Deserialize,
+};
+use std::{
+    convert::{TryFrom, TryInto},
+    fmt::Display,
+};
+
+const NUMBER: u8 = 0x10;
+const DATE: u8 = 0x20;
+const TOKEN: u8 = 0x30;
+const STRING: u8 = 0x31;
+const BOOL: u8 = 0x40;
+
+/// Marks another element of a sequence or map; the end is marked with `0`.
+const MORE: u8 = 1;
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::Key(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::Key(msg.to_string())
+    }
+}
+
+pub fn to_key<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    let mut serializer = KeySerializer { out: vec![] };
+    value.serialize(&mut serializer)?;
+    Ok(serializer.out)
+}
+
+pub fn from_key<'de, T: Deserialize<'de>>(bytes: &'de [u8]) -> Result<T> {
+    let mut deserializer = KeyDeserializer { input: bytes };
+    let value = T::deserialize(&mut deserializer)?;
+    if deserializer.input.is_empty() {
+        Ok(value)
+    } else {
+        Err(Error::Key("trailing bytes".into()))
+    }
+}
+
+struct KeySerializer {
+    out: Vec<u8>,
+}
+
+impl KeySerializer {
+    /// Variants below `0xFF` take a single byte, the rest are escaped with a leading `0xFF`.
+    fn variant(&mut self, index: u32) {
+        match u8::try_from(index) {
+            Ok(index) if index < 0xFF => self.out.push(index),
+            _ => {
+                self.out.push(0xFF);
+                self.out.extend(&index.to_be_bytes());
+            }
+        }
+    }
+}
+
+macro_rules! serialize_signed {
+    ($($method:ident: $int:ty as $uint:ty,)*) => {$(
+        fn $method(self, v: $int) -> Result<()> {
+            let flipped = (v as $uint) ^ (1 << (<$uint>::BITS - 1));
+            self.out.extend(&flipped.to_be_bytes());
+            Ok(())
+        }
+    )*};
+}
+
+macro_rules! serialize_unsigned {
+    ($($method:ident: $uint:ty,)*) => {$(
+        fn $method(self, v: $uint) -> Result<()> {
+            self.out.extend(&v.to_be_bytes());
+            Ok(())
+        }
+    )*};
+}
+
+impl ser::Serializer for &mut KeySerializer {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    serialize_signed! {
+        serialize_i8: i8 as u8,
+        serialize_i16: i16 as u16,
+        serialize_i32: i32 as u32,
+        serialize_i64: i64 as u64,
+        serialize_i128: i128 as u128,
+    }
+
+    serialize_unsigned! {
+        serialize_u8: u8,
+        serialize_u16: u16,
+        serialize_u32: u32,
+        serialize_u64: u64,
+        serialize_u128: u128,
+    }
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.out.push(u8::from(v));
+        Ok(())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        let bits = v.to_bits();
+        let bits = if v.is_sign_negative() {
+            !bits
+        } else {
+            bits ^ (1 << 31)
+        };
+        self.out.extend(&bits.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        self.out.extend(&encode_f64(v));
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.serialize_u32(v.into())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        encode_bytes(&mut self.out, v.as_bytes());
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        encode_bytes(&mut self.out, v);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        self.out.push(0);
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<()> {
+        self.out.push(1);
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+    ) -> Result<()> {
+        self.variant(index);
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.variant(index);
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self> {
+        self.variant(index);
+        Ok(self)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self> {
+        self.variant(index);
+        Ok(self)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+impl ser::SerializeSeq for &mut KeySerializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.out.push(MORE);
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        self.out.push(0);
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for &mut KeySerializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        self.out.push(MORE);
+        key.serialize(&mut **self)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        self.out.push(0);
+        Ok(())
+    }
+}
+
+macro_rules! serialize_fields {
+    ($($trait:ident :: $method:ident ($($name:ident: $ty:ty),*),)*) => {$(
+        impl ser::$trait for &mut KeySerializer {
+            type Ok = ();
+            type Error = Error;
+
+            fn $method<T: Serialize + ?Sized>(&mut self, $($name: $ty,)* value: &T) -> Result<()> {
+                $(let _ = $name;)*
+                value.serialize(&mut **self)
+            }
+
+            fn end(self) -> Result<()> {
+                Ok(())
+            }
+        }
+    )*};
+}
+
+serialize_fields! {
+    SerializeTuple::serialize_element(),
+    SerializeTupleStruct::serialize_field(),
+    SerializeTupleVariant::serialize_field(),
+    SerializeStruct::serialize_field(key: &'static str),
+    SerializeStructVariant::serialize_field(key: &'static str),
+}
+
+struct KeyDeserializer<'de> {
+    input: &'de [u8],
+}
+
+impl<'de> KeyDeserializer<'de> {
+    fn take(&mut self, len: usize) -> Result<&'de [u8]> {
+        if self.input.len() < len {
+            return Err(Error::Key("unexpected end of key".into()));
+        }
+        let (taken, rest) = self.input.split_at(len);
+        self.input = rest;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().expect("took N bytes"))
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>> {
+        let mut out = vec![];
+        loop {
+            match self.byte()? {
+                0 => match self.byte()? {
+                    0 => return Ok(out),
+                    0xFF => out.push(0),
+                    _ => return Err(Error::Key("bad escape".into())),
+                },
+                byte => out.push(byte),
+            }
+        }
+    }
+
+    fn variant(&mut self) -> Result<u32> {
+        match self.byte()? {
+            0xFF => Ok(u32::from_be_bytes(self.array()?)),
+            index => Ok(index.into()),
+        }
+    }
+
+    fn more(&mut self) -> Result<bool> {
+        match self.byte()? {
+            MORE => Ok(true),
+            0 => Ok(false),
+            _ => Err(Error::Key("bad sequence marker".into())),
+        }
+    }
+}
+
+macro_rules! deserialize_signed {
+    ($($method:ident: $visit:ident $int:ty as $uint:ty,)*) => {$(
+        fn $method<Vis: Visitor<'de>>(self, visitor: Vis) -> Result<Vis::Value> {
+            let flipped = <$uint>::from_be_bytes(self.array()?);
+            visitor.$visit((flipped ^ (1 << (<$uint>::BITS - 1))) as $int)
+        }
+    )*};
+}
+
+macro_rules! deserialize_unsigned {
+    ($($method:ident: $visit:ident $uint:ty,)*) => {$(
+        fn $method<Vis: Visitor<'de>>(self, visitor: Vis) -> Result<Vis::Value> {
+            visitor.$visit(<$uint>::from_be_bytes(self.array()?))
+        }
+    )*};
+}
+
+impl<'de> de::Deserializer<'de> for &mut KeyDeserializer<'de> {
+    type Error = Error;
+
+    deserialize_signed! {
+        deserialize_i8: visit_i8 i8 as u8,
+        deserialize_i16: visit_i16 i16 as u16,
+        deserialize_i32: visit_i32 i32 as u32,
+        deserialize_i64: visit_i64 i64 as u64,
+        deserialize_i128: visit_i128 i128 as u128,
+    }
+
+    deserialize_unsigned! {
+        deserialize_u8: visit_u8 u8,
+        deserialize_u16: visit_u16 u16,
+        deserialize_u32: visit_u32 u32,
+        deserialize_u64: visit_u64 u64,
+        deserialize_u128: visit_u128 u128,
+    }
+
+    fn deserialize_any<Vis: Visitor<'de>>(self, _visitor: Vis) -> Result<Vis::Value> {
+        Err(Error::Key("keys can only be read as a known type".into()))
+    }
+
+    fn deserialize_bool<Vis: Visitor<'de>>(self, visitor: Vis) -> Result<Vis::Value> {
+        visitor.visit_bool(self.byte()? != 0)
+    }
+
+    fn deserialize_f32<Vis: Visitor<'de>>(self, visitor: Vis) -> Result<Vis::Value> {
+        let bits = u32::from_be_bytes(self.array()?);
+        let bits = if bits & (1 << 31) == 0 {
+            !bits
+        } else {
+            bits ^ (1 << 31)
+        };
+        visitor.visit_f32(f32::from_bits(bits))
+    }
+
+    fn deserialize_f64<Vis: Visitor<'de>>(self, visitor: Vis) -> Result<Vis::Value> {
+        let bits = u64::from_be_bytes(self.array()?);
+        let bits = if bits & (1 << 63) == 0 {
+            !bits
+        } else {
+            bits ^ (1 << 63)
+        };
+        visitor.visit_f64(f64::from_bits(bits))
+    }
+
+    fn deserialize_char<Vis: Visitor<'de>>(self, visitor: Vis) -> Result<Vis::Value> {
+        let c = u32::from_be_bytes(self.array()?);
+        visitor.visit_char(char::from_u32(c).ok_or_else(|| Error::Key("bad char".into()))?)
+    }
+
+    fn deserialize_str<Vis: Visitor<'de>>(self, visitor: Vis) -> Result<Vis::Value> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<Vis: Visitor<'de>>(self, visitor: Vis) -> Result<Vis::Value> {
+        let s = String::from_utf8(self.bytes()?).map_err(|e| Error::Key(e.to_string()))?;
+        visitor.visit_string(s)
+    }
+
+    fn deserialize_bytes<Vis: Visitor<'de>>(self, visitor: Vis) -> Result<Vis::Value> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<Vis: Visitor<'de>>(self, visitor: Vis) -> Result<Vis::Value> {
+        visitor.visit_byte_buf(self.bytes()?)
+    }
+
+    fn deserialize_option<Vis: Visitor<'de>>(self, visitor: Vis) -> Result<Vis::Value> {
+        match self.byte()? {
+            0 => visitor.visit_none(),
+            1 => visitor.visit_some(self),
+            _ => Err(Error::Key("bad option marker".into())),
+        }
+    }
+
+    fn deserialize_unit<Vis: Visitor<'de>>(self, visitor: Vis) -> Result<Vis::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<Vis: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: Vis,
+    ) -> Result<Vis::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<Vis: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: Vis,
+    ) -> Result<Vis::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<Vis: Visitor<'de>>(self, visitor: Vis) -> Result<Vis::Value> {
+        visitor.visit_seq(Marked(self))
+    }
+
+    fn deserialize_tuple<Vis: Visitor<'de>>(self, len: usize, visitor: Vis) -> Result<Vis::Value> {
+        visitor.visit_seq(Fields(self, len))
+    }
+
+    fn deserialize_tuple_struct<Vis: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: Vis,
+    ) -> Result<Vis::Value> {
+        visitor.visit_seq(Fields(self, len))
+    }
+
+    fn deserialize_map<Vis: Visitor<'de>>(self, visitor: Vis) -> Result<Vis::Value> {
+        visitor.visit_map(Marked(self))
+    }
+
+    fn deserialize_struct<Vis: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: Vis,
+    ) -> Result<Vis::Value> {
+        visitor.visit_seq(Fields(self, fields.len()))
+    }
+
+    fn deserialize_enum<Vis: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: Vis,
+    ) -> Result<Vis::Value> {
+        visitor.visit_enum(self)
+    }
+
+    fn deserialize_identifier<Vis: Visitor<'de>>(self, visitor: Vis) -> Result<Vis::Value> {
+        visitor.visit_u32(self.variant()?)
+    }
+
+    fn deserialize_ignored_any<Vis: Visitor<'de>>(self, visitor: Vis) -> Result<Vis::Value> {
+        self.deserialize_any(visitor)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// Elements of a sequence or map, each preceded by `MORE`.
+struct Marked<'a, 'de>(&'a mut KeyDeserializer<'de>);
+
+impl<'de, 'a> de::SeqAccess<'de> for Marked<'a, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        if self.0.more()? {
+            seed.deserialize(&mut *self.0).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<'de, 'a> de::MapAccess<'de> for Marked<'a, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        if self.0.more()? {
+            seed.deserialize(&mut *self.0).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn next_value_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<T::Value> {
+        seed.deserialize(&mut *self.0)
+    }
+}
+
+/// A fixed number of fields, as in tuples and structs.
+struct Fields<'a, 'de>(&'a mut KeyDeserializer<'de>, usize);
+
+impl<'de, 'a> de::SeqAccess<'de> for Fields<'a, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        if self.1 == 0 {
+            return Ok(None);
+        }
+        self.1 -= 1;
+        seed.deserialize(&mut *self.0).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.1)
+    }
+}
+
+impl<'de> de::EnumAccess<'de> for &mut KeyDeserializer<'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<(T::Value, Self)> {
+        let index: de::value::U32Deserializer<Error> = self.variant()?.into_deserializer();
+        Ok((seed.deserialize(index)?, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for &mut KeyDeserializer<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<Vis: Visitor<'de>>(self, len: usize, visitor: Vis) -> Result<Vis::Value> {
+        visitor.visit_seq(Fields(self, len))
+    }
+
+    fn struct_variant<Vis: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: Vis,
+    ) -> Result<Vis::Value> {
+        visitor.visit_seq(Fields(self, fields.len()))
+    }
+}
+
+/// Encodes `value` so that comparing the bytes orders values the way `PValue::compare`
+/// does, or `None` if the value has no such encoding.
+///
+/// Numbers of every width are encoded by value, as the `f64` they round to followed by the
+/// distance of an integer from it, so `I32(3)` and `Double(3.0)` share a key while integers
+/// too large for an `f64` keep their own.
+pub fn encode_value<V, E, P>(value: &PValue<V, E, P>) -> Option<Vec<u8>>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    let mut out = vec![];
+    match value {
+        PValue::I32(_)
+        | PValue::I64(_)
+        | PValue::I128(_)
+        | PValue::Float(_)
+        | PValue::Double(_) => {
+            let (rounded, rest) = number(value)?;
+            out.push(NUMBER);
+            out.extend(&encode_f64(rounded));
+            out.extend(&((rest as u128) ^ (1 << 127)).to_be_bytes());
+        }
+        PValue::Date(date) => {
+            out.push(DATE);
+            out.extend(&encode_i64(date.timestamp()));
+            out.extend(&date.timestamp_subsec_nanos().to_be_bytes());
+        }
+        PValue::Token(s) => {
+            out.push(TOKEN);
+            encode_bytes(&mut out, s.as_bytes());
+        }
+        PValue::String(s) => {
+            out.push(STRING);
+            encode_bytes(&mut out, s.as_bytes());
+        }
+        PValue::Bool(b) => out.extend(&[BOOL, u8::from(*b)]),
+        _ => return None,
+    }
+    Some(out)
+}
+
+fn number<V, E, P>(value: &PValue<V, E, P>) -> Option<(f64, i128)>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    let (n, rest) = value.as_number()?;
+    if n.is_nan() {
+        None
+    } else if n == 0.0 {
+        // -0.0 compares equal to 0.0, so they need the same key
+        Some((0.0, rest))
+    } else {
+        Some((n, rest))
+    }
+}
+
+const fn encode_f64(n: f64) -> [u8; 8] {
+    let bits = n.to_bits();
+    let bits = if n.is_sign_negative() {
+        !bits
+    } else {
+        bits ^ (1 << 63)
+    };
+    bits.to_be_bytes()
+}
+
+const fn encode_i64(n: i64) -> [u8; 8] {
+    ((n as u64) ^ (1 << 63)).to_be_bytes()
+}
+
+/// Escapes nul bytes and terminates the bytes, so they always sort before any longer
+/// run of bytes they are a prefix of.
+fn encode_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    for &byte in bytes {
+        out.push(byte);
+        if byte == 0 {
+            out.push(0xFF);
+        }
+    }
+    out.extend(&[0, 0]);
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use rstest::rstest;
+    use serde::Serialize;
+    use std::fmt::Debug;
+
+    use super::*;
+    use crate::graph::{Id, Type};
+
+    type PV = PValue<String, String, String>;
+
+    fn assert_keys_sorted<T: Serialize + for<'de> Deserialize<'de> + PartialEq + Debug>(
+        values: &[T],
+    ) {
+        let keys: Vec<_> = values.iter().map(|v| to_key(v).unwrap()).collect();
+        for pair in keys.windows(2) {
+            assert!(pair[0] < pair[1], "{:?} >= {:?}", pair[0], pair[1]);
+        }
+        for (key, value) in keys.iter().zip(values) {
+            assert_eq!(&from_key::<T>(key).unwrap(), value);
+        }
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    enum Label {
+        Unit,
+        Tuple(i8, u16),
+        Struct { name: String, weight: f32 },
+    }
+
+    #[rstest]
+    fn test_key_order() {
+        assert_keys_sorted(&[i64::MIN, -300, -1, 0, 1, 300, i64::MAX]);
+        assert_keys_sorted(&[-1e10, -0.5, 0.0, 1e-10, 2.0, f64::INFINITY]);
+        assert_keys_sorted(&["", "a", "a\0", "ab", "b"].map(String::from));
+        assert_keys_sorted(&[
+            ("a".to_string(), 2u8),
+            ("a".to_string(), 10),
+            ("ab".to_string(), 0),
+        ]);
+        assert_keys_sorted(&[vec![], vec![1i32], vec![1, -1], vec![1, 0], vec![2]]);
+        assert_keys_sorted(&[None, Some(-1i128), Some(1)]);
+        assert_keys_sorted(&[
+            Label::Unit,
+            Label::Tuple(-1, 5),
+            Label::Tuple(0, 0),
+            Label::Struct {
+                name: "a".into(),
+                weight: 2.0,
+            },
+            Label::Struct {
+                name: "b".into(),
+                weight: -2.0,
+            },
+        ]);
+    }
+
+    #[rstest]
+    fn test_key_round_trip() -> Result<()> {
+        let id = Id::max(Type::Edge);
+        assert_eq!(from_key::<Id>(&to_key(&id)?)?, id);
+        assert_eq!(from_key::<()>(&to_key(&())?)?, ());
+        assert_eq!(from_key::<char>(&to_key(&'λ')?)?, 'λ');
+        assert!(matches!(from_key::<String>(b"ab"), Err(Error::Key(_))));
+        assert!(matches!(from_key::<u8>(&[1, 2]), Err(Error::Key(_))));
+
+        Ok(())
+    }
+
+    fn assert_sorted(values: &[PV]) {
+        let encoded: Vec<_> = values.iter().map(|v| encode_value(v).unwrap()).collect();
+        for pair in encoded.windows(2) {
+            assert!(pair[0] < pair[1], "{:?} >= {:?}", pair[0], pair[1]);
+        }
+    }
+
+    #[rstest]
+    fn test_number_order() {
+        assert_sorted(&[
+            PV::Double(f64::NEG_INFINITY),
+            PV::I128(-(1 << 70)),
+            PV::I64(-5),
+            PV::Float(-0.5),
+            PV::I32(0),
+            PV::Double(1e-300),
+            PV::I32(2),
+            PV::Double(2.5),
+            PV::I64(1 << 40),
+            PV::Double(9007199254740992.0),
+            PV::I64((1 << 53) + 1),
+            PV::I128((1 << 53) + 2),
+            PV::I64(i64::MAX),
+            PV::I128(i128::MAX - 1),
+            PV::I128(i128::MAX),
+            PV::Double(f64::INFINITY),
+        ]);
+        assert_eq!(
+            encode_value(&PV::I64(i64::MAX)),
+            encode_value(&PV::I128(i64::MAX.into()))
+        );
+        assert_eq!(encode_value(&PV::I32(3)), encode_value(&PV::Double(3.0)));
+        assert_eq!(encode_value(&PV::Double(-0.0)), encode_value(&PV::I64(0)));
+        assert_eq!(encode_value(&PV::Double(f64::NAN)), None);
+    }
+
+    #[rstest]
+    fn test_string_order() {
+        assert_sorted(&[
+            PV::String("".into()),
+            PV::String("a".into()),
+            PV::String("a\0".into()),
+            PV::String("a\0b".into()),
+            PV::String("ab".into()),
+            PV::String("b".into()),
+        ]);
+        assert_ne!(
+            encode_value(&PV::String("a".into())),
+            encode_value(&PV::Token("a".into()))
+        );
+    }
+
+    #[rstest]
+    fn test_other_order() {
+        assert_sorted(&[
+            PV::Date(Utc.timestamp_opt(-10, 5).unwrap()),
+            PV::Date(Utc.timestamp_opt(0, 0).unwrap()),
+            PV::Date(Utc.timestamp_opt(0, 1).unwrap()),
+            PV::Date(Utc.timestamp_opt(1_600_000_000, 0).unwrap()),
+        ]);
+        assert_sorted(&[PV::Bool(false), PV::Bool(true)]);
+        assert_eq!(encode_value(&PV::None), None);
+        assert_eq!(encode_value(&PV::List(vec![PV::I32(1)])), None);
+    }
+}
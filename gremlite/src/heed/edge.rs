@@ -1,10 +1,13 @@
-use heed::{RoIter, RoRange, RoTxn, RwTxn};
-use std::{fmt::Debug, marker::PhantomData, ops::Deref};
+use heed::{types::ByteSlice, RoRange, RoTxn, RwTxn};
+use std::{collections::HashMap, fmt::Debug, iter::once, marker::PhantomData, ops::Bound};
 
-use super::{IdParam, LabelId, ParamId};
+use super::{corrupt, decode, LabelId, EDGES, EDGES_IDX, HEXSTORE};
 use crate::{
-    error::Result,
-    graph::{Edge, Id, PValue, Type, Writable},
+    error::{Error, Result},
+    graph::{
+        edge::{Direction, HexOrder, ORDERS},
+        Edge, Id, PValue, Type, Writable,
+    },
     heed::Graph,
 };
 
@@ -15,54 +18,182 @@ where
     P: Writable + Eq,
 {
     pub fn put_edge(&self, txn: &mut RwTxn, edge: &Edge<V, E, P>) -> Result<Edge<V, E, P>> {
-        let e = if edge.id.is_some() {
-            let e: Option<_> = self.edge_db.get(txn, edge.id.as_ref().unwrap())?;
-            if let Some(e) = e {
-                self.edge_idx_db
-                    .delete(txn, &LabelId(e.label, edge.id.unwrap()))?;
-            }
-            edge.clone()
+        let (e, old) = if let Some(id) = edge.id {
+            let old = match self.get_edge_by_id(txn, &id)? {
+                Some(e) => {
+                    self.delete_hexstore(txn, &e)?;
+                    self.edge_idx_db.delete(txn, &LabelId(e.label, id))?;
+                    e.parameters
+                }
+                None => HashMap::new(),
+            };
+            (edge.clone(), old)
         } else {
             let id = Id::new(Type::Edge, &mut self.generator.lock())?;
-            Edge {
+            let e = Edge {
                 id: Some(id),
                 ..edge.clone()
-            }
+            };
+            (e, HashMap::new())
         };
-        self.edge_db.put(txn, e.id.as_ref().unwrap(), &e)?;
-        let rev = &LabelId(e.label.clone(), e.id.unwrap());
-        self.edge_idx_db.put(txn, rev, e.id.as_ref().unwrap())?;
-        e.parameters.iter().for_each(|(k, v)| {
-            self.parameters_db
-                .put(txn, &IdParam(e.id.unwrap(), k.clone()), v)
-                .unwrap();
-            self.parameters_idx_db
-                .put(txn, &ParamId(k.clone(), e.id.unwrap()), &e.id.unwrap())
-                .unwrap();
-        });
-        // TODO: Add Hexstore stuff for faster searching
+        let id = e.id.ok_or(Error::EdgeInvalid)?;
+        self.edge_db.put(txn, &id, &e)?;
+        self.edge_idx_db
+            .put(txn, &LabelId(e.label.clone(), id), &id)?;
+        self.put_parameters(txn, &id, &old, &e.parameters)?;
+        self.put_hexstore(txn, &e)?;
         Ok(e)
     }
 
+    pub fn remove_edge(&self, txn: &mut RwTxn, id: &Id) -> Result<Option<Edge<V, E, P>>> {
+        let edge = match self.get_edge_by_id(txn, id)? {
+            Some(edge) => edge,
+            None => return Ok(None),
+        };
+        self.delete_hexstore(txn, &edge)?;
+        self.edge_db.delete(txn, id)?;
+        self.edge_idx_db
+            .delete(txn, &LabelId(edge.label.clone(), *id))?;
+        self.delete_parameters(txn, id, &edge.parameters)?;
+        Ok(Some(edge))
+    }
+
+    pub(crate) fn put_hexstore(&self, txn: &mut RwTxn, edge: &Edge<V, E, P>) -> Result<()> {
+        let id = edge.id.ok_or(Error::EdgeInvalid)?;
+        for order in ORDERS.iter() {
+            let key = order.to_db(id, edge.to, edge.from, edge.label.clone())?;
+            self.hexstore_db.put(txn, &key, &id)?;
+        }
+        Ok(())
+    }
+
+    fn delete_hexstore(&self, txn: &mut RwTxn, edge: &Edge<V, E, P>) -> Result<()> {
+        let id = edge.id.ok_or(Error::EdgeInvalid)?;
+        for order in ORDERS.iter() {
+            let key = order.to_db(id, edge.to, edge.from, edge.label.clone())?;
+            self.hexstore_db.delete(txn, &key)?;
+        }
+        Ok(())
+    }
+
+    /// Scans the hexstore for every edge going out of or into `vertex`, as `direction`
+    /// says, and to or from `other` if it's given, yielding `(id, to, from)` for those
+    /// labelled `label` if one is given. Each lookup is a prefix scan over whichever order
+    /// leads with what's known.
+    pub(crate) fn adjacent<'txn>(
+        &self,
+        txn: &'txn RoTxn,
+        direction: Direction,
+        vertex: Id,
+        other: Option<Id>,
+        label: Option<&E>,
+    ) -> Result<HexRange<'txn, E>> {
+        let order = match (direction, other, label) {
+            (Direction::In, None, Some(_)) => HexOrder::TEF,
+            (Direction::In, _, _) => HexOrder::TFE,
+            (_, None, Some(_)) => HexOrder::FET,
+            _ => HexOrder::FTE,
+        };
+        let ids: Vec<Id> = once(vertex).chain(other).collect();
+        let prefix = order.prefix_to_db(label, &ids)?;
+        let iter = self.hexstore_db.prefix_iter(txn, &prefix)?;
+        Ok(HexRange {
+            iter,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Yields `(edge, other)` for every edge touching `vertex` in `direction`, where
+    /// `other` is the vertex at the far end. An empty `labels` matches every edge.
+    pub(crate) fn adjacent_edges<'txn>(
+        &self,
+        txn: &'txn RoTxn,
+        vertex: Id,
+        direction: Direction,
+        labels: &[E],
+    ) -> Result<impl 'txn + Iterator<Item = Result<(Id, Id)>>> {
+        let labels: Vec<Option<&E>> = if labels.is_empty() {
+            vec![None]
+        } else {
+            labels.iter().map(Some).collect()
+        };
+        let mut ranges = vec![];
+        for label in labels {
+            if direction != Direction::In {
+                let out = self.adjacent(txn, Direction::Out, vertex, None, label)?;
+                ranges.push((Direction::Out, out));
+            }
+            if direction != Direction::Out {
+                let into = self.adjacent(txn, Direction::In, vertex, None, label)?;
+                ranges.push((Direction::In, into));
+            }
+        }
+        Ok(ranges.into_iter().flat_map(|(direction, range)| {
+            range.map(move |next| {
+                next.map(|(id, to, from)| match direction {
+                    Direction::In => (id, from),
+                    _ => (id, to),
+                })
+            })
+        }))
+    }
+
+    pub fn get_edges_from<'txn>(
+        &'txn self,
+        txn: &'txn RoTxn,
+        from: &Id,
+        label: Option<&E>,
+    ) -> Result<AdjacentEdges<'txn, V, E, P>> {
+        let iter = self.adjacent(txn, Direction::Out, *from, None, label)?;
+        Ok(AdjacentEdges::new(self, txn, iter))
+    }
+
+    pub fn get_edges_to<'txn>(
+        &'txn self,
+        txn: &'txn RoTxn,
+        to: &Id,
+        label: Option<&E>,
+    ) -> Result<AdjacentEdges<'txn, V, E, P>> {
+        let iter = self.adjacent(txn, Direction::In, *to, None, label)?;
+        Ok(AdjacentEdges::new(self, txn, iter))
+    }
+
+    pub fn get_edges_between<'txn>(
+        &'txn self,
+        txn: &'txn RoTxn,
+        from: &Id,
+        to: &Id,
+        label: Option<&E>,
+    ) -> Result<AdjacentEdges<'txn, V, E, P>> {
+        let iter = self.adjacent(txn, Direction::Out, *from, Some(*to), label)?;
+        Ok(AdjacentEdges::new(self, txn, iter))
+    }
+
     pub fn get_edge_by_id(&self, txn: &RoTxn, id: &Id) -> Result<Option<Edge<V, E, P>>> {
-        let edge = self.edge_db.get(txn, id)?;
-        Ok(edge)
+        let bytes = self
+            .edge_db
+            .as_polymorph()
+            .get::<_, Id, ByteSlice>(txn, id)?;
+        bytes.map(|bytes| decode(EDGES, *id, bytes)).transpose()
+    }
+
+    /// The edge an index entry in `db` points at, which has to exist.
+    fn indexed_edge(&self, txn: &RoTxn, db: &'static str, id: Result<Id>) -> Result<Edge<V, E, P>> {
+        let id = id?;
+        self.get_edge_by_id(txn, &id)?
+            .ok_or(Error::Corrupt(db, Some(id)))
     }
 
-    pub fn get_edges_by_ids<'txn, Txn>(
+    /// The edges with `ids`, skipping any that don't exist.
+    pub fn get_edges_by_ids<'txn>(
         &'txn self,
-        txn: &'txn Txn,
+        txn: &'txn RoTxn,
         ids: Vec<Id>,
-    ) -> Result<impl 'txn + Iterator<Item = PValue<V, E, P>>>
-    where
-        Txn: Deref<Target = RoTxn> + ?Sized,
-    {
+    ) -> Result<impl 'txn + Iterator<Item = Result<PValue<V, E, P>>>> {
         Ok(ids
             .into_iter()
-            .map(move |id| self.edge_db.get(txn, &id).ok())
-            .flatten()
-            .flatten()
-            .map(PValue::Edge))
+            .filter_map(move |id| self.get_edge_by_id(txn, &id).transpose())
+            .map(|edge| edge.map(PValue::Edge)))
     }
 
     pub fn get_edges_by_label<'txn>(
@@ -79,28 +210,51 @@ where {
         Ok(EdgeRange::new(self, txn, iter))
     }
 
-    pub fn get_edge_by_label<'txn>(
-        &self,
-        txn: &'txn RoTxn,
-        value: &E,
-    ) -> Result<Option<Edge<V, E, P>>>
+    pub fn get_edge_by_label(&self, txn: &RoTxn, value: &E) -> Result<Option<Edge<V, E, P>>>
     where
         E: Clone + Debug,
     {
-        Ok(self.get_edges_by_label(txn, value)?.next())
+        self.get_edges_by_label(txn, value)?.next().transpose()
     }
 
+    /// The number of edges, failing with `Error::CountMismatch` if the label index
+    /// doesn't agree.
     pub fn edge_count(&self, txn: &RoTxn) -> Result<usize> {
-        assert_eq!(self.edge_db.len(txn)?, self.edge_idx_db.len(txn)?);
-        Ok(self.edge_db.len(txn)?)
+        let (count, indexed) = (self.edge_db.len(txn)?, self.edge_idx_db.len(txn)?);
+        if count != indexed {
+            return Err(Error::CountMismatch(EDGES, count, indexed));
+        }
+        Ok(count)
     }
 
     pub fn edges<'txn>(
         &self,
         txn: &'txn RoTxn,
-    ) -> Result<impl 'txn + Iterator<Item = PValue<V, E, P>>> {
+    ) -> Result<impl 'txn + Iterator<Item = Result<PValue<V, E, P>>>> {
+        self.edge_range(txn, (Bound::Unbounded, Bound::Unbounded))
+    }
+
+    /// The edges with ids after `id`, in id order, for resuming a scan where the last
+    /// one left off.
+    pub fn edges_after<'txn>(
+        &self,
+        txn: &'txn RoTxn,
+        id: &Id,
+    ) -> Result<impl 'txn + Iterator<Item = Result<PValue<V, E, P>>>> {
+        self.edge_range(txn, (Bound::Excluded(*id), Bound::Unbounded))
+    }
+
+    fn edge_range<'txn>(
+        &self,
+        txn: &'txn RoTxn,
+        range: (Bound<Id>, Bound<Id>),
+    ) -> Result<EdgeIter<'txn, V, E, P>> {
         Ok(EdgeIter {
-            iter: self.edge_db.iter(txn)?,
+            iter: self
+                .edge_db
+                .as_polymorph()
+                .range::<_, Id, ByteSlice, _>(txn, &range)?,
+            _marker: PhantomData,
         })
     }
 }
@@ -111,7 +265,8 @@ where
     E: 'static + Writable,
     P: 'static + Writable + Eq,
 {
-    pub(crate) iter: RoIter<'txn, Id, Edge<V, E, P>>,
+    pub(crate) iter: RoRange<'txn, Id, ByteSlice>,
+    _marker: PhantomData<(V, E, P)>,
 }
 
 impl<'txn, V, E, P> Iterator for EdgeIter<'txn, V, E, P>
@@ -120,15 +275,14 @@ where
     E: 'static + Writable,
     P: 'static + Writable + Eq,
 {
-    type Item = PValue<V, E, P>;
+    type Item = Result<PValue<V, E, P>>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let next = self.iter.next();
-        match next {
-            Some(Ok(edge)) => Some(PValue::Edge(edge.1)),
-            Some(Err(_)) => None,
-            None => None,
-        }
+        let (id, bytes) = match self.iter.next()? {
+            Ok(next) => next,
+            Err(e) => return Some(Err(corrupt(EDGES)(e))),
+        };
+        Some(decode(EDGES, id, bytes).map(PValue::Edge))
     }
 }
 
@@ -150,15 +304,15 @@ where
     E: 'txn + Writable,
     P: 'txn + Writable + Eq,
 {
-    type Item = Edge<V, E, P>;
+    type Item = Result<Edge<V, E, P>>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let next = self.iter.next();
-        match next {
-            Some(Ok(next)) => self.graph.get_edge_by_id(self.txn, &next.1).unwrap(),
-            Some(Err(_)) => None,
-            None => None,
-        }
+        let id = self
+            .iter
+            .next()?
+            .map(|(_, id)| id)
+            .map_err(corrupt(EDGES_IDX));
+        Some(self.graph.indexed_edge(self.txn, EDGES_IDX, id))
     }
 }
 
@@ -168,7 +322,7 @@ where
     E: Writable,
     P: Writable + Eq,
 {
-    pub fn new(
+    pub const fn new(
         graph: &'txn Graph<V, E, P>,
         txn: &'txn RoTxn,
         iter: RoRange<'txn, LabelId<E>, Id>,
@@ -182,6 +336,69 @@ where
     }
 }
 
+pub struct HexRange<'txn, E>
+where
+    E: 'static + Writable,
+{
+    iter: RoRange<'txn, ByteSlice, Id>,
+    _marker: PhantomData<E>,
+}
+
+impl<'txn, E> Iterator for HexRange<'txn, E>
+where
+    E: 'static + Writable,
+{
+    type Item = Result<(Id, Id, Id)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, id) = match self.iter.next()? {
+            Ok(next) => next,
+            Err(e) => return Some(Err(corrupt(HEXSTORE)(e))),
+        };
+        Some(HexOrder::from_db::<E>(key).map_err(|_| Error::Corrupt(HEXSTORE, Some(id))))
+    }
+}
+
+pub struct AdjacentEdges<'txn, V, E, P>
+where
+    V: 'static + Writable,
+    E: 'static + Writable,
+    P: 'static + Writable + Eq,
+{
+    iter: HexRange<'txn, E>,
+    graph: &'txn Graph<V, E, P>,
+    txn: &'txn RoTxn,
+}
+
+impl<'txn, V, E, P> AdjacentEdges<'txn, V, E, P>
+where
+    V: Writable,
+    E: Writable,
+    P: Writable + Eq,
+{
+    pub const fn new(
+        graph: &'txn Graph<V, E, P>,
+        txn: &'txn RoTxn,
+        iter: HexRange<'txn, E>,
+    ) -> Self {
+        Self { iter, graph, txn }
+    }
+}
+
+impl<'txn, V, E, P> Iterator for AdjacentEdges<'txn, V, E, P>
+where
+    V: 'txn + Writable,
+    E: 'txn + Writable,
+    P: 'txn + Writable + Eq,
+{
+    type Item = Result<Edge<V, E, P>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.iter.next()?.map(|(id, _, _)| id);
+        Some(self.graph.indexed_edge(self.txn, HEXSTORE, id))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use rstest::{fixture, rstest};
@@ -262,7 +479,7 @@ mod tests {
         for i in 0..10 {
             returned.push(graph.put_edge(
                 &mut txn,
-                &Edge::new(&ferb, &phineas, format!("test {}", i).into())?,
+                &Edge::new(&ferb, &phineas, format!("test {}", i))?,
             )?);
         }
         txn.commit()?;
@@ -270,9 +487,8 @@ mod tests {
         let txn = graph.read_txn()?;
         let edges: Vec<Edge<_, _, _>> = graph
             .edges(&txn)?
-            .map(FromPValue::from_pvalue)
-            .flatten()
-            .collect();
+            .map(|e| e.and_then(FromPValue::from_pvalue))
+            .collect::<Result<_>>()?;
         assert_eq!(edges, returned);
         Ok(())
     }
@@ -284,11 +500,11 @@ mod tests {
         let mut txn = graph.write_txn()?;
 
         let value: String = "brothers".into();
-        let edge = &Edge::new(&ferb, &phineas, value.clone())?;
+        let edge = &Edge::new(&ferb, &phineas, value)?;
 
         let mut returned = graph.put_edge(&mut txn, &edge.clone())?;
         returned.label = "sisters".to_string();
-        graph.put_edge(&mut txn, &returned.clone())?;
+        graph.put_edge(&mut txn, &returned)?;
         txn.commit()?;
 
         let txn = graph.read_txn()?;
@@ -300,4 +516,105 @@ mod tests {
 
         Ok(())
     }
+
+    #[rstest]
+    fn test_put_drops_stale_params(graph: Graph<String, String, ()>, vertices: Pair) -> Result<()> {
+        let Pair(ferb, phineas) = vertices;
+        let mut txn = graph.write_txn()?;
+        let edge = Edge::new(&ferb, &phineas, "brothers".into())?.set_param((), PValue::None);
+        let mut returned = graph.put_edge(&mut txn, &edge)?;
+        returned.parameters.clear();
+        graph.put_edge(&mut txn, &returned)?;
+        txn.commit()?;
+
+        let txn = graph.read_txn()?;
+        assert_eq!(graph.parameters_db.len(&txn)?, 0);
+        assert_eq!(graph.parameters_idx_db.len(&txn)?, 0);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_remove_edge(graph: Graph<String, String, ()>, vertices: Pair) -> Result<()> {
+        let Pair(ferb, phineas) = vertices;
+        let mut txn = graph.write_txn()?;
+        let mut edge = Edge::new(&ferb, &phineas, "brothers".into())?;
+        edge.parameters.insert((), PValue::None);
+        let edge = graph.put_edge(&mut txn, &edge)?;
+        let id = edge.id.unwrap();
+
+        let removed = graph.remove_edge(&mut txn, &id)?;
+        assert_eq!(removed, Some(edge));
+        assert!(graph.remove_edge(&mut txn, &id)?.is_none());
+        txn.commit()?;
+
+        let txn = graph.read_txn()?;
+        assert_eq!(graph.edge_count(&txn)?, 0);
+        assert!(graph.get_edge_by_label(&txn, &"brothers".into())?.is_none());
+        assert_eq!(graph.parameters_db.len(&txn)?, 0);
+        assert_eq!(graph.parameters_idx_db.len(&txn)?, 0);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_adjacent_edges(graph: Graph<String, String, ()>, vertices: Pair) -> Result<()> {
+        let Pair(ferb, phineas) = vertices;
+        let mut txn = graph.write_txn()?;
+        let brothers = graph.put_edge(&mut txn, &Edge::new(&ferb, &phineas, "brothers".into())?)?;
+        let friends = graph.put_edge(&mut txn, &Edge::new(&ferb, &phineas, "friends".into())?)?;
+        let back = graph.put_edge(&mut txn, &Edge::new(&phineas, &ferb, "brothers".into())?)?;
+        txn.commit()?;
+
+        let (f, p) = (ferb.id.unwrap(), phineas.id.unwrap());
+        let txn = graph.read_txn()?;
+        let from_phineas: Vec<_> = graph
+            .get_edges_from(&txn, &p, None)?
+            .collect::<Result<_>>()?;
+        assert_eq!(from_phineas.len(), 2);
+        assert!(from_phineas.contains(&brothers) && from_phineas.contains(&friends));
+
+        let to_phineas: Vec<_> = graph.get_edges_to(&txn, &p, None)?.collect::<Result<_>>()?;
+        assert_eq!(to_phineas, vec![back.clone()]);
+
+        let labelled: Vec<_> = graph
+            .get_edges_from(&txn, &p, Some(&"friends".into()))?
+            .collect::<Result<_>>()?;
+        assert_eq!(labelled, vec![friends.clone()]);
+        let labelled: Vec<_> = graph
+            .get_edges_to(&txn, &f, Some(&"brothers".into()))?
+            .collect::<Result<_>>()?;
+        assert_eq!(labelled, vec![brothers.clone()]);
+        let labelled: Vec<_> = graph
+            .get_edges_between(&txn, &p, &f, Some(&"friends".into()))?
+            .collect::<Result<_>>()?;
+        assert_eq!(labelled, vec![friends.clone()]);
+        assert_eq!(
+            graph
+                .get_edges_from(&txn, &p, Some(&"brother".into()))?
+                .count(),
+            0
+        );
+
+        let between: Vec<_> = graph
+            .get_edges_between(&txn, &f, &p, None)?
+            .collect::<Result<_>>()?;
+        assert_eq!(between, vec![back]);
+        drop(txn);
+
+        let mut txn = graph.write_txn()?;
+        let mut moved = brothers;
+        moved.from = f;
+        moved.to = p;
+        graph.put_edge(&mut txn, &moved)?;
+        graph.remove_edge(&mut txn, friends.id.as_ref().unwrap())?;
+        txn.commit()?;
+
+        let txn = graph.read_txn()?;
+        assert_eq!(graph.get_edges_from(&txn, &p, None)?.count(), 0);
+        assert_eq!(graph.get_edges_to(&txn, &p, None)?.count(), 2);
+        assert_eq!(graph.hexstore_db.len(&txn)?, 2 * ORDERS.len());
+
+        Ok(())
+    }
 }
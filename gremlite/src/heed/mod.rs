@@ -1,21 +1,60 @@
+pub(crate) mod codec;
 pub mod edge;
 pub mod vertex;
 
-use heed::{BytesDecode, BytesEncode, Database, Env, EnvOpenOptions, RoTxn, RwTxn};
+use heed::{
+    types::{ByteSlice, OwnedType, Str},
+    BytesDecode, BytesEncode, Database, Env, EnvOpenOptions, RoTxn, RwTxn,
+};
 use parking_lot::Mutex;
-use postcard::{from_bytes, to_stdvec};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use std::{borrow::Cow, fmt::Debug, path::Path, time::Duration};
+use std::{
+    borrow::Cow, collections::HashMap, fmt::Debug, iter::once, ops::Bound, path::Path,
+    time::Duration,
+};
 use tracing::instrument;
 
 use crate::{
     error::{Error, Result},
-    graph::{parameter::PValue, Edge, Id, Vertex, Writable},
-    gremlin::{terminator::TraversalTerminator, RWTraversalSource},
+    graph::{parameter::PValue, Edge, Id, Type, Vertex, Writable},
+    gremlin::{terminator::TraversalTerminator, ROTraversalSource, RWTraversalSource},
 };
 
+use codec::{encode_value, from_key, to_key};
 use ulid::Generator;
 
+pub(crate) const VERTICES: &str = "vertices:v1";
+pub(crate) const VERTICES_IDX: &str = "vertices_idx:v1";
+pub(crate) const EDGES: &str = "edges:v1";
+pub(crate) const EDGES_IDX: &str = "edges_idx:v1";
+pub(crate) const HEXSTORE: &str = "hexstore:v1";
+pub(crate) const PARAMETERS: &str = "parameters:v1";
+pub(crate) const PARAMETERS_IDX: &str = "parameters_idx:v1";
+pub(crate) const PARAMETERS_VALUE_IDX: &str = "parameters_value_idx:v1";
+pub(crate) const META: &str = "meta:v1";
+
+/// The layout of the index databases, bumped whenever one changes. A graph written with an
+/// older layout, or before the layout was recorded, has its indexes rebuilt from its vertex
+/// and edge records when it's opened, so the databases keep their names across layouts.
+pub(crate) const SCHEMA_VERSION: u32 = 2;
+const SCHEMA_KEY: &str = "schema";
+
+/// Decodes a record read raw from `db`, so a corrupt one can be reported by its id.
+pub(crate) fn decode<'a, T>(db: &'static str, id: Id, bytes: &'a [u8]) -> Result<T>
+where
+    T: BytesDecode<'a, DItem = T>,
+{
+    T::bytes_decode(bytes).ok_or(Error::Corrupt(db, Some(id)))
+}
+
+/// Maps a failure to decode an entry in `db`, whose key isn't known, to `Error::Corrupt`.
+pub(crate) fn corrupt(db: &'static str) -> impl Fn(heed::Error) -> Error {
+    move |e| match e {
+        heed::Error::Decoding => Error::Corrupt(db, None),
+        e => Error::Heed(e),
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct LabelId<Label>(
     #[serde(bound(deserialize = "Label: DeserializeOwned"))] Label,
@@ -30,7 +69,7 @@ where
 {
     type EItem = Self;
     fn bytes_encode(item: &'a Self::EItem) -> Option<Cow<'a, [u8]>> {
-        to_stdvec(item).map(Cow::Owned).ok()
+        to_key(item).map(Cow::Owned).ok()
     }
 }
 
@@ -40,7 +79,7 @@ where
 {
     type DItem = Self;
     fn bytes_decode(bytes: &'a [u8]) -> Option<Self::DItem> {
-        from_bytes(bytes).ok()
+        from_key(bytes).ok()
     }
 }
 
@@ -58,7 +97,7 @@ where
 {
     type EItem = Self;
     fn bytes_encode(item: &'a Self::EItem) -> Option<Cow<'a, [u8]>> {
-        to_stdvec(item).map(Cow::Owned).ok()
+        to_key(item).map(Cow::Owned).ok()
     }
 }
 
@@ -68,7 +107,7 @@ where
 {
     type DItem = Self;
     fn bytes_decode(bytes: &'a [u8]) -> Option<Self::DItem> {
-        from_bytes(bytes).ok()
+        from_key(bytes).ok()
     }
 }
 
@@ -83,7 +122,7 @@ where
 {
     type EItem = Self;
     fn bytes_encode(item: &'a Self::EItem) -> Option<Cow<'a, [u8]>> {
-        to_stdvec(item).map(Cow::Owned).ok()
+        to_key(item).map(Cow::Owned).ok()
     }
 }
 
@@ -93,7 +132,7 @@ where
 {
     type DItem = Self;
     fn bytes_decode(bytes: &'a [u8]) -> Option<Self::DItem> {
-        from_bytes(bytes).ok()
+        from_key(bytes).ok()
     }
 }
 
@@ -111,9 +150,17 @@ where
 
     pub(crate) edge_db: Database<Id, Edge<V, E, P>>,
     pub(crate) edge_idx_db: Database<LabelId<E>, Id>,
+    /// Every edge keyed in each `HexOrder` by its ends, label and id, valued by its id.
+    pub(crate) hexstore_db: Database<ByteSlice, Id>,
 
     pub(crate) parameters_db: Database<IdParam<P>, PValue<V, E, P>>,
     pub(crate) parameters_idx_db: Database<ParamId<P>, Id>,
+    /// Keyed by parameter key, the order-preserving encoding of its value (see
+    /// `codec::encode_value`) and the element's id. Values without an encoding are left out.
+    pub(crate) parameters_value_idx_db: Database<ByteSlice, Id>,
+
+    /// Facts about the graph as a whole, such as its `SCHEMA_VERSION`.
+    pub(crate) meta_db: Database<Str, OwnedType<u32>>,
     // TODO: Create a collection of databases that can be used as indices
 }
 
@@ -141,14 +188,17 @@ where
             .map_size(2 << 40)
             .open(path)?;
         let generator = Mutex::new(Generator::new());
-        let vertex_db = env.create_database(Some("vertices:v1"))?;
-        let vertex_idx_db = env.create_database(Some("vertices_idx:v1"))?;
-        let edge_db = env.create_database(Some("edges:v1"))?;
-        let edge_idx_db = env.create_database(Some("edges_idx:v1"))?;
-
-        let parameters_db = env.create_database(Some("parameters:v1"))?;
-        let parameters_idx_db = env.create_database(Some("parameters_idx:v1"))?;
-        Ok(Self {
+        let vertex_db = env.create_database(Some(VERTICES))?;
+        let vertex_idx_db = env.create_database(Some(VERTICES_IDX))?;
+        let edge_db = env.create_database(Some(EDGES))?;
+        let edge_idx_db = env.create_database(Some(EDGES_IDX))?;
+        let hexstore_db = env.create_database(Some(HEXSTORE))?;
+
+        let parameters_db = env.create_database(Some(PARAMETERS))?;
+        let parameters_idx_db = env.create_database(Some(PARAMETERS_IDX))?;
+        let parameters_value_idx_db = env.create_database(Some(PARAMETERS_VALUE_IDX))?;
+        let meta_db = env.create_database(Some(META))?;
+        let graph = Self {
             env,
             generator,
 
@@ -156,22 +206,80 @@ where
             vertex_idx_db,
             edge_db,
             edge_idx_db,
+            hexstore_db,
 
             parameters_db,
             parameters_idx_db,
-        })
+            parameters_value_idx_db,
+
+            meta_db,
+        };
+        graph.migrate()?;
+        Ok(graph)
+    }
+
+    /// Brings the indexes up to `SCHEMA_VERSION`, refusing to open a graph written by a
+    /// newer version that this one can't read.
+    fn migrate(&self) -> Result<()> {
+        let version = self.meta_db.get(&self.read_txn()?, SCHEMA_KEY)?;
+        match version {
+            Some(SCHEMA_VERSION) => return Ok(()),
+            Some(version) if version > SCHEMA_VERSION => {
+                return Err(Error::Schema(version, SCHEMA_VERSION))
+            }
+            _ => {}
+        }
+        let mut txn = self.write_txn()?;
+        self.rebuild_indexes(&mut txn)?;
+        self.meta_db.put(&mut txn, SCHEMA_KEY, &SCHEMA_VERSION)?;
+        txn.commit()?;
+        Ok(())
+    }
+
+    /// Clears every index database and fills it again from the vertex and edge records.
+    pub(crate) fn rebuild_indexes(&self, txn: &mut RwTxn) -> Result<()> {
+        self.vertex_idx_db.clear(txn)?;
+        self.edge_idx_db.clear(txn)?;
+        self.hexstore_db.clear(txn)?;
+        self.parameters_db.clear(txn)?;
+        self.parameters_idx_db.clear(txn)?;
+        self.parameters_value_idx_db.clear(txn)?;
+
+        let none = HashMap::new();
+        let vertices = self
+            .vertex_db
+            .iter(txn)?
+            .map(|next| next.map_err(corrupt(VERTICES)))
+            .collect::<Result<Vec<_>>>()?;
+        for (id, vertex) in vertices {
+            self.vertex_idx_db
+                .put(txn, &LabelId(vertex.label.clone(), id), &id)?;
+            self.put_parameters(txn, &id, &none, &vertex.parameters)?;
+        }
+        let edges = self
+            .edge_db
+            .iter(txn)?
+            .map(|next| next.map_err(corrupt(EDGES)))
+            .collect::<Result<Vec<_>>>()?;
+        for (id, edge) in edges {
+            self.edge_idx_db
+                .put(txn, &LabelId(edge.label.clone(), id), &id)?;
+            self.put_parameters(txn, &id, &none, &edge.parameters)?;
+            self.put_hexstore(txn, &edge)?;
+        }
+        Ok(())
     }
 
     #[inline]
-    pub fn write_txn(&self) -> Result<RwTxn> {
+    pub fn write_txn(&self) -> Result<RwTxn<'_>> {
         self.write_txn_wait(Duration::from_secs(30))
     }
 
     #[instrument]
-    pub fn write_txn_wait(&self, _d: Duration) -> Result<RwTxn> {
+    pub fn write_txn_wait(&self, _d: Duration) -> Result<RwTxn<'_>> {
         // TODO: Need to add timeout to heed
         let txn = self.env.write_txn();
-        if let Err(heed::Error::Mdb(heed::MdbError::Busy)) = txn {
+        if matches!(txn, Err(heed::Error::Mdb(heed::MdbError::Busy))) {
             return Err(Error::Busy);
         }
         Ok(txn?)
@@ -188,9 +296,172 @@ where
         self.vertex_idx_db.clear(txn)?;
         self.edge_db.clear(txn)?;
         self.edge_idx_db.clear(txn)?;
+        self.hexstore_db.clear(txn)?;
+        self.parameters_db.clear(txn)?;
+        self.parameters_idx_db.clear(txn)?;
+        self.parameters_value_idx_db.clear(txn)?;
+        Ok(())
+    }
+
+    /// Removes the parameter `key` from the vertex or edge with `id`, returning its old
+    /// value if it had one.
+    pub fn remove_property(
+        &self,
+        txn: &mut RwTxn,
+        id: &Id,
+        key: &P,
+    ) -> Result<Option<PValue<V, E, P>>> {
+        let value = match id.get_type() {
+            Type::Vertex => match self.get_vertex_by_id(txn, id)? {
+                Some(mut vertex) => {
+                    let value = vertex.parameters.remove(key);
+                    self.vertex_db.put(txn, id, &vertex)?;
+                    value
+                }
+                None => return Err(Error::NotFound(*id)),
+            },
+            Type::Edge => match self.get_edge_by_id(txn, id)? {
+                Some(mut edge) => {
+                    let value = edge.parameters.remove(key);
+                    self.edge_db.put(txn, id, &edge)?;
+                    value
+                }
+                None => return Err(Error::NotFound(*id)),
+            },
+            Type::Parameter => return Err(Error::BadRequest("parameters have no properties")),
+        };
+        if let Some(value) = &value {
+            self.delete_parameters(txn, id, once((key, value)))?;
+        }
+        Ok(value)
+    }
+
+    /// Writes the parameter rows for `id`, dropping the rows of values in `old` that are
+    /// no longer in `new`.
+    pub(crate) fn put_parameters(
+        &self,
+        txn: &mut RwTxn,
+        id: &Id,
+        old: &HashMap<P, PValue<V, E, P>>,
+        new: &HashMap<P, PValue<V, E, P>>,
+    ) -> Result<()> {
+        let stale = old.iter().filter(|&(k, v)| new.get(k) != Some(v));
+        self.delete_parameters(txn, id, stale)?;
+        for (key, value) in new {
+            if old.get(key) != Some(value) {
+                self.parameters_db
+                    .put(txn, &IdParam(*id, key.clone()), value)?;
+                self.parameters_idx_db
+                    .put(txn, &ParamId(key.clone(), *id), id)?;
+                if let Some(row) = Self::value_idx_key(key, value, id)? {
+                    self.parameters_value_idx_db.put(txn, &row, id)?;
+                }
+            }
+        }
         Ok(())
     }
 
+    pub(crate) fn delete_parameters<'a, I>(&self, txn: &mut RwTxn, id: &Id, params: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (&'a P, &'a PValue<V, E, P>)>,
+    {
+        for (key, value) in params {
+            self.parameters_db.delete(txn, &IdParam(*id, key.clone()))?;
+            self.parameters_idx_db
+                .delete(txn, &ParamId(key.clone(), *id))?;
+            if let Some(row) = Self::value_idx_key(key, value, id)? {
+                self.parameters_value_idx_db.delete(txn, &row)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn value_idx_key(key: &P, value: &PValue<V, E, P>, id: &Id) -> Result<Option<Vec<u8>>> {
+        encode_value(value)
+            .map(|value| Ok([to_key(key)?, value, to_key(id)?].concat()))
+            .transpose()
+    }
+
+    /// Ids of the elements whose `key` parameter may lie between `start` and `end`, in
+    /// value order.
+    ///
+    /// Both ends are scanned inclusively, so callers have to check the values of what they
+    /// find. An open end stays within the type of the other end.
+    pub(crate) fn value_range<'txn>(
+        &self,
+        txn: &'txn RoTxn,
+        key: &P,
+        start: Bound<&PValue<V, E, P>>,
+        end: Bound<&PValue<V, E, P>>,
+    ) -> Result<impl 'txn + Iterator<Item = Result<Id>>> {
+        let prefix = to_key(key)?;
+        let encode = |bound: Bound<&PValue<V, E, P>>| -> Result<Option<Vec<u8>>> {
+            match bound {
+                Bound::Included(value) | Bound::Excluded(value) => {
+                    let value =
+                        encode_value(value).ok_or(Error::BadRequest("value can't be indexed"))?;
+                    Ok(Some([prefix.as_slice(), &value].concat()))
+                }
+                Bound::Unbounded => Ok(None),
+            }
+        };
+        let (start, end) = (encode(start)?, encode(end)?);
+        let tag = start.as_ref().or(end.as_ref()).map(|b| b[prefix.len()]);
+        let lower = start.unwrap_or_else(|| [prefix.as_slice(), tag.as_slice()].concat());
+        // Past every id stored under the end value
+        let upper = end.map_or_else(
+            || [prefix.as_slice(), &[tag.map_or(0xFF, |t| t + 1)]].concat(),
+            |end| [end.as_slice(), &[0xFF]].concat(),
+        );
+        let range = (
+            Bound::Included(lower.as_slice()),
+            Bound::Included(upper.as_slice()),
+        );
+        Ok(self
+            .parameters_value_idx_db
+            .range(txn, &range)?
+            .map(|next| {
+                next.map(|(_, id)| id)
+                    .map_err(corrupt(PARAMETERS_VALUE_IDX))
+            }))
+    }
+
+    /// Ids of the elements in the value index under `key`, from the largest value to the
+    /// smallest. Ids under the same value keep their ascending order, the way a stable sort
+    /// leaves them. There's no reverse cursor, so this isn't streamed: every id under `key`
+    /// is read into memory before the first is returned, though their elements aren't.
+    pub(crate) fn value_ids_desc(&self, txn: &RoTxn, key: &P) -> Result<Vec<Id>> {
+        let prefix = to_key(key)?;
+        let upper = [prefix.as_slice(), &[0xFF]].concat();
+        let range = (
+            Bound::Included(prefix.as_slice()),
+            Bound::Included(upper.as_slice()),
+        );
+        let mut runs: Vec<(Vec<u8>, Vec<Id>)> = vec![];
+        for next in self.parameters_value_idx_db.range(txn, &range)? {
+            let (row, id) = next.map_err(corrupt(PARAMETERS_VALUE_IDX))?;
+            let value = row
+                .get(prefix.len()..row.len().saturating_sub(to_key(&id)?.len()))
+                .ok_or(Error::Corrupt(PARAMETERS_VALUE_IDX, Some(id)))?;
+            match runs.last_mut() {
+                Some((last, ids)) if last.as_slice() == value => ids.push(id),
+                _ => runs.push((value.to_vec(), vec![id])),
+            }
+        }
+        Ok(runs.into_iter().rev().flat_map(|(_, ids)| ids).collect())
+    }
+
+    /// Runs `f` with a traversal source over a fresh read transaction. Any number of these
+    /// can run at once, alongside a writer.
+    pub fn read_traversal<'graph, T, F>(&'graph self, f: F) -> Result<T>
+    where
+        F: for<'a> FnOnce(&'a ROTraversalSource<'graph, V, E, P>, &'a RoTxn) -> Result<T>,
+    {
+        let g = ROTraversalSource::new(self);
+        let txn = self.read_txn()?;
+        (f)(&g, &txn)
+    }
+
     pub fn write_traversal<'graph, T, F>(&'graph self, f: F) -> Result<T>
     where
         F: for<'a> FnOnce(
@@ -207,7 +478,7 @@ where
         result
     }
 
-    pub(crate) fn terminator(&self) -> TraversalTerminator<V, E, P> {
+    pub(crate) const fn terminator<M>(&self) -> TraversalTerminator<'_, V, E, P, M> {
         TraversalTerminator::new(self)
     }
 }
@@ -221,13 +492,20 @@ mod tests {
     use crate::{
         error::Error,
         graph::{
+            edge::ORDERS,
             parameter::{FromPValue, ToPValue},
-            Type, Vertex,
+            Path, Type, Vertex,
         },
-        gremlin::TraversalSource,
+        gremlin::{By, MergeE, MergeV, Order, Predicate, TraversalSource, __},
     };
     use parking::Parker;
-    use std::{collections::HashSet, sync::Arc, thread::JoinHandle};
+    use std::{
+        collections::{HashMap, HashSet},
+        convert::TryFrom,
+        iter::once,
+        sync::Arc,
+        thread::JoinHandle,
+    };
 
     #[fixture]
     fn tmpdir() -> TempDir {
@@ -318,8 +596,7 @@ mod tests {
         let vertices: Vec<Vertex<String, String, ()>> = graph
             .vertices(&txn)
             .unwrap()
-            .map(Vertex::from_pvalue)
-            .map(Result::unwrap)
+            .map(|v| v.and_then(Vertex::from_pvalue).unwrap())
             .collect();
         assert_eq!(vertices.len(), 2);
         let vertex_values: HashSet<String> = vertices.iter().map(|n| n.get_label()).collect();
@@ -373,8 +650,7 @@ mod tests {
         let vertices: Vec<Vertex<String, String, ()>> = graph
             .vertices(&txn)
             .unwrap()
-            .map(Vertex::from_pvalue)
-            .map(Result::unwrap)
+            .map(|v| v.and_then(Vertex::from_pvalue).unwrap())
             .collect();
         assert_eq!(vertices.len(), 2);
         let vertex_values: Vec<String> = vertices.iter().map(|n| n.get_label()).collect();
@@ -387,19 +663,18 @@ mod tests {
 
     #[rstest]
     fn test_vertex_traversal(graph: Graph<String, String, ()>) -> Result<()> {
-        let returned = graph.write_traversal(|g, mut txn| g.add_v("test".into()).next(&mut txn))?;
-        let returned = Vertex::from_pvalue(returned.clone()).unwrap();
+        let returned = graph.write_traversal(|g, txn| g.add_v("test".into()).next(txn))?;
+        let returned = Vertex::from_pvalue(returned).unwrap();
 
-        let vs = graph.write_traversal(|g, mut txn| g.v(()).to_list(&mut txn))?;
+        let vs = graph.write_traversal(|g, txn| g.v(()).to_list(txn))?;
         assert_eq!(vs.len(), 1);
         assert_eq!(vs[0], returned.to_pvalue());
 
-        let vs = graph.write_traversal(|g, mut txn| g.v(returned.id.unwrap()).to_list(&mut txn))?;
+        let vs = graph.write_traversal(|g, txn| g.v(returned.id.unwrap()).to_list(txn))?;
         assert_eq!(vs.len(), 1);
         assert_eq!(vs[0], returned.to_pvalue());
 
-        let vs =
-            graph.write_traversal(|g, mut txn| g.v(Id::nil(Type::Vertex)).to_list(&mut txn))?;
+        let vs = graph.write_traversal(|g, txn| g.v(Id::nil(Type::Vertex)).to_list(txn))?;
         assert_eq!(vs.len(), 0);
 
         Ok(())
@@ -408,26 +683,1800 @@ mod tests {
     #[rstest]
     fn test_edge_traversal(graph: Graph<String, String, ()>) -> Result<()> {
         // TODO: clean this crap up
-        let e = graph.write_traversal(|g, mut txn| {
-            let v1 = g.add_v("test".into()).next(&mut txn)?;
-            let v2 = g.add_v("test".into()).next(&mut txn)?;
-            let e = g.add_e("test".into()).from(&v1)?.to(&v2)?.next(&mut txn)?;
+        let e = graph.write_traversal(|g, txn| {
+            let v1 = g.add_v("test".into()).next(txn)?;
+            let v2 = g.add_v("test".into()).next(txn)?;
+            let e = g.add_e("test".into()).from(&v1)?.to(&v2)?.next(txn)?;
             Ok(e)
         })?;
-        let e = Edge::from_pvalue(e.clone())?;
+        let e = Edge::from_pvalue(e)?;
 
-        let es = graph.write_traversal(|g, mut txn| g.e(()).to_list(&mut txn))?;
+        let es = graph.write_traversal(|g, txn| g.e(()).to_list(txn))?;
         assert_eq!(es.len(), 1);
         assert_eq!(es[0], e.to_pvalue());
 
-        let es = graph.write_traversal(|g, mut txn| g.e(e.id.unwrap()).to_list(&mut txn))?;
+        let es = graph.write_traversal(|g, txn| g.e(e.id.unwrap()).to_list(txn))?;
         assert_eq!(es.len(), 1);
         assert_eq!(es[0], e.to_pvalue());
 
-        let es =
-            graph.write_traversal(|g, mut txn| g.e(Id::nil(Type::Vertex)).to_list(&mut txn))?;
+        let es = graph.write_traversal(|g, txn| g.e(Id::nil(Type::Vertex)).to_list(txn))?;
         assert_eq!(es.len(), 0);
 
         Ok(())
     }
+
+    #[rstest]
+    fn test_adjacent_traversal(graph: Graph<String, String, ()>) -> Result<()> {
+        let (phineas, ferb, isabella, brothers, friends) = graph.write_traversal(|g, txn| {
+            let phineas = g.add_v("phineas".into()).next(txn)?;
+            let ferb = g.add_v("ferb".into()).next(txn)?;
+            let isabella = g.add_v("isabella".into()).next(txn)?;
+            let brothers = g
+                .add_e("brother".into())
+                .from(&phineas)?
+                .to(&ferb)?
+                .next(txn)?;
+            g.add_e("brother".into())
+                .from(&ferb)?
+                .to(&phineas)?
+                .next(txn)?;
+            let friends = g
+                .add_e("friend".into())
+                .from(&phineas)?
+                .to(&isabella)?
+                .next(txn)?;
+            Ok((phineas, ferb, isabella, brothers, friends))
+        })?;
+        let p = Id::try_from(&phineas)?;
+        let i = Id::try_from(&isabella)?;
+
+        let out = graph.write_traversal(|g, txn| g.v(p).out(()).to_list(txn))?;
+        assert_eq!(out.len(), 2);
+        assert!(out.contains(&ferb) && out.contains(&isabella));
+
+        let friend = graph.write_traversal(|g, txn| g.v(p).out(["friend".into()]).to_list(txn))?;
+        assert_eq!(friend, vec![isabella.clone()]);
+
+        let into = graph.write_traversal(|g, txn| g.v(p).in_(()).to_list(txn))?;
+        assert_eq!(into, vec![ferb.clone()]);
+
+        let both = graph.write_traversal(|g, txn| g.v(p).both(["brother".into()]).to_list(txn))?;
+        assert_eq!(both, vec![ferb.clone(), ferb.clone()]);
+
+        let edges = graph.write_traversal(|g, txn| g.v(i).in_e(()).to_list(txn))?;
+        assert_eq!(edges, vec![friends]);
+
+        let edges = graph.write_traversal(|g, txn| g.v(p).both_e(()).to_list(txn))?;
+        assert_eq!(edges.len(), 3);
+
+        let chained =
+            graph.write_traversal(|g, txn| g.v(i).in_(()).out(["brother".into()]).to_list(txn))?;
+        assert_eq!(chained, vec![ferb.clone()]);
+
+        let ends = graph.write_traversal(|g, txn| {
+            let e = Id::try_from(&brothers)?;
+            Ok((g.e(e).out_v().next(txn)?, g.e(e).in_v().next(txn)?))
+        })?;
+        assert_eq!(ends, (phineas.clone(), ferb));
+
+        let others = graph.write_traversal(|g, txn| g.v(i).in_e(()).other_v().to_list(txn))?;
+        assert_eq!(others, vec![phineas]);
+
+        let others = graph
+            .write_traversal(|g, txn| g.v(p).out_e(["friend".into()]).other_v().to_list(txn))?;
+        assert_eq!(others, vec![isabella]);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_pipeline(graph: Graph<String, String, ()>) -> Result<()> {
+        graph.write_traversal(|g, txn| {
+            g.add_v("phineas".into()).next(txn)?;
+            g.add_v("ferb".into()).next(txn)?;
+            Ok(())
+        })?;
+
+        let shadows =
+            graph.write_traversal(|g, txn| g.v(()).add_v("shadow".into()).to_list(txn))?;
+        assert_eq!(shadows.len(), 2);
+
+        let vs = graph.write_traversal(|g, txn| g.v(()).to_list(txn))?;
+        assert_eq!(vs.len(), 4);
+        let vs = graph.write_traversal(|g, txn| g.v(()).v(()).to_list(txn))?;
+        assert_eq!(vs.len(), 16);
+
+        let missing =
+            graph.write_traversal(|g, txn| g.add_e("e".into()).from(&shadows[0])?.next(txn));
+        assert!(matches!(missing, Err(Error::BadRequest(_))));
+        let vs = graph.write_traversal(|g, txn| g.e(()).to_list(txn))?;
+        assert_eq!(vs.len(), 0);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_filter_traversal(graph: Graph<String, String, ()>) -> Result<()> {
+        let mut txn = graph.write_txn()?;
+        let phineas = graph.put_vertex(
+            &mut txn,
+            &Vertex::new("kid".into()).set_param((), PValue::I32(10)),
+        )?;
+        let candace = graph.put_vertex(
+            &mut txn,
+            &Vertex::new("kid".into()).set_param((), PValue::I32(15)),
+        )?;
+        let perry = graph.put_vertex(&mut txn, &Vertex::new("platypus".into()))?;
+        let owns = graph.put_edge(&mut txn, &Edge::new(&perry, &phineas, "owns".into())?)?;
+        txn.commit()?;
+        let (phineas, candace, perry) =
+            (phineas.to_pvalue(), candace.to_pvalue(), perry.to_pvalue());
+
+        let kids =
+            graph.write_traversal(|g, txn| g.v(()).has_label(["kid".into()]).to_list(txn))?;
+        assert_eq!(kids, vec![phineas.clone(), candace.clone()]);
+
+        let all = graph.write_traversal(|g, txn| {
+            g.v(())
+                .has_label(["platypus".into(), "kid".into(), "kid".into()])
+                .to_list(txn)
+        })?;
+        assert_eq!(all.len(), 3);
+
+        let aged = graph.write_traversal(|g, txn| g.v(()).has((), PValue::I64(15)).to_list(txn))?;
+        assert_eq!(aged, vec![candace]);
+
+        let young = graph.write_traversal(|g, txn| {
+            g.v(())
+                .has((), Predicate::between(PValue::I32(0), PValue::I32(13)))
+                .to_list(txn)
+        })?;
+        assert_eq!(young, vec![phineas.clone()]);
+
+        let within = graph.write_traversal(|g, txn| {
+            let ages = vec![PValue::I32(10), PValue::Double(10.0), PValue::I64(15)];
+            g.v(()).has((), Predicate::within(ages)).to_list(txn)
+        })?;
+        assert_eq!(within.len(), 2);
+
+        let older = graph.write_traversal(|g, txn| {
+            g.v(()).has((), Predicate::gt(PValue::I32(10))).to_list(txn)
+        })?;
+        assert_eq!(older.len(), 1);
+
+        let keyed = graph.write_traversal(|g, txn| g.v(()).has_key([()]).to_list(txn))?;
+        assert_eq!(keyed.len(), 2);
+
+        let unkeyed = graph.write_traversal(|g, txn| g.v(()).has_not(()).to_list(txn))?;
+        assert_eq!(unkeyed, vec![perry.clone()]);
+
+        let p = Id::try_from(&phineas)?;
+        let by_id = graph.write_traversal(|g, txn| g.v(()).has_id(p).to_list(txn))?;
+        assert_eq!(by_id, vec![phineas]);
+
+        let owners = graph.write_traversal(|g, txn| {
+            g.v(p)
+                .out_e(())
+                .has_edge_label(["owns".into()])
+                .in_v()
+                .has_label(["platypus".into()])
+                .to_list(txn)
+        })?;
+        assert_eq!(owners, vec![perry]);
+
+        let edges = graph
+            .write_traversal(|g, txn| g.e(()).has_edge_label(["likes".into()]).to_list(txn))?;
+        assert!(edges.is_empty());
+        assert_eq!(
+            graph.write_traversal(|g, txn| g.e(()).has_edge_label(["owns".into()]).to_list(txn))?,
+            vec![owns.to_pvalue()]
+        );
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_property_traversal(graph: Graph<String, String, ()>) -> Result<()> {
+        let phineas = graph.write_traversal(|g, txn| {
+            g.add_v("phineas".into())
+                .property((), PValue::I32(10))
+                .next(txn)
+        })?;
+        let p = Id::try_from(&phineas)?;
+        let ferb = graph.write_traversal(|g, txn| g.add_v("ferb".into()).next(txn))?;
+
+        let (aged, edge) = graph.write_traversal(|g, txn| {
+            let aged = g.v(p).property((), PValue::I32(11)).next(txn)?;
+            let edge = g
+                .add_e("brother".into())
+                .from(&ferb)?
+                .to(&phineas)?
+                .property((), PValue::Bool(true))
+                .next(txn)?;
+            Ok((aged, edge))
+        })?;
+        let e = Id::try_from(&edge)?;
+
+        let txn = graph.read_txn()?;
+        let vertex = graph.get_vertex_by_id(&txn, &p)?.unwrap();
+        assert_eq!(vertex.parameters.get(&()), Some(&PValue::I32(11)));
+        assert_eq!(aged, vertex.to_pvalue());
+        let stored = graph.get_edge_by_id(&txn, &e)?.unwrap();
+        assert_eq!(stored.parameters.get(&()), Some(&PValue::Bool(true)));
+        assert_eq!(graph.parameters_db.len(&txn)?, 2);
+        assert_eq!(graph.parameters_idx_db.len(&txn)?, 2);
+        drop(txn);
+
+        let both = graph.write_traversal(|g, txn| {
+            g.e(e)
+                .property((), PValue::Bool(false))
+                .out_v()
+                .has((), PValue::Bool(false))
+                .to_list(txn)
+        })?;
+        assert!(both.is_empty());
+        let brothers = graph
+            .write_traversal(|g, txn| g.v(()).has((), PValue::I32(11)).in_(()).to_list(txn))?;
+        assert_eq!(brothers, vec![ferb]);
+
+        let pets = graph.write_traversal(|g, txn| {
+            g.v(())
+                .property((), PValue::String("perry".into()))
+                .to_list(txn)
+        })?;
+        assert_eq!(pets.len(), 2);
+        let txn = graph.read_txn()?;
+        assert_eq!(graph.parameters_idx_db.len(&txn)?, 3);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_remove_property(graph: Graph<String, String, ()>) -> Result<()> {
+        let mut txn = graph.write_txn()?;
+        let vertex = graph.put_vertex(
+            &mut txn,
+            &Vertex::new("test".into()).set_param((), PValue::I32(1)),
+        )?;
+        let id = vertex.id.unwrap();
+        let removed = graph.remove_property(&mut txn, &id, &())?;
+        assert_eq!(removed, Some(PValue::I32(1)));
+        assert_eq!(graph.remove_property(&mut txn, &id, &())?, None);
+        let missing = graph.remove_property(&mut txn, &Id::nil(Type::Edge), &());
+        assert!(matches!(missing, Err(Error::NotFound(_))));
+        txn.commit()?;
+
+        let txn = graph.read_txn()?;
+        let fetched = graph.get_vertex_by_id(&txn, &id)?.unwrap();
+        assert!(fetched.parameters.is_empty());
+        assert_eq!(graph.parameters_db.len(&txn)?, 0);
+        assert_eq!(graph.parameters_idx_db.len(&txn)?, 0);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_clear(graph: Graph<String, String, ()>) -> Result<()> {
+        let mut txn = graph.write_txn()?;
+        let ferb = graph.put_vertex(
+            &mut txn,
+            &Vertex::new("ferb".into()).set_param((), PValue::None),
+        )?;
+        let phineas = graph.put_vertex(&mut txn, &Vertex::new("phineas".into()))?;
+        graph.put_edge(&mut txn, &Edge::new(&ferb, &phineas, "brothers".into())?)?;
+        graph.clear(&mut txn)?;
+        txn.commit()?;
+
+        let txn = graph.read_txn()?;
+        assert_eq!(graph.vertex_count(&txn)?, 0);
+        assert_eq!(graph.edge_count(&txn)?, 0);
+        assert_eq!(graph.hexstore_db.len(&txn)?, 0);
+        assert_eq!(graph.parameters_db.len(&txn)?, 0);
+        assert_eq!(graph.parameters_idx_db.len(&txn)?, 0);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_read_traversal(graph: Graph<String, String, ()>) -> Result<()> {
+        let (phineas, ferb) = graph.write_traversal(|g, txn| {
+            let phineas = g.add_v("phineas".into()).next(txn)?;
+            let ferb = g.add_v("ferb".into()).next(txn)?;
+            g.add_e("brother".into())
+                .from(&phineas)?
+                .to(&ferb)?
+                .next(txn)?;
+            Ok((phineas, ferb))
+        })?;
+        let p = Id::try_from(&phineas)?;
+
+        let vs = graph.read_traversal(|g, txn| g.v(()).to_list(txn))?;
+        assert_eq!(vs, vec![phineas, ferb.clone()]);
+        let out = graph.read_traversal(|g, txn| g.v(p).out(()).next(txn))?;
+        assert_eq!(out, ferb);
+        let edges = graph.read_traversal(|g, txn| g.e(()).in_v().to_list(txn))?;
+        assert_eq!(edges, vec![ferb]);
+
+        let write = graph.read_traversal(|g, txn| g.v(p).property((), PValue::None).to_list(txn));
+        assert!(matches!(write, Err(Error::WriteInReadTxn)));
+        let write = graph.read_traversal(|g, txn| g.v(()).add_v("candace".into()).next(txn));
+        assert!(matches!(write, Err(Error::WriteInReadTxn)));
+        assert_eq!(graph.read_txn().map(|txn| graph.vertex_count(&txn))??, 2);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_concurrent_read_traversals(graph: Graph<String, String, ()>) -> Result<()> {
+        graph.write_traversal(|g, txn| g.add_v("phineas".into()).next(txn))?;
+        let graph = Arc::new(graph);
+
+        // Readers neither wait for nor see an open writer
+        let mut txn = graph.write_txn()?;
+        graph.put_vertex(&mut txn, &Vertex::new("ferb".into()))?;
+        let readers: Vec<JoinHandle<Result<usize>>> = (0..4)
+            .map(|_| {
+                let graph = graph.clone();
+                std::thread::spawn(move || {
+                    graph.read_traversal(|g, txn| Ok(g.v(()).to_list(txn)?.len()))
+                })
+            })
+            .collect();
+        for reader in readers {
+            assert_eq!(reader.join().unwrap()?, 1);
+        }
+        txn.commit()?;
+
+        assert_eq!(
+            graph.read_traversal(|g, txn| g.v(()).to_list(txn))?.len(),
+            2
+        );
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_terminators(graph: Graph<String, String, ()>) -> Result<()> {
+        let (phineas, ferb) = graph.write_traversal(|g, txn| {
+            let phineas = g.add_v("phineas".into()).next(txn)?;
+            let ferb = g.add_v("ferb".into()).next(txn)?;
+            g.add_e("brother".into())
+                .from(&phineas)?
+                .to(&ferb)?
+                .iterate(txn)?;
+            g.add_e("friend".into())
+                .from(&phineas)?
+                .to(&ferb)?
+                .iterate(txn)?;
+            Ok((phineas, ferb))
+        })?;
+        let p = Id::try_from(&phineas)?;
+
+        graph.read_traversal(|g, txn| {
+            let mut iter = g.v(()).iter(txn)?;
+            assert_eq!(iter.next().transpose()?, Some(phineas.clone()));
+            assert_eq!(iter.next().transpose()?, Some(ferb.clone()));
+            assert!(iter.next().is_none());
+
+            assert!(g.v(p).out(()).has_next(txn)?);
+            assert!(!g.v(p).in_(()).has_next(txn)?);
+            assert_eq!(
+                g.v(p).out(()).to_list(txn)?,
+                vec![ferb.clone(), ferb.clone()]
+            );
+            assert_eq!(g.v(p).out(()).to_set(txn)?, vec![ferb.clone()]);
+            Ok(())
+        })?;
+
+        // has_next doesn't swallow errors, and iterate still runs writes
+        let has_next = graph.read_traversal(|g, txn| g.v(p).add_v("candace".into()).has_next(txn));
+        assert!(matches!(has_next, Err(Error::WriteInReadTxn)));
+        graph.write_traversal(|g, txn| g.v(p).property((), PValue::Bool(true)).iterate(txn))?;
+        let props = graph.read_traversal(|g, txn| g.v(()).has_key([()]).to_list(txn))?;
+        assert_eq!(props.len(), 1);
+
+        // Checking a write traversal for results runs its writes once
+        let candace = graph.write_traversal(|g, txn| {
+            let mut results = g.add_v("candace".into()).has_next(txn)?;
+            assert!(results.has_next()?);
+            assert!(results.has_next()?);
+            let candace = results.next().transpose()?;
+            assert!(!results.has_next()?);
+            Ok(candace)
+        })?;
+        let vs = graph.read_traversal(|g, txn| g.v(()).to_list(txn))?;
+        assert_eq!(vs.len(), 3);
+        assert_eq!(candace.as_ref(), vs.last());
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_reducing_traversal(graph: Graph<String, String, ()>) -> Result<()> {
+        let (phineas, ferb) = graph.write_traversal(|g, txn| {
+            let phineas = g.add_v("phineas".into()).next(txn)?;
+            let ferb = g.add_v("ferb".into()).next(txn)?;
+            let candace = g.add_v("candace".into()).next(txn)?;
+            for sibling in [&ferb, &candace] {
+                g.add_e("sibling".into())
+                    .from(&phineas)?
+                    .to(sibling)?
+                    .iterate(txn)?;
+            }
+            Ok((phineas, ferb))
+        })?;
+
+        graph.read_traversal(|g, txn| {
+            assert_eq!(g.v(()).count().next(txn)?, PValue::I64(3));
+            assert_eq!(g.e(()).count().next(txn)?, PValue::I64(2));
+            assert_eq!(g.v(()).v(()).count().next(txn)?, PValue::I64(9));
+            assert_eq!(g.v(()).out(()).count().next(txn)?, PValue::I64(2));
+            assert_eq!(
+                g.v(()).has_label(["ferb".to_string()]).count().next(txn)?,
+                PValue::I64(1)
+            );
+            assert_eq!(g.v(()).has_key([()]).count().next(txn)?, PValue::I64(0));
+
+            assert_eq!(g.v(()).out(()).count().sum().next(txn)?, PValue::I64(2));
+            assert_eq!(g.v(()).count().mean().next(txn)?, PValue::Double(3.0));
+            // Elements compare by id
+            assert_eq!(g.v(()).min().next(txn)?, phineas);
+            assert_eq!(g.v(()).out(()).min().next(txn)?, ferb);
+            assert_eq!(g.v(()).max().to_list(txn)?.len(), 1);
+            Ok(())
+        })?;
+
+        // Folding nothing still gives a list, the other reductions give nothing
+        graph.read_traversal(|g, txn| {
+            let folded = g.v(()).fold().next(txn)?;
+            match &folded {
+                PValue::List(vertices) => assert_eq!(vertices.len(), 3),
+                other => panic!("expected a list, got {:?}", other),
+            }
+            assert_eq!(g.v(()).fold().unfold().to_list(txn)?, g.v(()).to_list(txn)?);
+            assert_eq!(
+                g.v(()).has_key([()]).fold().next(txn)?,
+                PValue::List(vec![])
+            );
+            assert!(!g.v(()).has_key([()]).sum().has_next(txn)?);
+            assert!(g.v(()).sum().next(txn).is_err());
+            Ok(())
+        })?;
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_paging_traversal(graph: Graph<String, String, ()>) -> Result<()> {
+        let vertices = graph.write_traversal(|g, txn| {
+            (0..10)
+                .map(|i| g.add_v(format!("v{}", i)).next(txn))
+                .collect::<Result<Vec<_>>>()
+        })?;
+
+        graph.read_traversal(|g, txn| {
+            assert_eq!(g.v(()).limit(3).to_list(txn)?, vertices[..3]);
+            assert_eq!(g.v(()).range(2, 5).to_list(txn)?, vertices[2..5]);
+            assert_eq!(g.v(()).skip(8).to_list(txn)?, vertices[8..]);
+            assert_eq!(g.v(()).tail(2).to_list(txn)?, vertices[8..]);
+            assert_eq!(g.v(()).tail(20).to_list(txn)?, vertices);
+            assert!(g.v(()).tail(0).to_list(txn)?.is_empty());
+            assert!(g.v(()).range(5, 2).to_list(txn)?.is_empty());
+            assert_eq!(g.v(()).skip(2).limit(2).count().next(txn)?, PValue::I64(2));
+
+            // Page through four at a time, picking up after the last vertex of each page
+            let mut pages = vec![g.v(()).limit(4).to_list(txn)?];
+            while let Some(last) = pages.last().and_then(|page| page.last()) {
+                let page = g.v(()).after(last)?.limit(4).to_list(txn)?;
+                pages.push(page);
+            }
+            assert_eq!(
+                pages.iter().map(Vec::len).collect::<Vec<_>>(),
+                vec![4, 4, 2, 0]
+            );
+            assert_eq!(pages.concat(), vertices);
+            // Anywhere else, after filters
+            assert_eq!(
+                g.v(()).skip(0).after(&vertices[7])?.to_list(txn)?,
+                vertices[8..]
+            );
+            Ok(())
+        })?;
+
+        // Neither limit nor after read as far as the corrupt record
+        let corrupt = Id::try_from(&vertices[5])?;
+        let mut txn = graph.write_txn()?;
+        graph
+            .vertex_db
+            .as_polymorph()
+            .put::<_, Id, ByteSlice>(&mut txn, &corrupt, &[0xFF; 3])?;
+        txn.commit()?;
+        graph.read_traversal(|g, txn| {
+            assert_eq!(g.v(()).limit(5).to_list(txn)?, vertices[..5]);
+            assert_eq!(g.v(()).after(&vertices[5])?.to_list(txn)?, vertices[6..]);
+            assert!(matches!(
+                g.v(()).to_list(txn),
+                Err(Error::Corrupt(VERTICES, Some(id))) if id == corrupt
+            ));
+            Ok(())
+        })?;
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_order_traversal(graph: Graph<String, String, ()>) -> Result<()> {
+        let ages = vec![
+            ("a", Some(PValue::I32(30))),
+            ("b", Some(PValue::Double(12.5))),
+            ("c", Some(PValue::String("old".into()))),
+            ("d", None),
+            ("e", Some(PValue::List(vec![]))),
+            ("f", Some(PValue::I64(5))),
+            ("b", Some(PValue::I32(40))),
+        ];
+        let vs = graph.write_traversal(|g, txn| {
+            ages.into_iter()
+                .map(|(name, age)| match age {
+                    Some(age) => g.add_v(name.into()).property((), age).next(txn),
+                    None => g.add_v(name.into()).next(txn),
+                })
+                .collect::<Result<Vec<_>>>()
+        })?;
+        let pick = |idx: &[usize]| idx.iter().map(|&i| vs[i].clone()).collect::<Vec<_>>();
+
+        graph.read_traversal(|g, txn| {
+            // Straight off the value index, and sorted in memory
+            let by_age = pick(&[5, 1, 0, 6, 2, 4]);
+            assert_eq!(g.v(()).order().by(()).to_list(txn)?, by_age);
+            let sorted = g.v(()).has_key([()]).order().by(());
+            assert_eq!(sorted.to_list(txn)?, by_age);
+            let desc = g.v(()).order().by_order((), Order::Desc).to_list(txn)?;
+            assert_eq!(desc, by_age.into_iter().rev().collect::<Vec<_>>());
+
+            assert_eq!(g.v(()).order().to_list(txn)?, vs);
+            assert_eq!(
+                g.v(())
+                    .order()
+                    .by_order(By::Id, Order::Desc)
+                    .limit(2)
+                    .to_list(txn)?,
+                pick(&[6, 5])
+            );
+            assert_eq!(
+                g.v(()).order().by(By::Label).to_list(txn)?,
+                pick(&[0, 1, 6, 2, 3, 4, 5])
+            );
+            assert_eq!(
+                g.v(())
+                    .order()
+                    .by_order(By::Label, Order::Desc)
+                    .by_order((), Order::Desc)
+                    .to_list(txn)?,
+                pick(&[5, 4, 2, 6, 1, 0])
+            );
+
+            let mut shuffled = g
+                .v(())
+                .order()
+                .by_order(By::Value, Order::Shuffle)
+                .to_list(txn)?;
+            shuffled.sort_by(PValue::total_cmp);
+            assert_eq!(shuffled, vs);
+            assert!(g.v(()).order().by(By::Id).fold().unfold().has_next(txn)?);
+            Ok(())
+        })?;
+
+        // Values the index can't hold are only read once the indexed ones run out
+        let unindexed = Id::try_from(&vs[4])?;
+        let mut txn = graph.write_txn()?;
+        graph
+            .vertex_db
+            .as_polymorph()
+            .put::<_, Id, ByteSlice>(&mut txn, &unindexed, &[0xFF; 3])?;
+        txn.commit()?;
+        graph.read_traversal(|g, txn| {
+            let ordered = || g.v(()).order().by(());
+            assert_eq!(ordered().limit(5).to_list(txn)?, pick(&[5, 1, 0, 6, 2]));
+            assert!(matches!(ordered().to_list(txn), Err(Error::Corrupt(..))));
+            Ok(())
+        })?;
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_order_large_numbers(graph: Graph<String, String, ()>) -> Result<()> {
+        let big = 1_i64 << 53;
+        let ages = vec![
+            PValue::I64(big + 1),
+            PValue::I32(3),
+            PValue::I64(i64::MAX),
+            PValue::Double(big as f64),
+            PValue::I64(big + 2),
+            PValue::Double(3.0),
+            PValue::I64(big),
+            PValue::I64(-1),
+        ];
+        graph.write_traversal(|g, txn| {
+            for age in ages {
+                g.add_v("a".into()).property((), age).iterate(txn)?;
+            }
+            Ok(())
+        })?;
+
+        // Streamed off the index in the same order as sorted in memory, ties included
+        graph.read_traversal(|g, txn| {
+            for order in &[Order::Asc, Order::Desc] {
+                let streamed = g.v(()).order().by_order((), *order).to_list(txn)?;
+                let sorted = g.v(()).has_key([()]).order().by_order((), *order);
+                assert_eq!(streamed, sorted.to_list(txn)?);
+            }
+            let ages = g.v(()).order().by(()).values(()).to_list(txn)?;
+            assert_eq!(ages[5], PValue::I64(big + 1));
+            assert_eq!(ages[6], PValue::I64(big + 2));
+            Ok(())
+        })?;
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_grouping_traversal(graph: Graph<String, String, ()>) -> Result<()> {
+        let people = vec![
+            ("person", Some(PValue::I32(30))),
+            ("person", Some(PValue::I32(40))),
+            ("software", Some(PValue::I32(30))),
+            ("person", None),
+        ];
+        let vs = graph.write_traversal(|g, txn| {
+            people
+                .into_iter()
+                .map(|(label, age)| match age {
+                    Some(age) => g.add_v(label.into()).property((), age).next(txn),
+                    None => g.add_v(label.into()).next(txn),
+                })
+                .collect::<Result<Vec<_>>>()
+        })?;
+        let pick = |idx: &[usize]| idx.iter().map(|&i| vs[i].clone()).collect::<Vec<_>>();
+        let person = || PValue::VertexLabel("person".to_string());
+        let software = || PValue::VertexLabel("software".to_string());
+
+        graph.read_traversal(|g, txn| {
+            assert_eq!(
+                g.v(()).group_count().by(By::Label).next(txn)?,
+                PValue::Entries(vec![
+                    (person(), PValue::I64(3)),
+                    (software(), PValue::I64(1))
+                ])
+            );
+            assert_eq!(
+                g.v(()).group().by(By::Label).by(()).next(txn)?,
+                PValue::Entries(vec![
+                    (
+                        person(),
+                        PValue::List(vec![PValue::I32(30), PValue::I32(40)])
+                    ),
+                    (software(), PValue::List(vec![PValue::I32(30)])),
+                ])
+            );
+            assert_eq!(
+                g.v(()).group().by(()).next(txn)?,
+                PValue::Entries(vec![
+                    (
+                        PValue::I32(30),
+                        PValue::List(vec![vs[0].clone(), vs[2].clone()])
+                    ),
+                    (PValue::I32(40), PValue::List(vec![vs[1].clone()])),
+                ])
+            );
+            assert_eq!(
+                g.v(())
+                    .group_count()
+                    .by(By::Label)
+                    .unfold()
+                    .count()
+                    .next(txn)?,
+                PValue::I64(2)
+            );
+            assert_eq!(
+                g.v(()).limit(0).group_count().next(txn)?,
+                PValue::Entries(vec![])
+            );
+
+            assert_eq!(g.v(()).dedup().to_list(txn)?, vs);
+            assert_eq!(g.v(()).dedup().by(By::Label).to_list(txn)?, pick(&[0, 2]));
+            assert_eq!(g.v(()).dedup().by(()).to_list(txn)?, vs[..2]);
+            assert_eq!(
+                g.v(())
+                    .fold()
+                    .unfold()
+                    .dedup()
+                    .by(By::Id)
+                    .count()
+                    .next(txn)?,
+                PValue::I64(4)
+            );
+            assert!(g
+                .v(())
+                .group_count()
+                .by(By::Label)
+                .dedup()
+                .by(By::Label)
+                .next(txn)
+                .is_err());
+            Ok(())
+        })?;
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_element_traversal(graph: Graph<String, String, ()>) -> Result<()> {
+        let (a, b, e) = graph.write_traversal(|g, txn| {
+            let a = g
+                .add_v("person".into())
+                .property((), PValue::I32(30))
+                .next(txn)?;
+            let b = g.add_v("person".into()).next(txn)?;
+            let e = g
+                .add_e("knows".into())
+                .from(&a)?
+                .to(&b)?
+                .property((), PValue::Double(0.5))
+                .next(txn)?;
+            Ok((Id::try_from(&a)?, Id::try_from(&b)?, Edge::from_pvalue(e)?))
+        })?;
+        assert_eq!(e.from(), a);
+        assert_eq!(e.to(), b);
+        assert_eq!(e.get_param(&()), Some(&PValue::Double(0.5)));
+
+        graph.read_traversal(|g, txn| {
+            let token = |token: &str| PValue::Token(token.to_string());
+            let age = || PValue::Map(once(((), PValue::I32(30))).collect());
+            let age_property = PValue::Property((), Box::new(PValue::I32(30)));
+            let person = || PValue::VertexLabel("person".to_string());
+
+            assert_eq!(g.v(()).values(()).to_list(txn)?, vec![PValue::I32(30)]);
+            assert_eq!(g.v(()).values([()]).to_list(txn)?, vec![PValue::I32(30)]);
+            assert_eq!(g.e(()).values(()).to_list(txn)?, vec![PValue::Double(0.5)]);
+            assert_eq!(g.v(()).properties(()).to_list(txn)?, vec![age_property]);
+            assert_eq!(
+                g.v(()).value_map(false).to_list(txn)?,
+                vec![age(), PValue::Map(HashMap::new())]
+            );
+            assert_eq!(
+                g.v(a).value_map(true).next(txn)?,
+                PValue::Entries(vec![
+                    (token("id"), PValue::Id(a)),
+                    (token("label"), person()),
+                    (PValue::Key(()), PValue::I32(30)),
+                ])
+            );
+            assert_eq!(
+                g.v(b).element_map().next(txn)?,
+                PValue::Entries(vec![
+                    (token("id"), PValue::Id(b)),
+                    (token("label"), person())
+                ])
+            );
+            assert_eq!(
+                g.e(()).element_map().next(txn)?,
+                PValue::Entries(vec![
+                    (token("id"), PValue::Id(e.get_id().unwrap())),
+                    (token("label"), PValue::EdgeLabel("knows".to_string())),
+                    (token("IN"), PValue::Id(b)),
+                    (token("OUT"), PValue::Id(a)),
+                    (PValue::Key(()), PValue::Double(0.5)),
+                ])
+            );
+            assert_eq!(
+                g.v(()).id().to_list(txn)?,
+                vec![PValue::Id(a), PValue::Id(b)]
+            );
+            assert_eq!(g.v(()).label().dedup().to_list(txn)?, vec![person()]);
+            assert!(g.v(()).values(()).label().next(txn).is_err());
+            assert!(g.v(()).id().values(()).next(txn).is_err());
+            Ok(())
+        })?;
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_path_traversal(graph: Graph<String, String, ()>) -> Result<()> {
+        let (a, b, c) = graph.write_traversal(|g, txn| {
+            let a = g.add_v("a".into()).next(txn)?;
+            let b = g.add_v("b".into()).next(txn)?;
+            let c = g.add_v("c".into()).next(txn)?;
+            for (from, to) in [(&a, &b), (&b, &a), (&b, &c)].iter() {
+                g.add_e("knows".into()).from(*from)?.to(*to)?.iterate(txn)?;
+            }
+            Ok((a, b, c))
+        })?;
+
+        graph.read_traversal(|g, txn| {
+            let start = || g.v(Id::try_from(&a).unwrap());
+            let paths = start()
+                .as_("start")
+                .out(())
+                .out(())
+                .path()
+                .to_list(txn)?
+                .into_iter()
+                .map(Path::from_pvalue)
+                .collect::<Result<Vec<_>>>()?;
+            assert_eq!(paths.len(), 2);
+            let objects = paths
+                .iter()
+                .map(|p| p.objects().to_vec())
+                .collect::<Vec<_>>();
+            assert!(objects.contains(&vec![a.clone(), b.clone(), a.clone()]));
+            assert!(objects.contains(&vec![a.clone(), b.clone(), c.clone()]));
+            assert!(paths.iter().all(|p| p.get("start") == Some(&a)));
+
+            let simple = start().out(()).out(()).simple_path().to_list(txn)?;
+            assert_eq!(simple, vec![c.clone()]);
+            let cyclic = start().out(()).out(()).cyclic_path().to_list(txn)?;
+            assert_eq!(cyclic, vec![a.clone()]);
+
+            assert_eq!(
+                start()
+                    .as_("x")
+                    .out(())
+                    .as_("y")
+                    .select(["x"])
+                    .to_list(txn)?,
+                vec![a.clone()]
+            );
+            assert_eq!(
+                start()
+                    .as_("x")
+                    .out(())
+                    .as_("y")
+                    .select(["x", "y"])
+                    .next(txn)?,
+                PValue::Entries(vec![
+                    (PValue::String("x".into()), a.clone()),
+                    (PValue::String("y".into()), b.clone()),
+                ])
+            );
+            // The last object with a label wins, and missing labels drop the traverser
+            let last = start()
+                .as_("x")
+                .out(())
+                .as_("x")
+                .select(["x"])
+                .to_list(txn)?;
+            assert_eq!(last, vec![b.clone()]);
+            assert!(!start().out(()).select(["nope"]).has_next(txn)?);
+            assert!(!start().as_("x").select(["x", "nope"]).has_next(txn)?);
+
+            // Reducing steps start a new path
+            let counted = start().out(()).count().path().next(txn)?;
+            assert_eq!(Path::from_pvalue(counted)?.objects(), &[PValue::I64(1)]);
+            let injected = g.inject(vec![PValue::I32(1)]).path().next(txn)?;
+            assert_eq!(Path::from_pvalue(injected)?.objects(), &[PValue::I32(1)]);
+
+            // Paths are kept for child traversals that read them
+            let child = start().out(()).union([__::path()]).to_list(txn)?;
+            let child = child.into_iter().map(Path::from_pvalue);
+            assert_eq!(child.collect::<Result<Vec<_>>>()?[0].len(), 2);
+            Ok(())
+        })?;
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_repeat_traversal(graph: Graph<String, String, ()>) -> Result<()> {
+        // a -> b -> c -> d, and d -> b closes a cycle
+        let vs = graph.write_traversal(|g, txn| {
+            let vs = ["pkg", "pkg", "pkg", "leaf"]
+                .iter()
+                .map(|label| g.add_v(label.to_string()).next(txn))
+                .collect::<Result<Vec<_>>>()?;
+            for (from, to) in [(0, 1), (1, 2), (2, 3), (3, 1)].iter() {
+                let e = g.add_e("dependsOn".into()).from(&vs[*from])?.to(&vs[*to])?;
+                e.iterate(txn)?;
+            }
+            Ok(vs)
+        })?;
+        let pick = |idx: &[usize]| idx.iter().map(|&i| vs[i].clone()).collect::<Vec<_>>();
+        let depends_on = || __::out(["dependsOn".to_string()]);
+
+        graph.read_traversal(|g, txn| {
+            let a = || g.v(Id::try_from(&vs[0]).unwrap());
+            assert_eq!(a().repeat(depends_on()).times(2).to_list(txn)?, pick(&[2]));
+            assert_eq!(a().repeat(depends_on()).times(5).to_list(txn)?, pick(&[2]));
+            assert_eq!(
+                a().repeat(depends_on()).times(2).path().next(txn)?,
+                g.v(Id::try_from(&vs[0])?)
+                    .out(())
+                    .out(())
+                    .path()
+                    .next(txn)?
+            );
+            assert_eq!(
+                a().repeat(depends_on())
+                    .until(__::has_label(["leaf".to_string()]))
+                    .to_list(txn)?,
+                pick(&[3])
+            );
+            assert_eq!(
+                a().repeat(depends_on())
+                    .until(__::loops().is(PValue::I64(3)))
+                    .to_list(txn)?,
+                pick(&[3])
+            );
+
+            // Coming back round to b ends the loop instead of cycling forever, once b has
+            // been emitted again
+            assert_eq!(
+                a().repeat(depends_on()).emit().to_list(txn)?,
+                pick(&[1, 2, 3, 1])
+            );
+            assert_eq!(
+                a().repeat(depends_on())
+                    .emit_if(__::has_label(["leaf".to_string()]))
+                    .to_list(txn)?,
+                pick(&[3])
+            );
+            assert!(!a().repeat(depends_on()).has_next(txn)?);
+            assert_eq!(a().repeat(__::start()).emit().to_list(txn)?, pick(&[0]));
+
+            // Getting back to where it started still passes until
+            let b = || g.v(Id::try_from(&vs[1]).unwrap());
+            let back = b()
+                .repeat(depends_on())
+                .until(__::has_id(Id::try_from(&vs[1])?));
+            assert_eq!(back.to_list(txn)?, pick(&[1]));
+
+            // until and emit before repeat are checked before going round too
+            let pkg = || __::has_label(["pkg".to_string()]);
+            assert_eq!(
+                a().until(pkg()).repeat(depends_on()).to_list(txn)?,
+                pick(&[0])
+            );
+            assert_eq!(
+                a().emit().repeat(depends_on()).times(2).to_list(txn)?,
+                pick(&[0, 1, 2])
+            );
+            assert!(matches!(
+                a().emit().out(()).to_list(txn),
+                Err(Error::BadRequest(_))
+            ));
+            assert!(matches!(
+                a().out(()).by(()).to_list(txn),
+                Err(Error::BadRequest(_))
+            ));
+
+            let loops = a().repeat(depends_on()).emit().loops().to_list(txn)?;
+            assert_eq!(loops, vec![PValue::I64(0); 4]);
+            let inner = a()
+                .repeat(depends_on().as_("x").loops().select(["x"]))
+                .times(3);
+            assert_eq!(inner.to_list(txn)?, pick(&[3]));
+
+            assert!(matches!(
+                a().repeat(__::start().add_v("pkg".into())).to_list(txn),
+                Err(Error::WriteInReadTxn)
+            ));
+            Ok(())
+        })?;
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_branch_traversal(graph: Graph<String, String, ()>) -> Result<()> {
+        let (a, b, s) = graph.write_traversal(|g, txn| {
+            let a = g
+                .add_v("person".into())
+                .property((), PValue::I32(30))
+                .next(txn)?;
+            let b = g.add_v("person".into()).next(txn)?;
+            let s = g.add_v("software".into()).next(txn)?;
+            g.add_e("knows".into()).from(&a)?.to(&b)?.iterate(txn)?;
+            g.add_e("created".into()).from(&a)?.to(&s)?.iterate(txn)?;
+            Ok((a, b, s))
+        })?;
+        let knows = || __::out(["knows".to_string()]);
+        let created = || __::out(["created".to_string()]);
+        let software = || __::has_label(["software".to_string()]);
+
+        graph.read_traversal(|g, txn| {
+            let start = || g.v(Id::try_from(&a).unwrap());
+            assert_eq!(
+                start().union([knows(), created()]).to_list(txn)?,
+                vec![b.clone(), s.clone()]
+            );
+            let likes = || __::out(["likes".to_string()]);
+            assert_eq!(
+                start()
+                    .coalesce([likes(), knows(), created()])
+                    .to_list(txn)?,
+                vec![b.clone()]
+            );
+            assert!(!start().coalesce([likes()]).has_next(txn)?);
+            assert_eq!(start().optional(likes()).to_list(txn)?, vec![a.clone()]);
+            assert_eq!(start().optional(knows()).to_list(txn)?, vec![b.clone()]);
+            assert_eq!(
+                g.v(())
+                    .choose(software(), __::start(), __::out(()))
+                    .to_list(txn)?,
+                vec![b.clone(), s.clone(), s.clone()]
+            );
+            assert_eq!(g.v(()).where_(created()).to_list(txn)?, vec![a.clone()]);
+            assert_eq!(
+                g.v(()).not(__::out(())).to_list(txn)?,
+                vec![b.clone(), s.clone()]
+            );
+            assert_eq!(
+                g.v(()).and([knows(), __::has_key([()])]).to_list(txn)?,
+                vec![a.clone()]
+            );
+            assert_eq!(
+                g.v(()).or([software(), knows()]).to_list(txn)?,
+                vec![a.clone(), s.clone()]
+            );
+            assert!(!g.v(()).and([knows(), software()]).has_next(txn)?);
+
+            let get_or_create = g.v(()).coalesce([software(), __::add_v("software".into())]);
+            assert!(matches!(
+                get_or_create.to_list(txn),
+                Err(Error::WriteInReadTxn)
+            ));
+            Ok(())
+        })?;
+
+        // Get or create, without going back to Rust between the read and the write
+        let get_or_create = |label: &str| {
+            graph.write_traversal(|g, txn| {
+                g.v(())
+                    .has_label([label.to_string()])
+                    .fold()
+                    .coalesce([__::unfold(), __::add_v(label.to_string())])
+                    .next(txn)
+            })
+        };
+        assert_eq!(get_or_create("software")?, s);
+        let created = get_or_create("library")?;
+        assert_eq!(get_or_create("library")?, created);
+        graph.read_traversal(|g, txn| {
+            assert_eq!(g.v(()).count().next(txn)?, PValue::I64(4));
+            Ok(())
+        })?;
+
+        // Writes in a child run once per traverser, in the traversal's transaction
+        let tagged = graph.write_traversal(|g, txn| {
+            g.v(())
+                .has_label(["person".to_string()])
+                .optional(__::has_key([()]).property((), PValue::I32(31)))
+                .values(())
+                .to_list(txn)
+        })?;
+        assert_eq!(tagged, vec![PValue::I32(31)]);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_side_effect_traversal(graph: Graph<String, String, ()>) -> Result<()> {
+        let (a, b, s) = graph.write_traversal(|g, txn| {
+            let a = g.add_v("person".into()).next(txn)?;
+            let b = g.add_v("person".into()).next(txn)?;
+            let s = g.add_v("software".into()).next(txn)?;
+            g.add_e("knows".into()).from(&a)?.to(&b)?.iterate(txn)?;
+            g.add_e("created".into()).from(&a)?.to(&s)?.iterate(txn)?;
+            Ok((a, b, s))
+        })?;
+        let all = PValue::List(vec![a.clone(), b.clone(), s.clone()]);
+
+        graph.read_traversal(|g, txn| {
+            // store only sees what has been pulled through it, aggregate sees everything
+            assert_eq!(
+                g.v(()).store("x").limit(1).cap(["x"]).next(txn)?,
+                PValue::List(vec![a.clone()])
+            );
+            assert_eq!(g.v(()).aggregate("x").limit(1).cap(["x"]).next(txn)?, all);
+            assert_eq!(g.v(()).aggregate("x").to_list(txn)?.len(), 3);
+
+            let g = g.with_side_effect("x", PValue::List(vec![PValue::I32(1)]));
+            assert_eq!(
+                g.v(()).limit(1).store("x").cap(["x"]).next(txn)?,
+                PValue::List(vec![PValue::I32(1), a.clone()])
+            );
+            // Each traversal starts from the initial value again
+            assert_eq!(
+                g.v(()).limit(0).cap(["x"]).next(txn)?,
+                PValue::List(vec![PValue::I32(1)])
+            );
+            let g = g.with_side_effect("y", PValue::Set(vec![]));
+            assert_eq!(
+                g.v(()).label().store("y").cap(["y"]).next(txn)?,
+                PValue::Set(vec![
+                    PValue::VertexLabel("person".into()),
+                    PValue::VertexLabel("software".into())
+                ])
+            );
+            assert_eq!(
+                g.v(()).limit(0).cap(["x", "y"]).next(txn)?,
+                PValue::Entries(vec![
+                    (
+                        PValue::String("x".into()),
+                        PValue::List(vec![PValue::I32(1)])
+                    ),
+                    (PValue::String("y".into()), PValue::Set(vec![])),
+                ])
+            );
+            assert!(matches!(
+                g.v(()).cap(["z"]).next(txn),
+                Err(Error::SideEffect(key)) if key == "z"
+            ));
+            let g = g.with_side_effect("z", PValue::I32(0));
+            assert!(matches!(
+                g.v(()).store("z").iterate(txn),
+                Err(Error::InvalidPValue(_))
+            ));
+            Ok(())
+        })?;
+
+        graph.read_traversal(|g, txn| {
+            // Child traversals share the side effects, and sideEffect passes on its input
+            let start = || g.v(Id::try_from(&a).unwrap());
+            assert_eq!(
+                start().side_effect(__::out(()).store("x")).to_list(txn)?,
+                vec![a.clone()]
+            );
+            assert_eq!(
+                start()
+                    .side_effect(__::out(()).store("x"))
+                    .cap(["x"])
+                    .next(txn)?,
+                PValue::List(vec![b.clone(), s.clone()])
+            );
+            Ok(())
+        })?;
+
+        let added = graph.write_traversal(|g, txn| {
+            g.v(())
+                .side_effect(__::add_v("tag".into()).store("tags"))
+                .cap(["tags"])
+                .next(txn)
+        })?;
+        assert!(matches!(&added, PValue::List(tags) if tags.len() == 3));
+        graph.read_traversal(|g, txn| {
+            assert_eq!(g.v(()).count().next(txn)?, PValue::I64(6));
+            Ok(())
+        })?;
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_shape_traversal(tmpdir: TempDir) -> Result<()> {
+        let graph: Graph<String, String, String> = Graph::new(tmpdir.path())?;
+        let name = |n: &str| PValue::String(n.into());
+        let (a, b, s) = graph.write_traversal(|g, txn| {
+            let a = g
+                .add_v("person".into())
+                .property("name".into(), name("alice"))
+                .property("age".into(), PValue::I32(30))
+                .next(txn)?;
+            let b = g
+                .add_v("person".into())
+                .property("name".into(), name("bob"))
+                .property("age".into(), PValue::I32(25))
+                .next(txn)?;
+            let s = g
+                .add_v("software".into())
+                .property("name".into(), name("lib"))
+                .next(txn)?;
+            g.add_e("knows".into()).from(&a)?.to(&b)?.iterate(txn)?;
+            g.add_e("created".into()).from(&a)?.to(&s)?.iterate(txn)?;
+            Ok((a, b, s))
+        })?;
+        let row = |entries: &[(&str, PValue)]| {
+            PValue::Map(
+                entries
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.clone()))
+                    .collect(),
+            )
+        };
+        let people = || ["person".to_string()];
+
+        graph.read_traversal(|g, txn| {
+            assert_eq!(
+                g.v(())
+                    .has_label(people())
+                    .project(["name".to_string(), "friends".into()])
+                    .by("name".to_string())
+                    .by(__::out(["knows".to_string()]).count())
+                    .to_list(txn)?,
+                vec![
+                    row(&[("name", name("alice")), ("friends", PValue::I64(1))]),
+                    row(&[("name", name("bob")), ("friends", PValue::I64(0))]),
+                ]
+            );
+            // The bys cycle, with no bys the value itself, and keys without a value are left
+            // out
+            let start = || g.v(Id::try_from(&a).unwrap());
+            assert_eq!(
+                start()
+                    .project(["x".to_string(), "y".into()])
+                    .by("name".to_string())
+                    .next(txn)?,
+                row(&[("x", name("alice")), ("y", name("alice"))])
+            );
+            assert_eq!(
+                start().project(["self".to_string()]).next(txn)?,
+                row(&[("self", a.clone())])
+            );
+            let start = || g.v(Id::try_from(&s).unwrap());
+            assert_eq!(
+                start()
+                    .project(["age".to_string()])
+                    .by("age".to_string())
+                    .next(txn)?,
+                row(&[])
+            );
+            let age_or_zero = || {
+                __::start().coalesce([
+                    __::values(["age".to_string()]),
+                    __::constant(PValue::I32(0)),
+                ])
+            };
+            assert_eq!(
+                start()
+                    .project(["age".to_string()])
+                    .by(age_or_zero())
+                    .next(txn)?,
+                row(&[("age", PValue::I32(0))])
+            );
+            assert_eq!(
+                g.v(()).order().by(age_or_zero()).to_list(txn)?,
+                vec![s.clone(), b.clone(), a.clone()]
+            );
+
+            assert_eq!(
+                g.v(()).constant(name("x")).to_list(txn)?,
+                vec![name("x"); 3]
+            );
+
+            assert_eq!(
+                g.inject(vec![PValue::I32(1), PValue::I32(2)])
+                    .to_list(txn)?,
+                vec![PValue::I32(1), PValue::I32(2)]
+            );
+            assert!(!g.inject(vec![]).has_next(txn)?);
+            assert_eq!(
+                g.v(Id::try_from(&a).unwrap())
+                    .values(["name".to_string()])
+                    .inject(vec![name("z")])
+                    .to_list(txn)?,
+                vec![name("z"), name("alice")]
+            );
+            assert_eq!(
+                g.inject(vec![PValue::I32(1)]).v(()).count().next(txn)?,
+                PValue::I64(3)
+            );
+
+            assert_eq!(
+                g.inject(vec![PValue::I32(3), PValue::I64(4), PValue::Float(0.5)])
+                    .math("_ * 2 + 1")
+                    .to_list(txn)?,
+                vec![
+                    PValue::Double(7.0),
+                    PValue::Double(9.0),
+                    PValue::Double(2.0)
+                ]
+            );
+            assert_eq!(
+                g.v(())
+                    .as_("p")
+                    .out(["knows".to_string()])
+                    .as_("f")
+                    .math("(p - f) / 2")
+                    .by("age".to_string())
+                    .to_list(txn)?,
+                vec![PValue::Double(2.5)]
+            );
+            assert_eq!(
+                g.v(())
+                    .has_label(people())
+                    .math("_ + 1")
+                    .by(__::values(["age".to_string()]))
+                    .to_list(txn)?,
+                vec![PValue::Double(31.0), PValue::Double(26.0)]
+            );
+            assert!(matches!(
+                g.v(()).math("_ + 1").next(txn),
+                Err(Error::Math(_))
+            ));
+            assert!(matches!(
+                g.v(()).math("p + 1").next(txn),
+                Err(Error::Math(_))
+            ));
+            assert!(matches!(g.v(()).math("1 +").next(txn), Err(Error::Math(_))));
+            Ok(())
+        })?;
+
+        let injected =
+            graph.write_traversal(|g, txn| g.inject(vec![name("carol")]).to_list(txn))?;
+        assert_eq!(injected, vec![name("carol")]);
+
+        // A by traversal that writes would run once per traverser in a read step
+        let writing = graph.write_traversal(|g, txn| {
+            g.v(())
+                .project(["new".to_string()])
+                .by(__::add_v("person".into()))
+                .to_list(txn)
+        });
+        assert!(matches!(writing, Err(Error::BadRequest(_))));
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_drop_traversal(tmpdir: TempDir) -> Result<()> {
+        let graph: Graph<String, String, String> = Graph::new(tmpdir.path())?;
+        let name = |n: &str| PValue::String(n.into());
+        let (a, b, s, knows) = graph.write_traversal(|g, txn| {
+            let a = g
+                .add_v("person".into())
+                .property("name".into(), name("alice"))
+                .property("age".into(), PValue::I32(30))
+                .next(txn)?;
+            let b = g
+                .add_v("person".into())
+                .property("name".into(), name("bob"))
+                .next(txn)?;
+            let s = g
+                .add_v("software".into())
+                .property("name".into(), name("lib"))
+                .next(txn)?;
+            let knows = g.add_e("knows".into()).from(&a)?.to(&b)?.next(txn)?;
+            g.add_e("created".into()).from(&a)?.to(&s)?.iterate(txn)?;
+            g.add_e("uses".into())
+                .from(&b)?
+                .to(&s)?
+                .property("weight".into(), PValue::I32(1))
+                .iterate(txn)?;
+            Ok((a, b, s, knows))
+        })?;
+        let id = |v: &PValue| Id::try_from(v).unwrap();
+
+        graph.read_traversal(|g, txn| {
+            assert!(matches!(
+                g.v(()).drop().iterate(txn),
+                Err(Error::WriteInReadTxn)
+            ));
+            Ok(())
+        })?;
+
+        // Maps of parameters aren't properties, so dropping them leaves the element alone
+        graph.write_traversal(|g, txn| {
+            let params = g.v(id(&a)).value_map(false).drop().iterate(txn);
+            assert!(matches!(params, Err(Error::InvalidPValue(_))));
+            let row = g
+                .v(id(&a))
+                .project(["age".to_string()])
+                .by("age".to_string());
+            assert!(matches!(
+                row.drop().iterate(txn),
+                Err(Error::InvalidPValue(_))
+            ));
+            Ok(())
+        })?;
+        {
+            let txn = graph.read_txn()?;
+            let alice = graph.get_vertex_by_id(&txn, &id(&a))?.unwrap();
+            assert_eq!(alice.get_param(&"age".into()), Some(&PValue::I32(30)));
+            assert_eq!(alice.get_param(&"name".into()), Some(&name("alice")));
+        }
+
+        let dropped = graph.write_traversal(|g, txn| {
+            g.v(id(&a))
+                .properties(["age".to_string()])
+                .drop()
+                .to_list(txn)
+        })?;
+        assert!(dropped.is_empty());
+        graph.write_traversal(|g, txn| {
+            g.e(())
+                .properties(["weight".to_string()])
+                .drop()
+                .iterate(txn)?;
+            g.e(id(&knows)).drop().iterate(txn)
+        })?;
+        {
+            let txn = graph.read_txn()?;
+            let alice = graph.get_vertex_by_id(&txn, &id(&a))?.unwrap();
+            assert_eq!(alice.get_param(&"age".into()), None);
+            assert_eq!(alice.get_param(&"name".into()), Some(&name("alice")));
+            assert_eq!(graph.get_vertices_by_param(&txn, &"age".into())?.count(), 0);
+            assert_eq!(graph.edge_count(&txn)?, 2);
+            assert_eq!(graph.parameters_db.len(&txn)?, 3);
+        }
+        graph.read_traversal(|g, txn| {
+            assert_eq!(g.v(id(&a)).out(()).to_list(txn)?, vec![s.clone()]);
+            assert!(!g.v(id(&b)).in_(()).has_next(txn)?);
+            assert!(!g.e(()).has_key(["weight".to_string()]).has_next(txn)?);
+            Ok(())
+        })?;
+
+        // Vertices take their edges with them
+        graph.write_traversal(|g, txn| {
+            g.v(())
+                .has_label(["person".to_string()])
+                .drop()
+                .iterate(txn)
+        })?;
+        {
+            let txn = graph.read_txn()?;
+            assert_eq!(graph.vertex_count(&txn)?, 1);
+            assert_eq!(graph.edge_count(&txn)?, 0);
+            assert_eq!(graph.hexstore_db.len(&txn)?, 0);
+            let people = graph.get_vertices_by_label(&txn, &"person".into())?;
+            assert_eq!(people.count(), 0);
+            let named = graph.get_vertices_by_param(&txn, &"name".into())?;
+            assert_eq!(named.collect::<Result<Vec<_>>>()?.len(), 1);
+            let alice = graph.get_vertices_by_value(&txn, &"name".into(), &name("alice"))?;
+            assert_eq!(alice.count(), 0);
+            assert_eq!(graph.parameters_db.len(&txn)?, 1);
+            assert_eq!(graph.parameters_idx_db.len(&txn)?, 1);
+            assert_eq!(graph.parameters_value_idx_db.len(&txn)?, 1);
+        }
+        graph.read_traversal(|g, txn| {
+            assert!(!g.v(id(&s)).both_e(()).has_next(txn)?);
+            Ok(())
+        })?;
+
+        let not_element =
+            graph.write_traversal(|g, txn| g.inject(vec![PValue::I32(1)]).drop().iterate(txn));
+        assert!(matches!(not_element, Err(Error::InvalidPValue(_))));
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_merge_traversal(tmpdir: TempDir) -> Result<()> {
+        let graph: Graph<String, String, String> = Graph::new(tmpdir.path())?;
+        let name = |n: &str| PValue::String(n.into());
+        let param = |key: &str, value: PValue| once((key.to_string(), value));
+        let alice = || {
+            MergeV::new()
+                .label("person".into())
+                .property("name".into(), name("alice"))
+        };
+        let merge_alice = |g: &RWTraversalSource<String, String, String>, txn: &mut RwTxn| {
+            g.merge_v(alice())
+                .on_create(param("created", PValue::Bool(true)))
+                .on_match(param("seen", PValue::Bool(true)))
+                .to_list(txn)
+        };
+
+        let created = graph.write_traversal(merge_alice)?;
+        let a = created[0].clone();
+        let vertex = Vertex::from_pvalue(a.clone())?;
+        assert_eq!(
+            vertex.get_param(&"created".into()),
+            Some(&PValue::Bool(true))
+        );
+        assert_eq!(vertex.get_param(&"seen".into()), None);
+        let matched = graph.write_traversal(merge_alice)?;
+        assert_eq!(Id::try_from(&matched[0])?, Id::try_from(&a)?);
+        let vertex = Vertex::from_pvalue(matched[0].clone())?;
+        assert_eq!(vertex.get_param(&"seen".into()), Some(&PValue::Bool(true)));
+
+        let b = graph.write_traversal(|g, txn| {
+            // Numbers match across types, the way the index compares them
+            let by_age = g
+                .merge_v(MergeV::new().property("age".into(), PValue::I64(30)))
+                .to_list(txn);
+            assert!(matches!(by_age, Err(Error::BadRequest(_))));
+            g.v(())
+                .property("age".into(), PValue::I32(30))
+                .iterate(txn)?;
+            assert_eq!(
+                g.merge_v(MergeV::new().property("age".into(), PValue::I64(30)))
+                    .id()
+                    .to_list(txn)?,
+                vec![PValue::Id(Id::try_from(&a)?)]
+            );
+            assert_eq!(
+                g.merge_v(MergeV::new().id(&a)?).id().to_list(txn)?,
+                vec![PValue::Id(Id::try_from(&a)?)]
+            );
+
+            // Once per traverser, so the second finds what the first made
+            let bobs = g
+                .inject(vec![PValue::I32(1), PValue::I32(2)])
+                .merge_v(
+                    MergeV::new()
+                        .label("person".into())
+                        .property("name".into(), name("bob")),
+                )
+                .to_list(txn)?;
+            assert_eq!(bobs.len(), 2);
+            assert_eq!(bobs[0], bobs[1]);
+            assert_eq!(
+                g.merge_v(MergeV::new().label("person".into()))
+                    .on_match(param("seen", PValue::Bool(false)))
+                    .values(["seen".to_string()])
+                    .to_list(txn)?,
+                vec![PValue::Bool(false); 2]
+            );
+
+            let conflict = g
+                .merge_v(alice().property("name".into(), name("carol")))
+                .on_create(param("name", name("dave")))
+                .to_list(txn);
+            assert!(matches!(conflict, Err(Error::BadRequest(_))));
+            Ok(bobs[0].clone())
+        })?;
+
+        let knows = || {
+            MergeE::new()
+                .label("knows".into())
+                .from(&a)
+                .and_then(|merge| merge.to(&b))
+        };
+        let edges = graph.write_traversal(|g, txn| {
+            let made = g
+                .merge_e(knows()?)
+                .on_create(param("since", PValue::I32(2020)))
+                .next(txn)?;
+            let found = g
+                .merge_e(knows()?)
+                .on_create(param("since", PValue::I32(2021)))
+                .next(txn)?;
+            assert_eq!(made, found);
+            assert_eq!(
+                g.merge_e(MergeE::new().label("knows".into()))
+                    .values(["since".to_string()])
+                    .to_list(txn)?,
+                vec![PValue::I32(2020)]
+            );
+            let from_bob = MergeE::new().label("knows".into()).from(&b)?;
+            assert!(matches!(
+                g.merge_e(from_bob).to_list(txn),
+                Err(Error::BadRequest(_))
+            ));
+            assert!(matches!(
+                g.merge_e(MergeE::new().id(&a)?).to_list(txn),
+                Err(Error::BadRequest(_))
+            ));
+
+            // Ids that are taken by something that doesn't match aren't written over
+            let software = MergeV::new().id(&a)?.label("software".into());
+            assert!(matches!(
+                g.merge_v(software).to_list(txn),
+                Err(Error::AlreadyExists(_))
+            ));
+            let likes = MergeE::new().id(&made)?.label("likes".into());
+            assert!(matches!(
+                g.merge_e(likes.from(&a)?.to(&b)?).to_list(txn),
+                Err(Error::AlreadyExists(_))
+            ));
+
+            // and new edges need both of their vertices
+            let gone = g.add_v("person".into()).next(txn)?;
+            g.v(Id::try_from(&gone)?).drop().iterate(txn)?;
+            let dangling = MergeE::new().label("knows".into()).from(&a)?.to(&gone)?;
+            assert!(matches!(
+                g.merge_e(dangling).to_list(txn),
+                Err(Error::NotFound(_))
+            ));
+            let to_edge = MergeE::new().label("knows".into()).from(&a)?.to(&made)?;
+            assert!(matches!(
+                g.merge_e(to_edge).to_list(txn),
+                Err(Error::BadRequest(_))
+            ));
+            assert_eq!(
+                g.v(Id::try_from(&a)?).label().next(txn)?,
+                PValue::VertexLabel("person".into())
+            );
+            g.e(()).count().next(txn)
+        })?;
+        assert_eq!(edges, PValue::I64(1));
+
+        // Everything happens in the caller's transaction, so nothing sticks if it fails
+        let aborted: Result<()> = graph.write_traversal(|g, txn| {
+            g.merge_v(MergeV::new().label("software".into()))
+                .iterate(txn)?;
+            Err(Error::BadRequest("abort"))
+        });
+        assert!(aborted.is_err());
+        graph.read_traversal(|g, txn| {
+            assert_eq!(g.v(()).count().next(txn)?, PValue::I64(2));
+            assert!(matches!(
+                g.v(()).merge_v(alice()).to_list(txn),
+                Err(Error::WriteInReadTxn)
+            ));
+            Ok(())
+        })?;
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_add_edge_traversal(tmpdir: TempDir) -> Result<()> {
+        let graph: Graph<String, String, String> = Graph::new(tmpdir.path())?;
+        let name = |n: &str| PValue::String(n.into());
+        let people = graph.write_traversal(|g, txn| {
+            let mut people = vec![];
+            for n in &["alice", "bob", "carol"] {
+                people.push(
+                    g.add_v("person".into())
+                        .property("name".into(), name(n))
+                        .next(txn)?,
+                );
+            }
+            Ok(people)
+        })?;
+        let ids = people
+            .iter()
+            .map(Id::try_from)
+            .collect::<Result<Vec<_>>>()?;
+        let (a, b, c) = (ids[0], ids[1], ids[2]);
+        let out_names = |g: &RWTraversalSource<String, String, String>,
+                         txn: &mut RwTxn,
+                         from: Id,
+                         label: &str| {
+            g.v(from)
+                .out([label.to_string()])
+                .values(["name".to_string()])
+                .to_list(txn)
+        };
+
+        graph.write_traversal(|g, txn| {
+            // Without a from, the edge goes from the vertex adding it
+            g.v(a).add_e("knows".into()).to(&people[1])?.iterate(txn)?;
+            assert_eq!(out_names(g, txn, a, "knows")?, vec![name("bob")]);
+
+            // Step labels pick out vertices earlier in the path
+            g.v(a)
+                .as_("x")
+                .out(["knows".to_string()])
+                .add_e("known_by".into())
+                .to("x")?
+                .iterate(txn)?;
+            assert_eq!(out_names(g, txn, b, "known_by")?, vec![name("alice")]);
+            g.v(c)
+                .as_("c")
+                .v(b)
+                .add_e("likes".into())
+                .from("c")?
+                .iterate(txn)?;
+            assert_eq!(out_names(g, txn, c, "likes")?, vec![name("bob")]);
+
+            // Traversals run from the traverser and take their first result
+            g.v(())
+                .has_label(["person".to_string()])
+                .add_e("admires".into())
+                .to(__::v(()).has("name".into(), name("carol")))?
+                .iterate(txn)?;
+            assert_eq!(
+                g.v(c).in_(["admires".to_string()]).count().next(txn)?,
+                PValue::I64(3)
+            );
+            g.v(b)
+                .add_e("owns".into())
+                .from(__::v(()).has("name".into(), name("alice")))?
+                .to(__::add_v("thing".into()))?
+                .iterate(txn)?;
+            assert_eq!(
+                g.v(a).out(["owns".to_string()]).label().to_list(txn)?,
+                vec![PValue::VertexLabel("thing".into())]
+            );
+
+            assert!(matches!(
+                g.inject(vec![PValue::I32(1)])
+                    .add_e("knows".into())
+                    .to(&people[1])?
+                    .to_list(txn),
+                Err(Error::BadRequest(_))
+            ));
+            assert!(matches!(
+                g.v(a).add_e("knows".into()).to("nope")?.to_list(txn),
+                Err(Error::BadRequest(_))
+            ));
+            assert!(matches!(
+                g.v(a)
+                    .add_e("knows".into())
+                    .to(__::v(()).has("name".into(), name("dave")))?
+                    .to_list(txn),
+                Err(Error::BadRequest(_))
+            ));
+
+            // Ids have to be of vertices that are still there
+            let gone = g.add_v("person".into()).next(txn)?;
+            g.v(Id::try_from(&gone)?).drop().iterate(txn)?;
+            assert!(matches!(
+                g.v(a).add_e("knows".into()).to(&gone)?.to_list(txn),
+                Err(Error::NotFound(_))
+            ));
+            Ok(())
+        })?;
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_rebuild_indexes_on_open(tmpdir: TempDir) -> Result<()> {
+        let graph: Graph<String, String, String> = Graph::new(tmpdir.path())?;
+        let name = |n: &str| PValue::String(n.into());
+        let a = graph.write_traversal(|g, txn| {
+            let a = g
+                .add_v("person".into())
+                .property("name".into(), name("alice"))
+                .next(txn)?;
+            let b = g.add_v("person".into()).next(txn)?;
+            g.add_e("knows".into()).from(&a)?.to(&b)?.iterate(txn)?;
+            Id::try_from(&a)
+        })?;
+
+        // As a graph written before its indexes were versioned, with rows in the old
+        // layout left in them
+        let mut txn = graph.write_txn()?;
+        graph.vertex_idx_db.clear(&mut txn)?;
+        graph.edge_idx_db.clear(&mut txn)?;
+        graph.hexstore_db.clear(&mut txn)?;
+        graph.parameters_db.clear(&mut txn)?;
+        graph.parameters_idx_db.clear(&mut txn)?;
+        graph.parameters_value_idx_db.clear(&mut txn)?;
+        graph.meta_db.clear(&mut txn)?;
+        for db in [
+            graph.hexstore_db.as_polymorph(),
+            graph.parameters_idx_db.as_polymorph(),
+        ]
+        .iter()
+        {
+            db.put::<_, ByteSlice, ByteSlice>(&mut txn, &[0xFE; 5], &[1])?;
+        }
+        txn.commit()?;
+        drop(graph);
+
+        let graph: Graph<String, String, String> = Graph::new(tmpdir.path())?;
+        graph.read_traversal(|g, txn| {
+            assert_eq!(
+                g.v(())
+                    .has("name".into(), name("alice"))
+                    .id()
+                    .to_list(txn)?,
+                vec![PValue::Id(a)]
+            );
+            assert_eq!(
+                g.v(a).out(["knows".into()]).count().next(txn)?,
+                PValue::I64(1)
+            );
+            assert_eq!(
+                g.v(()).has_label(["person".into()]).count().next(txn)?,
+                PValue::I64(2)
+            );
+            Ok(())
+        })?;
+        let txn = graph.read_txn()?;
+        assert_eq!(graph.vertex_count(&txn)?, 2);
+        assert_eq!(graph.edge_count(&txn)?, 1);
+        assert_eq!(graph.hexstore_db.len(&txn)?, ORDERS.len());
+        assert_eq!(graph.parameters_idx_db.len(&txn)?, 1);
+        drop(txn);
+
+        let mut txn = graph.write_txn()?;
+        graph
+            .meta_db
+            .put(&mut txn, SCHEMA_KEY, &(SCHEMA_VERSION + 1))?;
+        txn.commit()?;
+        drop(graph);
+        assert!(matches!(
+            Graph::<String, String, String>::new(tmpdir.path()),
+            Err(Error::Schema(..))
+        ));
+
+        Ok(())
+    }
 }
@@ -1,10 +1,21 @@
-use heed::{RoIter, RoRange, RoTxn, RwTxn};
-use std::{clone::Clone, fmt::Debug, marker::PhantomData};
+use heed::{types::ByteSlice, RoRange, RoTxn, RwTxn};
+use itertools::Either;
+use std::{
+    clone::Clone,
+    collections::HashMap,
+    fmt::Debug,
+    iter::{once, once_with},
+    marker::PhantomData,
+    ops::{Bound, RangeBounds},
+};
 
-use super::{Graph, IdParam, LabelId, ParamId};
+use super::{
+    codec::encode_value, corrupt, decode, Graph, LabelId, ParamId, PARAMETERS_IDX,
+    PARAMETERS_VALUE_IDX, VERTICES, VERTICES_IDX,
+};
 use crate::{
-    error::Result,
-    graph::{Id, PValue, Type, Vertex, Writable},
+    error::{Error, Result},
+    graph::{edge::Direction, Id, PValue, Type, Vertex, Writable},
 };
 
 impl<V, E, P> Graph<V, E, P>
@@ -14,54 +25,105 @@ where
     P: 'static + Writable + Eq,
 {
     pub fn put_vertex(&self, txn: &mut RwTxn, n: &Vertex<V, E, P>) -> Result<Vertex<V, E, P>> {
-        let n = if n.id.is_some() {
-            let vertex: Option<Vertex<V, E, P>> = self.vertex_db.get(txn, &n.id.unwrap())?;
-            if let Some(vertex) = vertex {
-                self.vertex_idx_db
-                    .delete(txn, &LabelId(vertex.label, vertex.id.unwrap()))?;
-            }
-            n.to_owned()
+        let (n, old) = if let Some(id) = n.id {
+            let old = match self.get_vertex_by_id(txn, &id)? {
+                Some(vertex) => {
+                    self.vertex_idx_db.delete(txn, &LabelId(vertex.label, id))?;
+                    vertex.parameters
+                }
+                None => HashMap::new(),
+            };
+            (n.to_owned(), old)
         } else {
             let id = Id::new(Type::Vertex, &mut self.generator.lock())?;
-            Vertex {
+            let n = Vertex {
                 id: Some(id),
                 ..n.clone()
-            }
+            };
+            (n, HashMap::new())
         };
-        self.vertex_db.put(txn, n.id.as_ref().unwrap(), &n)?;
-        let rev = &LabelId(n.label.clone(), n.id.unwrap());
-        self.vertex_idx_db.put(txn, rev, n.id.as_ref().unwrap())?;
-        n.parameters.iter().for_each(|(k, v)| {
-            self.parameters_db
-                .put(txn, &IdParam(n.id.unwrap(), k.clone()), v)
-                .unwrap();
-            self.parameters_idx_db
-                .put(txn, &ParamId(k.clone(), n.id.unwrap()), &n.id.unwrap())
-                .unwrap();
-        });
+        let id = n.id.ok_or(Error::VertexInvalid)?;
+        self.vertex_db.put(txn, &id, &n)?;
+        self.vertex_idx_db
+            .put(txn, &LabelId(n.label.clone(), id), &id)?;
+        self.put_parameters(txn, &id, &old, &n.parameters)?;
 
         Ok(n)
     }
 
+    /// Removes the vertex with `id` along with its label and parameter index rows.
+    ///
+    /// If `cascade` is set, every edge going to or from the vertex is removed as well,
+    /// otherwise the removal fails with `Error::IncidentEdges` while any such edge exists.
+    pub fn remove_vertex(
+        &self,
+        txn: &mut RwTxn,
+        id: &Id,
+        cascade: bool,
+    ) -> Result<Option<Vertex<V, E, P>>> {
+        let vertex = match self.get_vertex_by_id(txn, id)? {
+            Some(vertex) => vertex,
+            None => return Ok(None),
+        };
+        let edges = self.incident_edge_ids(txn, id)?;
+        if !edges.is_empty() && !cascade {
+            return Err(Error::IncidentEdges(*id));
+        }
+        for edge in edges.iter() {
+            self.remove_edge(txn, edge)?;
+        }
+        self.vertex_db.delete(txn, id)?;
+        self.vertex_idx_db
+            .delete(txn, &LabelId(vertex.label.clone(), *id))?;
+        self.delete_parameters(txn, id, &vertex.parameters)?;
+        Ok(Some(vertex))
+    }
+
+    fn incident_edge_ids(&self, txn: &RoTxn, id: &Id) -> Result<Vec<Id>> {
+        let mut ids: Vec<Id> = self
+            .adjacent(txn, Direction::Out, *id, None, None)?
+            .chain(self.adjacent(txn, Direction::In, *id, None, None)?)
+            .map(|next| next.map(|(edge, _, _)| edge))
+            .collect::<Result<_>>()?;
+        // Self loops show up under both orders
+        ids.sort();
+        ids.dedup();
+        Ok(ids)
+    }
+
     pub fn get_vertex_by_id(&self, txn: &RoTxn, id: &Id) -> Result<Option<Vertex<V, E, P>>> {
-        let vertex = self.vertex_db.get(txn, id)?;
-        Ok(vertex)
+        let bytes = self
+            .vertex_db
+            .as_polymorph()
+            .get::<_, Id, ByteSlice>(txn, id)?;
+        bytes.map(|bytes| decode(VERTICES, *id, bytes)).transpose()
+    }
+
+    /// The vertex an index entry in `db` points at, which has to exist.
+    fn indexed_vertex(
+        &self,
+        txn: &RoTxn,
+        db: &'static str,
+        id: Result<Id>,
+    ) -> Result<Vertex<V, E, P>> {
+        let id = id?;
+        self.get_vertex_by_id(txn, &id)?
+            .ok_or(Error::Corrupt(db, Some(id)))
     }
 
+    /// The vertices with `ids`, skipping any that don't exist.
     pub fn get_vertices_by_ids<'graph, 'txn>(
         &'graph self,
         txn: &'txn RoTxn,
         ids: Vec<Id>,
-    ) -> Result<impl 'txn + Iterator<Item = PValue<V, E, P>>>
+    ) -> Result<impl 'txn + Iterator<Item = Result<PValue<V, E, P>>>>
     where
         'graph: 'txn,
     {
         Ok(ids
             .into_iter()
-            .map(move |id| self.vertex_db.get(txn, &id).ok())
-            .flatten()
-            .flatten()
-            .map(PValue::Vertex))
+            .filter_map(move |id| self.get_vertex_by_id(txn, &id).transpose())
+            .map(|vertex| vertex.map(PValue::Vertex)))
     }
 
     pub fn get_vertices_by_label<'txn>(
@@ -80,29 +142,161 @@ where
         Ok(VertexRange::new(self, txn, iter))
     }
 
-    pub fn get_vertex_by_label<'txn>(
-        &self,
+    /// Vertices that have any value for the parameter `key`, read off the parameter index.
+    pub fn get_vertices_by_param<'txn>(
+        &'txn self,
+        txn: &'txn RoTxn,
+        key: &P,
+    ) -> Result<impl 'txn + Iterator<Item = Result<Vertex<V, E, P>>>> {
+        let range = ParamId(key.clone(), Id::nil(Type::Vertex))
+            ..=ParamId(key.clone(), Id::max(Type::Vertex));
+        let iter: RoRange<ParamId<P>, Id> = self.parameters_idx_db.range(txn, &range)?;
+        Ok(iter.map(move |next| {
+            let id = next.map(|(_, id)| id).map_err(corrupt(PARAMETERS_IDX));
+            self.indexed_vertex(txn, PARAMETERS_IDX, id)
+        }))
+    }
+
+    /// Vertices whose `key` parameter compares equal to `value`, read off the value index.
+    pub fn get_vertices_by_value<'txn>(
+        &'txn self,
+        txn: &'txn RoTxn,
+        key: &P,
+        value: &PValue<V, E, P>,
+    ) -> Result<impl 'txn + Iterator<Item = Result<Vertex<V, E, P>>>> {
+        self.get_vertices_by_value_range(txn, key, value.clone()..=value.clone())
+    }
+
+    /// Vertices whose `key` parameter lies within `range`, in order of that parameter.
+    ///
+    /// Only numbers, dates, strings, tokens and bools are indexed by value, and a range
+    /// bounded by anything else is rejected with `Error::BadRequest`.
+    pub fn get_vertices_by_value_range<'txn, R>(
+        &'txn self,
+        txn: &'txn RoTxn,
+        key: &P,
+        range: R,
+    ) -> Result<impl 'txn + Iterator<Item = Result<Vertex<V, E, P>>>>
+    where
+        R: RangeBounds<PValue<V, E, P>>,
+    {
+        let ids = self.value_range(txn, key, range.start_bound(), range.end_bound())?;
+        let (start, end) = (range.start_bound().cloned(), range.end_bound().cloned());
+        let key = key.clone();
+        Ok(ids
+            .filter(|id| id.as_ref().map_or(true, |id| id.get_type() == Type::Vertex))
+            .map(move |id| self.indexed_vertex(txn, PARAMETERS_VALUE_IDX, id))
+            .filter(move |vertex| {
+                vertex.as_ref().map_or(true, |vertex| {
+                    vertex
+                        .parameters
+                        .get(&key)
+                        .is_some_and(|value| value.in_bounds(start.as_ref(), end.as_ref()))
+                })
+            }))
+    }
+
+    /// Vertices that have a `key` parameter, in `PValue::total_cmp` order of its value, or
+    /// the reverse if `descending` is set. Vertices with equal values stay in id order.
+    ///
+    /// Indexed values come straight off the value index. Anything else sorts after them,
+    /// and is only read and sorted in memory once those run out, or before them when
+    /// descending. Descending order isn't streamed from the index either: every indexed id
+    /// is read up front, though the vertices are still fetched as they're pulled.
+    pub fn get_vertices_ordered_by<'txn>(
+        &'txn self,
         txn: &'txn RoTxn,
-        value: &V,
-    ) -> Result<Option<Vertex<V, E, P>>>
+        key: &P,
+        descending: bool,
+    ) -> Result<impl 'txn + Iterator<Item = Result<Vertex<V, E, P>>>> {
+        let ids: Box<dyn Iterator<Item = Result<Id>>> = if descending {
+            Box::new(self.value_ids_desc(txn, key)?.into_iter().map(Ok))
+        } else {
+            Box::new(self.value_range(txn, key, Bound::Unbounded, Bound::Unbounded)?)
+        };
+        let indexed = ids
+            .filter(|id| id.as_ref().map_or(true, |id| id.get_type() == Type::Vertex))
+            .map(move |id| self.indexed_vertex(txn, PARAMETERS_VALUE_IDX, id));
+        let key = key.clone();
+        let rest = once_with(move || {
+            let unindexed = |vertex: &Result<Vertex<V, E, P>>| {
+                vertex.as_ref().map_or(true, |vertex| {
+                    vertex
+                        .parameters
+                        .get(&key)
+                        .is_some_and(|value| encode_value(value).is_none())
+                })
+            };
+            let mut rest = self
+                .get_vertices_by_param(txn, &key)?
+                .filter(unindexed)
+                .collect::<Result<Vec<_>>>()?;
+            rest.sort_by(|a, b| {
+                let (a, b) = (&a.parameters[&key], &b.parameters[&key]);
+                if descending {
+                    b.total_cmp(a)
+                } else {
+                    a.total_cmp(b)
+                }
+            });
+            Ok(rest)
+        })
+        .flat_map(|rest: Result<Vec<_>>| match rest {
+            Ok(rest) => Either::Left(rest.into_iter().map(Ok)),
+            Err(e) => Either::Right(once(Err(e))),
+        });
+        Ok(if descending {
+            Either::Left(rest.chain(indexed))
+        } else {
+            Either::Right(indexed.chain(rest))
+        })
+    }
+
+    pub fn get_vertex_by_label(&self, txn: &RoTxn, value: &V) -> Result<Option<Vertex<V, E, P>>>
     where
         V: Clone + Debug,
     {
-        Ok(self.get_vertices_by_label(txn, value)?.next())
+        self.get_vertices_by_label(txn, value)?.next().transpose()
     }
 
-    pub fn vertex_count(&self, txn: &RoTxn) -> Result<usize>
-where {
-        assert_eq!(self.vertex_db.len(txn)?, self.vertex_idx_db.len(txn)?);
-        Ok(self.vertex_db.len(txn)?)
+    /// The number of vertices, failing with `Error::CountMismatch` if the label index
+    /// doesn't agree.
+    pub fn vertex_count(&self, txn: &RoTxn) -> Result<usize> {
+        let (count, indexed) = (self.vertex_db.len(txn)?, self.vertex_idx_db.len(txn)?);
+        if count != indexed {
+            return Err(Error::CountMismatch(VERTICES, count, indexed));
+        }
+        Ok(count)
     }
 
     pub fn vertices<'txn>(
         &self,
         txn: &'txn RoTxn,
-    ) -> Result<impl 'txn + Iterator<Item = PValue<V, E, P>>> {
+    ) -> Result<impl 'txn + Iterator<Item = Result<PValue<V, E, P>>>> {
+        self.vertex_range(txn, (Bound::Unbounded, Bound::Unbounded))
+    }
+
+    /// The vertices with ids after `id`, in id order, for resuming a scan where the last
+    /// one left off.
+    pub fn vertices_after<'txn>(
+        &self,
+        txn: &'txn RoTxn,
+        id: &Id,
+    ) -> Result<impl 'txn + Iterator<Item = Result<PValue<V, E, P>>>> {
+        self.vertex_range(txn, (Bound::Excluded(*id), Bound::Unbounded))
+    }
+
+    fn vertex_range<'txn>(
+        &self,
+        txn: &'txn RoTxn,
+        range: (Bound<Id>, Bound<Id>),
+    ) -> Result<VertexIter<'txn, V, E, P>> {
         Ok(VertexIter {
-            iter: self.vertex_db.iter(txn)?,
+            iter: self
+                .vertex_db
+                .as_polymorph()
+                .range::<_, Id, ByteSlice, _>(txn, &range)?,
+            _marker: PhantomData,
         })
     }
 }
@@ -113,7 +307,8 @@ where
     E: 'static + Writable,
     P: 'static + Writable + Eq,
 {
-    pub(crate) iter: RoIter<'txn, Id, Vertex<V, E, P>>,
+    pub(crate) iter: RoRange<'txn, Id, ByteSlice>,
+    _marker: PhantomData<(V, E, P)>,
 }
 
 impl<'txn, V, E, P> Iterator for VertexIter<'txn, V, E, P>
@@ -122,14 +317,14 @@ where
     E: 'static + Writable,
     P: 'static + Writable + Eq,
 {
-    type Item = PValue<V, E, P>;
+    type Item = Result<PValue<V, E, P>>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let next = self.iter.next();
-        match next {
-            Some(Ok(vertex)) => Some(PValue::Vertex(vertex.1)),
-            Some(Err(_)) | None => None,
-        }
+        let (id, bytes) = match self.iter.next()? {
+            Ok(next) => next,
+            Err(e) => return Some(Err(corrupt(VERTICES)(e))),
+        };
+        Some(decode(VERTICES, id, bytes).map(PValue::Vertex))
     }
 }
 
@@ -151,7 +346,7 @@ where
     E: Writable,
     P: Writable + Eq,
 {
-    pub fn new(
+    pub const fn new(
         graph: &'txn Graph<V, E, P>,
         txn: &'txn RoTxn,
         iter: RoRange<'txn, LabelId<V>, Id>,
@@ -171,15 +366,15 @@ where
     E: 'static + Writable,
     P: 'static + Writable + Eq,
 {
-    type Item = Vertex<V, E, P>;
+    type Item = Result<Vertex<V, E, P>>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let next = self.iter.next();
-        match next {
-            Some(Ok(next)) => self.graph.get_vertex_by_id(self.txn, &next.1).unwrap(),
-            Some(Err(_)) => None,
-            None => None,
-        }
+        let id = self
+            .iter
+            .next()?
+            .map(|(_, id)| id)
+            .map_err(corrupt(VERTICES_IDX));
+        Some(self.graph.indexed_vertex(self.txn, VERTICES_IDX, id))
     }
 }
 
@@ -188,7 +383,10 @@ mod tests {
     use rstest::{fixture, rstest};
 
     use super::*;
-    use crate::graph::parameter::{PValue, ToPValue};
+    use crate::graph::{
+        parameter::{PValue, ToPValue},
+        Edge,
+    };
     use tempfile::TempDir;
 
     #[allow(dead_code)]
@@ -209,7 +407,7 @@ mod tests {
     fn test_put(graph: Graph<String, String, ()>) -> Result<()> {
         let vertex = Vertex::new("test".to_string());
         let mut txn = graph.write_txn().unwrap();
-        let returned = graph.put_vertex(&mut txn, &vertex.clone()).unwrap();
+        let returned = graph.put_vertex(&mut txn, &vertex).unwrap();
         txn.commit()?;
         assert_eq!(vertex.id, None);
         assert_ne!(returned.id, None);
@@ -223,7 +421,7 @@ mod tests {
         let vertex = Vertex::new("test".to_string());
 
         let mut txn = graph.write_txn()?;
-        let returned = graph.put_vertex(&mut txn, &vertex.clone())?;
+        let returned = graph.put_vertex(&mut txn, &vertex)?;
         txn.commit()?;
 
         let txn = graph.read_txn()?;
@@ -244,14 +442,14 @@ mod tests {
         let vertex = Vertex::new("test".to_string()).set_param((), PValue::None);
 
         let mut txn = graph.write_txn()?;
-        let returned = graph.put_vertex(&mut txn, &vertex.clone())?;
+        let returned = graph.put_vertex(&mut txn, &vertex)?;
         graph.put_vertex(&mut txn, &Vertex::new("test3".to_string()))?;
         txn.commit()?;
 
         let txn = graph.read_txn()?;
         let fetched = graph
             .get_vertices_by_label(&txn, &vertex.label)?
-            .collect::<Vec<_>>();
+            .collect::<Result<Vec<_>>>()?;
         assert_eq!(fetched.len(), 1);
         let fetch = &fetched[0];
         assert_eq!(fetch.id, returned.id);
@@ -274,7 +472,7 @@ mod tests {
         txn.commit()?;
 
         let txn = graph.read_txn()?;
-        let vertices: Vec<_> = graph.vertices(&txn)?.collect();
+        let vertices: Vec<_> = graph.vertices(&txn)?.collect::<Result<_>>()?;
         assert_eq!(vertices, returned);
 
         Ok(())
@@ -285,9 +483,9 @@ mod tests {
         let vertex = Vertex::new("tester".to_string());
         let mut txn = graph.write_txn()?;
 
-        let mut returned = graph.put_vertex(&mut txn, &vertex.clone())?;
+        let mut returned = graph.put_vertex(&mut txn, &vertex)?;
         returned.label = "testers".to_string();
-        graph.put_vertex(&mut txn, &returned.clone())?;
+        graph.put_vertex(&mut txn, &returned)?;
         txn.commit()?;
 
         let txn = graph.read_txn()?;
@@ -299,4 +497,184 @@ mod tests {
 
         Ok(())
     }
+
+    #[rstest]
+    fn test_put_drops_stale_params(graph: Graph<String, String, ()>) -> Result<()> {
+        let vertex = Vertex::new("test".to_string()).set_param((), PValue::I32(1));
+        let mut txn = graph.write_txn()?;
+        let mut returned = graph.put_vertex(&mut txn, &vertex)?;
+        returned.parameters.clear();
+        graph.put_vertex(&mut txn, &returned)?;
+        txn.commit()?;
+
+        let txn = graph.read_txn()?;
+        assert_eq!(graph.parameters_db.len(&txn)?, 0);
+        assert_eq!(graph.get_vertices_by_param(&txn, &())?.count(), 0);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_get_by_value(graph: Graph<String, String, ()>) -> Result<()> {
+        let mut txn = graph.write_txn()?;
+        let mut ages = vec![];
+        for age in [
+            PValue::I32(3),
+            PValue::Double(10.5),
+            PValue::I64(10),
+            PValue::I32(-1),
+        ] {
+            let vertex = Vertex::new("kid".to_string()).set_param((), age);
+            ages.push(graph.put_vertex(&mut txn, &vertex)?);
+        }
+        graph.put_vertex(
+            &mut txn,
+            &Vertex::new("name".to_string()).set_param((), PValue::String("ferb".into())),
+        )?;
+        let mut moved = graph.put_vertex(
+            &mut txn,
+            &Vertex::new("kid".to_string()).set_param((), PValue::I32(7)),
+        )?;
+        moved.parameters.insert((), PValue::I32(70));
+        graph.put_vertex(&mut txn, &moved)?;
+        txn.commit()?;
+
+        let txn = graph.read_txn()?;
+        let ten: Vec<_> = graph
+            .get_vertices_by_value(&txn, &(), &PValue::Double(10.0))?
+            .collect::<Result<_>>()?;
+        assert_eq!(ten, vec![ages[2].clone()]);
+        assert_eq!(
+            graph
+                .get_vertices_by_value(&txn, &(), &PValue::I32(7))?
+                .count(),
+            0
+        );
+
+        let young: Vec<_> = graph
+            .get_vertices_by_value_range(&txn, &(), PValue::I32(0)..PValue::I32(11))?
+            .collect::<Result<_>>()?;
+        assert_eq!(
+            young,
+            vec![ages[0].clone(), ages[2].clone(), ages[1].clone()]
+        );
+
+        let old: Vec<_> = graph
+            .get_vertices_by_value_range(&txn, &(), PValue::I64(10)..)?
+            .collect::<Result<_>>()?;
+        assert_eq!(old, vec![ages[2].clone(), ages[1].clone(), moved]);
+
+        let names: Vec<_> = graph
+            .get_vertices_by_value_range(&txn, &(), ..=PValue::String("z".into()))?
+            .map(|v| v.map(|v| v.get_label()))
+            .collect::<Result<_>>()?;
+        assert_eq!(names, vec!["name".to_string()]);
+
+        let unindexed = graph.get_vertices_by_value(&txn, &(), &PValue::None);
+        assert!(matches!(unindexed, Err(Error::BadRequest(_))));
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_remove_vertex(graph: Graph<String, String, ()>) -> Result<()> {
+        let vertex = Vertex::new("test".to_string()).set_param((), PValue::None);
+        let mut txn = graph.write_txn()?;
+        let returned = graph.put_vertex(&mut txn, &vertex)?;
+        let id = returned.id.unwrap();
+
+        let removed = graph.remove_vertex(&mut txn, &id, false)?;
+        assert_eq!(removed, Some(returned));
+        assert!(graph.remove_vertex(&mut txn, &id, false)?.is_none());
+        txn.commit()?;
+
+        let txn = graph.read_txn()?;
+        assert_eq!(graph.vertex_count(&txn)?, 0);
+        assert!(graph.get_vertex_by_label(&txn, &vertex.label)?.is_none());
+        assert_eq!(graph.parameters_db.len(&txn)?, 0);
+        assert_eq!(graph.parameters_idx_db.len(&txn)?, 0);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_remove_vertex_cascade(graph: Graph<String, String, ()>) -> Result<()> {
+        let mut txn = graph.write_txn()?;
+        let ferb = graph.put_vertex(&mut txn, &Vertex::new("ferb".to_string()))?;
+        let phineas = graph.put_vertex(&mut txn, &Vertex::new("phineas".to_string()))?;
+        let candace = graph.put_vertex(&mut txn, &Vertex::new("candace".to_string()))?;
+        graph.put_edge(&mut txn, &Edge::new(&ferb, &phineas, "brothers".into())?)?;
+        graph.put_edge(&mut txn, &Edge::new(&candace, &ferb, "siblings".into())?)?;
+        let kept = graph.put_edge(&mut txn, &Edge::new(&candace, &phineas, "siblings".into())?)?;
+
+        let removed = graph.remove_vertex(&mut txn, ferb.id.as_ref().unwrap(), false);
+        assert!(matches!(removed, Err(Error::IncidentEdges(id)) if Some(id) == ferb.id));
+
+        graph.remove_vertex(&mut txn, ferb.id.as_ref().unwrap(), true)?;
+        txn.commit()?;
+
+        let txn = graph.read_txn()?;
+        assert_eq!(graph.vertex_count(&txn)?, 2);
+        assert_eq!(graph.edge_count(&txn)?, 1);
+        assert_eq!(
+            graph.edges(&txn)?.collect::<Result<Vec<_>>>()?,
+            vec![kept.to_pvalue()]
+        );
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_corrupt_records(graph: Graph<String, String, ()>) -> Result<()> {
+        let mut txn = graph.write_txn()?;
+        let ferb = graph.put_vertex(&mut txn, &Vertex::new("ferb".to_string()))?;
+        let phineas = graph.put_vertex(&mut txn, &Vertex::new("phineas".to_string()))?;
+        let (f, p) = (ferb.id.unwrap(), phineas.id.unwrap());
+        graph
+            .vertex_db
+            .as_polymorph()
+            .put::<_, Id, ByteSlice>(&mut txn, &f, &[0xFF; 3])?;
+        txn.commit()?;
+
+        let txn = graph.read_txn()?;
+        let vertices: Vec<_> = graph.vertices(&txn)?.collect();
+        assert!(matches!(vertices[0], Err(Error::Corrupt(VERTICES, Some(id))) if id == f));
+        assert_eq!(vertices[1].as_ref().ok(), Some(&phineas.to_pvalue()));
+        assert!(matches!(
+            graph.get_vertex_by_id(&txn, &f),
+            Err(Error::Corrupt(VERTICES, _))
+        ));
+        assert!(graph
+            .get_vertex_by_label(&txn, &"ferb".to_string())
+            .is_err());
+        drop(txn);
+
+        // A label index row left behind by a missing vertex
+        let mut txn = graph.write_txn()?;
+        graph.vertex_db.delete(&mut txn, &p)?;
+        txn.commit()?;
+
+        let txn = graph.read_txn()?;
+        let found = graph.get_vertex_by_label(&txn, &"phineas".to_string());
+        assert!(matches!(found, Err(Error::Corrupt(VERTICES_IDX, Some(id))) if id == p));
+        assert!(matches!(
+            graph.vertex_count(&txn),
+            Err(Error::CountMismatch(VERTICES, 1, 2))
+        ));
+        drop(txn);
+
+        // A key whose type byte isn't one of ours
+        let mut txn = graph.write_txn()?;
+        graph
+            .vertex_db
+            .as_polymorph()
+            .put::<_, ByteSlice, ByteSlice>(&mut txn, &[0xFF; 17], &[])?;
+        txn.commit()?;
+
+        let txn = graph.read_txn()?;
+        let last = graph.vertices(&txn)?.last();
+        assert!(matches!(last, Some(Err(Error::Corrupt(VERTICES, None)))));
+
+        Ok(())
+    }
 }
@@ -1,5 +1,5 @@
 #![warn(clippy::all, clippy::nursery)]
-#![allow(clippy::complexity::type_complexity)]
+#![allow(clippy::type_complexity)]
 
 pub mod error;
 pub mod graph;